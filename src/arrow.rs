@@ -0,0 +1,97 @@
+//! Columnar node-table export (requires the `arrow` feature).
+
+use std::sync::Arc;
+
+use arrow_array::{Float64Array, RecordBatch, UInt32Array, UInt64Array};
+use arrow_schema::{DataType, Field, Schema};
+
+use crate::{HalfSpaceTrees, Node};
+
+impl HalfSpaceTrees {
+    /// Export every node of every tree as one row of an Arrow record batch.
+    ///
+    /// Rows are grouped by tree and in pre-order within a tree. Schema:
+    ///
+    /// | column        | type    | nullable | meaning                                   |
+    /// |---------------|---------|----------|-------------------------------------------|
+    /// | `tree`        | UInt32  | no       | index of the tree in the forest           |
+    /// | `depth`       | UInt32  | no       | node depth, root = 0                      |
+    /// | `split_dim`   | UInt32  | yes      | split dimension; null for leaves          |
+    /// | `split_value` | Float64 | yes      | split threshold; null for leaves          |
+    /// | `mass`        | Float64 | no       | decayed mass                              |
+    /// | `left`        | UInt64  | yes      | row index of the left child (`x < value`) |
+    /// | `right`       | UInt64  | yes      | row index of the right child              |
+    ///
+    /// Oblique splits are exported by their first dimension and projected threshold only.
+    pub fn to_record_batch(&self) -> RecordBatch {
+        let mut cols = Columns::default();
+        for (i, t) in self.trees.iter().enumerate() {
+            t.root.push_rows(i as u32, &mut cols);
+        }
+        RecordBatch::try_new(
+            Arc::new(node_schema()),
+            vec![
+                Arc::new(UInt32Array::from(cols.tree)),
+                Arc::new(UInt32Array::from(cols.depth)),
+                Arc::new(UInt32Array::from(cols.split_dim)),
+                Arc::new(Float64Array::from(cols.split_value)),
+                Arc::new(Float64Array::from(cols.mass)),
+                Arc::new(UInt64Array::from(cols.left)),
+                Arc::new(UInt64Array::from(cols.right)),
+            ],
+        )
+        .expect("columns match the node schema")
+    }
+}
+
+fn node_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("tree", DataType::UInt32, false),
+        Field::new("depth", DataType::UInt32, false),
+        Field::new("split_dim", DataType::UInt32, true),
+        Field::new("split_value", DataType::Float64, true),
+        Field::new("mass", DataType::Float64, false),
+        Field::new("left", DataType::UInt64, true),
+        Field::new("right", DataType::UInt64, true),
+    ])
+}
+
+#[derive(Default)]
+struct Columns {
+    tree: Vec<u32>,
+    depth: Vec<u32>,
+    split_dim: Vec<Option<u32>>,
+    split_value: Vec<Option<f64>>,
+    mass: Vec<f64>,
+    left: Vec<Option<u64>>,
+    right: Vec<Option<u64>>,
+}
+
+impl Node {
+    /// Append this subtree in pre-order; returns this node's row index.
+    fn push_rows(&self, tree: u32, cols: &mut Columns) -> u64 {
+        let row = cols.tree.len();
+        cols.tree.push(tree);
+        cols.depth.push(self.depth);
+        cols.mass.push(self.mass);
+        match (&self.left, &self.right) {
+            (Some(l), Some(r)) => {
+                cols.split_dim.push(Some(self.split_dim as u32));
+                cols.split_value.push(Some(self.split_val));
+                cols.left.push(None);
+                cols.right.push(None);
+                let li = l.push_rows(tree, cols);
+                let ri = r.push_rows(tree, cols);
+                cols.left[row] = Some(li);
+                cols.right[row] = Some(ri);
+            }
+            _ => {
+                cols.split_dim.push(None);
+                cols.split_value.push(None);
+                cols.left.push(None);
+                cols.right.push(None);
+            }
+        }
+        row as u64
+    }
+}
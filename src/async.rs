@@ -0,0 +1,25 @@
+//! Async batch scoring (requires the `tokio` feature).
+
+use std::future::Future;
+
+use crate::HalfSpaceTrees;
+
+impl HalfSpaceTrees {
+    /// Score `xs` on tokio's blocking thread pool so CPU-bound work does not stall the
+    /// async runtime. The future resolves to the scores in input order.
+    ///
+    /// The forest's trees are cloned up front so the returned future is `'static`;
+    /// prefer fewer, larger batches over many small ones for big forests.
+    /// Must be awaited inside a tokio runtime.
+    pub fn score_batch_async(
+        &self,
+        xs: Vec<Vec<f64>>,
+    ) -> impl Future<Output = Vec<f64>> + Send + 'static {
+        let scorer = self.scoring_clone();
+        async move {
+            tokio::task::spawn_blocking(move || xs.iter().map(|x| scorer.score(x)).collect())
+                .await
+                .expect("scoring task panicked")
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! Isotonic calibration of raw scores into probabilities.
+
+/// Non-decreasing step function from score to probability, fit by pool-adjacent-violators.
+#[derive(Debug, Clone)]
+pub(crate) struct Isotonic {
+    // Lowest score of each pooled block, ascending
+    pub(crate) starts: Vec<f64>,
+    // Fraction of anomalies in each block, non-decreasing
+    pub(crate) values: Vec<f64>,
+}
+
+impl Isotonic {
+    pub(crate) fn fit(scores: &[f64], labels: &[bool]) -> Self {
+        assert_eq!(scores.len(), labels.len(), "one label per score");
+        assert!(!scores.is_empty(), "scores must not be empty");
+        let mut pairs: Vec<(f64, f64)> = scores
+            .iter()
+            .zip(labels)
+            .map(|(&s, &l)| (s, if l { 1.0 } else { 0.0 }))
+            .collect();
+        pairs.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        // Blocks as (start score, label sum, count); merge backwards while a block's mean
+        // falls below its predecessor's.
+        let mut blocks: Vec<(f64, f64, f64)> = Vec::new();
+        for (s, y) in pairs {
+            match blocks.last_mut() {
+                // Equal scores must share a value.
+                Some(last) if last.0 == s => {
+                    last.1 += y;
+                    last.2 += 1.0;
+                }
+                _ => blocks.push((s, y, 1.0)),
+            }
+            while blocks.len() > 1 {
+                let (b, a) = (blocks[blocks.len() - 1], blocks[blocks.len() - 2]);
+                if a.1 / a.2 <= b.1 / b.2 {
+                    break;
+                }
+                blocks.pop();
+                let a = blocks.last_mut().unwrap();
+                a.1 += b.1;
+                a.2 += b.2;
+            }
+        }
+        Self {
+            starts: blocks.iter().map(|b| b.0).collect(),
+            values: blocks.iter().map(|b| b.1 / b.2).collect(),
+        }
+    }
+
+    /// Value of the last block starting at or below `score`; flat beyond either end.
+    pub(crate) fn apply(&self, score: f64) -> f64 {
+        let i = self.starts.partition_point(|&s| s <= score);
+        self.values[i.saturating_sub(1)]
+    }
+}
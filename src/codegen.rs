@@ -0,0 +1,138 @@
+//! Source-code exporters for trained trees.
+
+use std::fmt::Write;
+
+use crate::{DepthWeighting, HalfSpaceTree, Node, ScoreMethod};
+
+impl<M> HalfSpaceTree<M> {
+    /// Emit a standalone, dependency-free Rust function `pub fn {fn_name}(x: &[f64]) -> f64`
+    /// that reproduces this tree's current score with hardcoded splits. Each leaf returns
+    /// its precomputed score, annotated with the leaf mass it was derived from.
+    /// The output is a snapshot: later inserts or decays are not reflected.
+    pub fn to_rust_source(&self, fn_name: &str) -> String {
+        let mut src = String::new();
+        writeln!(
+            src,
+            "/// Generated half-space tree scorer over {} dimensions.",
+            self.n_dims
+        )
+        .unwrap();
+        writeln!(src, "pub fn {fn_name}(x: &[f64]) -> f64 {{").unwrap();
+        let mut region = self.bounds.clone();
+        self.root
+            .write_rust(&mut src, 1, self, &mut region, (0.0, 0.0));
+        writeln!(src, "}}").unwrap();
+        src
+    }
+}
+
+impl<M> HalfSpaceTree<M> {
+    /// Human-readable dump of this tree's decisions as nested `if/else` blocks, with
+    /// each leaf annotated by its mass. Numbers are rounded for reading, not for reuse.
+    pub fn to_pseudocode(&self) -> String {
+        let mut out = String::new();
+        self.root.write_pseudocode(&mut out, 0);
+        out
+    }
+}
+
+impl Node {
+    fn write_pseudocode(&self, out: &mut String, indent: usize) {
+        let pad = "  ".repeat(indent);
+        match (&self.left, &self.right) {
+            (Some(l), Some(r)) => {
+                match &self.oblique {
+                    Some(o) => writeln!(
+                        out,
+                        "{pad}if {:.4} * x[{}] + {:.4} * x[{}] < {:.4} {{",
+                        o.w[0], o.dims[0], o.w[1], o.dims[1], self.split_val
+                    ),
+                    None => writeln!(
+                        out,
+                        "{pad}if x[{}] < {:.4} {{",
+                        self.split_dim, self.split_val
+                    ),
+                }
+                .unwrap();
+                l.write_pseudocode(out, indent + 1);
+                writeln!(out, "{pad}}} else {{").unwrap();
+                r.write_pseudocode(out, indent + 1);
+                writeln!(out, "{pad}}}").unwrap();
+            }
+            _ => writeln!(
+                out,
+                "{pad}leaf (depth {}, mass {:.4})",
+                self.depth, self.mass
+            )
+            .unwrap(),
+        }
+    }
+
+    /// `acc` carries the harmonic numerator/denominator from the ancestors.
+    fn write_rust<M>(
+        &self,
+        src: &mut String,
+        indent: usize,
+        tree: &HalfSpaceTree<M>,
+        region: &mut [(f64, f64)],
+        acc: (f64, f64),
+    ) {
+        let max_depth = tree.max_depth;
+        let pad = "    ".repeat(indent);
+        let w = 1.0 / ((max_depth - self.depth) as f64 + 1.0);
+        let acc = (acc.0 + w * self.rarity(), acc.1 + w);
+        match (&self.left, &self.right) {
+            (Some(l), Some(r)) => {
+                let saved = region[self.split_dim];
+                match &self.oblique {
+                    Some(o) => writeln!(
+                        src,
+                        "{pad}if {:?} * x[{}] + {:?} * x[{}] < {:?} {{",
+                        o.w[0], o.dims[0], o.w[1], o.dims[1], self.split_val
+                    ),
+                    None => writeln!(
+                        src,
+                        "{pad}if x[{}] < {:?} {{",
+                        self.split_dim, self.split_val
+                    ),
+                }
+                .unwrap();
+                if self.oblique.is_none() {
+                    region[self.split_dim].1 = saved.1.min(self.split_val);
+                }
+                l.write_rust(src, indent + 1, tree, region, acc);
+                writeln!(src, "{pad}}} else {{").unwrap();
+                if self.oblique.is_none() {
+                    region[self.split_dim] = (saved.0.max(self.split_val), saved.1);
+                }
+                r.write_rust(src, indent + 1, tree, region, acc);
+                region[self.split_dim] = saved;
+                writeln!(src, "{pad}}}").unwrap();
+            }
+            _ => {
+                let score = self.exported_leaf_score(tree, region, acc);
+                writeln!(src, "{pad}{score:?} // mass {:?}", self.mass).unwrap();
+            }
+        }
+    }
+
+    /// Static score of this leaf for exporters, given its reconstructed `region` and the
+    /// harmonic accumulator `acc` including the leaf itself.
+    pub(crate) fn exported_leaf_score<M>(
+        &self,
+        tree: &HalfSpaceTree<M>,
+        region: &[(f64, f64)],
+        acc: (f64, f64),
+    ) -> f64 {
+        let max_depth = tree.max_depth;
+        let mut score = match tree.weighting {
+            DepthWeighting::Linear => self.leaf_score(max_depth),
+            DepthWeighting::Harmonic => acc.0 / acc.1 * self.depth_factor(max_depth),
+        };
+        if tree.method == ScoreMethod::VarianceAware {
+            let (lo, hi) = region[self.split_dim];
+            score *= self.spread_factor(hi - lo);
+        }
+        score
+    }
+}
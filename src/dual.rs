@@ -0,0 +1,87 @@
+//! Paired fast/slow forests for anomalies relative to recent behaviour.
+
+use crate::{FeatureVector, HalfSpaceTrees};
+
+/// Two forests fed the same stream: a *recent* one that forgets quickly and a *baseline*
+/// one that forgets slowly. Each `insert` decays them by their own per-insert `alpha`.
+///
+/// [`DualTimescaleForest::relative_recent_score`] compares the two, answering "is this
+/// unusual compared to the last hour" instead of "compared to everything seen".
+#[derive(Debug)]
+pub struct DualTimescaleForest {
+    recent: HalfSpaceTrees,
+    baseline: HalfSpaceTrees,
+    recent_alpha: f64,
+    baseline_alpha: f64,
+}
+
+impl DualTimescaleForest {
+    /// Wrap two untrained forests over the same dimensions. `recent_alpha` should be
+    /// smaller (faster forgetting) than `baseline_alpha`; both must lie in (0,1].
+    pub fn new(
+        recent: HalfSpaceTrees,
+        baseline: HalfSpaceTrees,
+        recent_alpha: f64,
+        baseline_alpha: f64,
+    ) -> Self {
+        assert_eq!(
+            recent.n_dims(),
+            baseline.n_dims(),
+            "forests must share dimensions"
+        );
+        for alpha in [recent_alpha, baseline_alpha] {
+            assert!(alpha > 0.0 && alpha <= 1.0, "alpha must be in (0,1]");
+        }
+        Self {
+            recent,
+            baseline,
+            recent_alpha,
+            baseline_alpha,
+        }
+    }
+
+    pub fn insert(&mut self, x: &FeatureVector) {
+        self.recent.decay(self.recent_alpha);
+        self.recent.insert(x);
+        self.baseline.decay(self.baseline_alpha);
+        self.baseline.insert(x);
+    }
+
+    /// How much more anomalous the recent forest finds `x` than the baseline does.
+    /// The slow forest holds far more mass, so its masses are first rescaled to the recent
+    /// forest's mean total mass (via [`HalfSpaceTrees::score_at_alpha`]) and both are
+    /// compared at the same effective sample size. Positive for points the stream has
+    /// moved away from, negative for patterns that only lately became common.
+    pub fn relative_recent_score(&self, x: &FeatureVector) -> f64 {
+        let (recent, baseline) = (
+            mean_total_mass(&self.recent),
+            mean_total_mass(&self.baseline),
+        );
+        let scale = if baseline > 0.0 {
+            recent / baseline
+        } else {
+            1.0
+        };
+        self.recent.score(x) - self.baseline.score_at_alpha(x, scale, 1)
+    }
+
+    pub fn recent(&self) -> &HalfSpaceTrees {
+        &self.recent
+    }
+
+    pub fn baseline(&self) -> &HalfSpaceTrees {
+        &self.baseline
+    }
+
+    pub fn into_inner(self) -> (HalfSpaceTrees, HalfSpaceTrees) {
+        (self.recent, self.baseline)
+    }
+}
+
+fn mean_total_mass(forest: &HalfSpaceTrees) -> f64 {
+    let trees = forest.trees();
+    if trees.is_empty() {
+        return 0.0;
+    }
+    trees.iter().map(|t| t.total_mass()).sum::<f64>() / trees.len() as f64
+}
@@ -0,0 +1,27 @@
+use std::fmt;
+
+/// Errors returned by fallible conversions and loaders.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HstError {
+    /// Input data does not match the shape its layout describes, or a forest does not
+    /// fit the layout it is being packed into.
+    InvalidLayout(String),
+    /// Serialized streaming state is malformed or does not fit the forest.
+    InvalidState(String),
+    /// Reading or mapping a model file failed.
+    Io(String),
+}
+
+impl fmt::Display for HstError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HstError::InvalidLayout(msg) => write!(f, "invalid layout: {msg}"),
+            HstError::InvalidState(msg) => write!(f, "invalid streaming state: {msg}"),
+            HstError::Io(msg) => write!(f, "i/o error: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for HstError {}
+
+pub type Result<T> = std::result::Result<T, HstError>;
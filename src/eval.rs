@@ -0,0 +1,237 @@
+//! Evaluation helpers for scores against labeled data.
+//!
+//! Labels are `true` for anomalies. A point is flagged when its score is `>=` the threshold.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::{HalfSpaceTrees, padded_bounds, quantile};
+
+/// Threshold maximizing Youden's J (`sensitivity + specificity - 1`), returned with the
+/// achieved J. Every distinct score is a candidate; among equally good thresholds the
+/// highest wins, i.e. the one raising the fewest alerts. Returns `(f64::INFINITY, 0.0)`
+/// if either class is missing.
+pub fn best_threshold_youden(scores: &[f64], labels: &[bool]) -> (f64, f64) {
+    assert_eq!(scores.len(), labels.len(), "scores and labels must align");
+    let pos = labels.iter().filter(|&&l| l).count();
+    let neg = labels.len() - pos;
+    if pos == 0 || neg == 0 {
+        return (f64::INFINITY, 0.0);
+    }
+    let mut order: Vec<usize> = (0..scores.len()).collect();
+    order.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+
+    let (mut tp, mut fp) = (0usize, 0usize);
+    let mut best = (f64::INFINITY, 0.0);
+    let mut i = 0;
+    while i < order.len() {
+        // Admit every point tied at this score before evaluating the threshold.
+        let t = scores[order[i]];
+        while i < order.len() && scores[order[i]] == t {
+            if labels[order[i]] {
+                tp += 1;
+            } else {
+                fp += 1;
+            }
+            i += 1;
+        }
+        let j = tp as f64 / pos as f64 - fp as f64 / neg as f64;
+        if j > best.1 {
+            best = (t, j);
+        }
+    }
+    best
+}
+
+/// Leaf-mass cut best separating two groups, for operators who reason in mass rather
+/// than score: points whose leaf mass is below the cut are flagged as outliers. The cut
+/// maximizes balanced accuracy (equivalently Youden's J) and sits midway between the
+/// highest flagged mass and the next mass up; among equally good cuts the lowest wins,
+/// i.e. the one raising the fewest alerts. Returns `f64::NEG_INFINITY` (flag nothing) if
+/// either group is empty.
+pub fn best_mass_threshold(inlier_leaf_masses: &[f64], outlier_leaf_masses: &[f64]) -> f64 {
+    let (pos, neg) = (outlier_leaf_masses.len(), inlier_leaf_masses.len());
+    if pos == 0 || neg == 0 {
+        return f64::NEG_INFINITY;
+    }
+    let mut masses: Vec<(f64, bool)> = outlier_leaf_masses
+        .iter()
+        .map(|&m| (m, true))
+        .chain(inlier_leaf_masses.iter().map(|&m| (m, false)))
+        .collect();
+    masses.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let (mut tp, mut fp) = (0usize, 0usize);
+    let mut best = (f64::NEG_INFINITY, 0.0);
+    let mut i = 0;
+    while i < masses.len() {
+        // Admit every point tied at this mass before evaluating the cut above it.
+        let m = masses[i].0;
+        while i < masses.len() && masses[i].0 == m {
+            if masses[i].1 {
+                tp += 1;
+            } else {
+                fp += 1;
+            }
+            i += 1;
+        }
+        let j = tp as f64 / pos as f64 - fp as f64 / neg as f64;
+        if j > best.1 {
+            let cut = masses
+                .get(i)
+                .map_or(f64::INFINITY, |next| (m + next.0) / 2.0);
+            best = (cut, j);
+        }
+    }
+    best.0
+}
+
+/// Area under the ROC curve: the probability that a random anomaly outscores a random
+/// normal point, with ties counting half. Returns 0.5 if either class is missing.
+pub fn auc(scores: &[f64], labels: &[bool]) -> f64 {
+    assert_eq!(scores.len(), labels.len(), "scores and labels must align");
+    let pos = labels.iter().filter(|&&l| l).count();
+    let neg = labels.len() - pos;
+    if pos == 0 || neg == 0 {
+        return 0.5;
+    }
+    let mut order: Vec<usize> = (0..scores.len()).collect();
+    order.sort_by(|&a, &b| scores[a].total_cmp(&scores[b]));
+
+    // Mann-Whitney: sum the (tie-averaged, 1-based) ranks of the anomalies.
+    let mut rank_sum = 0.0;
+    let mut i = 0;
+    while i < order.len() {
+        let mut j = i;
+        while j < order.len() && scores[order[j]] == scores[order[i]] {
+            j += 1;
+        }
+        let avg_rank = (i + j + 1) as f64 / 2.0;
+        rank_sum += avg_rank * order[i..j].iter().filter(|&&k| labels[k]).count() as f64;
+        i = j;
+    }
+    let pos = pos as f64;
+    (rank_sum - pos * (pos + 1.0) / 2.0) / (pos * neg as f64)
+}
+
+/// ROC curve as `(false positive rate, true positive rate)` points, from the highest
+/// threshold (`(0, 0)`, nothing flagged) down to the lowest (`(1, 1)`, everything
+/// flagged). Equal scores are admitted together, so ties give one diagonal step rather
+/// than an order-dependent staircase; repeated points are dropped. If either class is
+/// missing the curve is the diagonal `[(0, 0), (1, 1)]`, matching [`auc`]'s 0.5.
+pub fn roc_curve(scores: &[f64], labels: &[bool]) -> Vec<(f64, f64)> {
+    assert_eq!(scores.len(), labels.len(), "scores and labels must align");
+    let pos = labels.iter().filter(|&&l| l).count();
+    let neg = labels.len() - pos;
+    if pos == 0 || neg == 0 {
+        return vec![(0.0, 0.0), (1.0, 1.0)];
+    }
+    let mut order: Vec<usize> = (0..scores.len()).collect();
+    order.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+
+    let (mut tp, mut fp) = (0usize, 0usize);
+    let mut curve = vec![(0.0, 0.0)];
+    let mut i = 0;
+    while i < order.len() {
+        let t = scores[order[i]];
+        while i < order.len() && scores[order[i]] == t {
+            if labels[order[i]] {
+                tp += 1;
+            } else {
+                fp += 1;
+            }
+            i += 1;
+        }
+        let point = (fp as f64 / neg as f64, tp as f64 / pos as f64);
+        if curve.last() != Some(&point) {
+            curve.push(point);
+        }
+    }
+    curve
+}
+
+/// Percentile bootstrap confidence interval for [`auc`] at confidence `level` (e.g. 0.95),
+/// returned as `(lower, point, upper)` where `point` is the AUC of the full sample.
+/// Each of `resamples` draws picks `scores.len()` points with replacement; draws missing
+/// a class are skipped. If every draw is skipped the interval collapses to the point.
+pub fn auc_bootstrap_ci<R: Rng + ?Sized>(
+    scores: &[f64],
+    labels: &[bool],
+    resamples: usize,
+    rng: &mut R,
+    level: f64,
+) -> (f64, f64, f64) {
+    assert!(level > 0.0 && level < 1.0, "level must be in (0,1)");
+    let point = auc(scores, labels);
+    let n = scores.len();
+    let mut s = vec![0.0; n];
+    let mut l = vec![false; n];
+    let mut aucs = Vec::with_capacity(resamples);
+    for _ in 0..resamples {
+        for (si, li) in s.iter_mut().zip(&mut l) {
+            let k = rng.random_range(0..n);
+            *si = scores[k];
+            *li = labels[k];
+        }
+        if l.iter().all(|&v| v) || l.iter().all(|&v| !v) {
+            continue;
+        }
+        aucs.push(auc(&s, &l));
+    }
+    if aucs.is_empty() {
+        return (point, point, point);
+    }
+    aucs.sort_by(f64::total_cmp);
+    let tail = (1.0 - level) / 2.0;
+    (quantile(&aucs, tail), point, quantile(&aucs, 1.0 - tail))
+}
+
+/// Fraction of all `(anomaly, normal)` pairs in which the anomaly scores higher, ties
+/// counting half. This is the AUC computed from the two score lists directly.
+/// Returns 0.5 if either list is empty.
+pub fn pairwise_ranking_accuracy(anomaly_scores: &[f64], normal_scores: &[f64]) -> f64 {
+    let scores: Vec<f64> = anomaly_scores
+        .iter()
+        .chain(normal_scores)
+        .copied()
+        .collect();
+    let labels: Vec<bool> = (0..scores.len())
+        .map(|i| i < anomaly_scores.len())
+        .collect();
+    auc(&scores, &labels)
+}
+
+/// Prequential AUC of the same forest replayed over `stream` once per decay rate: each
+/// point is scored, inserted, then the forest decays by `alpha`. Returns `(alpha, auc)`
+/// in the order given, to see empirically whether forgetting helps on a dataset.
+///
+/// Every replay uses identical trees (25 of depth 8 over the stream's range padded as in
+/// [`HalfSpaceTrees::auto`]), drawn from one seed taken from `rng`, so only `alpha` varies.
+pub fn decay_ablation<R: Rng + ?Sized>(
+    stream: &[Vec<f64>],
+    labels: &[bool],
+    alphas: &[f64],
+    rng: &mut R,
+) -> Vec<(f64, f64)> {
+    assert_eq!(stream.len(), labels.len(), "one label per point");
+    assert!(!stream.is_empty(), "stream must not be empty");
+    let bounds = padded_bounds(stream);
+    let seed: u64 = rng.random();
+    alphas
+        .iter()
+        .map(|&alpha| {
+            assert!(alpha > 0.0 && alpha <= 1.0, "alpha must be in (0,1]");
+            let mut forest = HalfSpaceTrees::new(25, 8, &bounds, &mut StdRng::seed_from_u64(seed));
+            let scores: Vec<f64> = stream
+                .iter()
+                .map(|x| {
+                    let score = forest.score(x);
+                    forest.insert(x);
+                    forest.decay(alpha);
+                    score
+                })
+                .collect();
+            (alpha, auc(&scores, labels))
+        })
+        .collect()
+}
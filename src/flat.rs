@@ -0,0 +1,218 @@
+//! Flat `f32` blob export for handing a forest to GPU or SIMD scorers.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::{
+    DepthWeighting, HalfSpaceTree, HalfSpaceTrees, HstError, Node, Result, ScoreMethod, Transform,
+};
+
+/// Describes how a forest is packed into a flat `f32` blob.
+///
+/// The blob starts with a header of `2 * n_dims` floats holding each dimension's
+/// `(min, max)` bounds. Then follow `n_trees` trees, each `tree_stride` floats long.
+/// A tree is its full binary tree of `nodes_per_tree` nodes in heap order (node `i`
+/// has children `2i + 1` and `2i + 2`), each node a record of `node_stride` floats:
+/// the split dimension at `dim_offset`, the split value at `value_offset` and the
+/// mass at `mass_offset`. Leaves carry a split too; scorers should ignore it.
+/// Per-node coordinate moments are not exported, so an imported forest starts
+/// them at zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ForestLayout {
+    pub n_trees: usize,
+    pub max_depth: u32,
+    pub n_dims: usize,
+    pub header_len: usize,
+    pub nodes_per_tree: usize,
+    pub node_stride: usize,
+    pub tree_stride: usize,
+    pub dim_offset: usize,
+    pub value_offset: usize,
+    pub mass_offset: usize,
+}
+
+/// Deepest trees the flat loaders accept; a full tree this deep already has 2^41 nodes.
+pub(crate) const MAX_FLAT_DEPTH: u32 = 40;
+
+impl ForestLayout {
+    /// [`ForestLayout::new`] for an untrusted shape: fails instead of overflowing.
+    pub(crate) fn checked(n_trees: usize, max_depth: u32, n_dims: usize) -> Result<Self> {
+        let too_big = || {
+            HstError::InvalidLayout(format!(
+                "implausible shape: {n_trees} trees, depth {max_depth}, {n_dims} dims"
+            ))
+        };
+        if max_depth > MAX_FLAT_DEPTH {
+            return Err(too_big());
+        }
+        let floats = 2 * n_dims as u128 + n_trees as u128 * 3 * ((1u128 << (max_depth + 1)) - 1);
+        if floats > usize::MAX as u128 {
+            return Err(too_big());
+        }
+        Ok(Self::new(n_trees, max_depth, n_dims))
+    }
+
+    pub(crate) fn new(n_trees: usize, max_depth: u32, n_dims: usize) -> Self {
+        let nodes_per_tree = (1usize << (max_depth + 1)) - 1;
+        let node_stride = 3;
+        Self {
+            n_trees,
+            max_depth,
+            n_dims,
+            header_len: 2 * n_dims,
+            nodes_per_tree,
+            node_stride,
+            tree_stride: nodes_per_tree * node_stride,
+            dim_offset: 0,
+            value_offset: 1,
+            mass_offset: 2,
+        }
+    }
+
+    /// Total blob length in floats.
+    pub fn len(&self) -> usize {
+        self.header_len + self.n_trees * self.tree_stride
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl HalfSpaceTrees {
+    /// Pack split geometry and masses into a flat `f32` blob described by the returned layout.
+    /// Fails with [`HstError::InvalidLayout`] unless every tree is axis-aligned, full and
+    /// of the same depth, as the layout has no room for oblique directions or for leaves
+    /// above `max_depth` (see [`crate::HalfSpaceTree::merge_similar_leaves`]).
+    pub fn to_flat_f32(&self) -> Result<(Vec<f32>, ForestLayout)> {
+        let max_depth = self.trees.first().map_or(0, |t| t.max_depth);
+        if self.trees.iter().any(|t| t.max_depth != max_depth) {
+            return Err(HstError::InvalidLayout(
+                "trees of different depths cannot share a flat layout".into(),
+            ));
+        }
+        let layout = ForestLayout::new(self.trees.len(), max_depth, self.n_dims());
+        let mut data = vec![0.0f32; layout.len()];
+        for (d, &(lo, hi)) in self.bounds.iter().enumerate() {
+            data[2 * d] = lo as f32;
+            data[2 * d + 1] = hi as f32;
+        }
+        for (i, t) in self.trees.iter().enumerate() {
+            let start = layout.header_len + i * layout.tree_stride;
+            t.root
+                .write_flat(&mut data[start..start + layout.tree_stride], 0, &layout)?;
+        }
+        Ok((data, layout))
+    }
+
+    /// Rebuild a forest from a blob produced by [`HalfSpaceTrees::to_flat_f32`].
+    /// Streaming state (smoothing, drift) starts fresh.
+    pub fn from_flat_f32(data: &[f32], layout: ForestLayout) -> Result<HalfSpaceTrees> {
+        let expected = ForestLayout::checked(layout.n_trees, layout.max_depth, layout.n_dims)?;
+        if layout != expected {
+            return Err(HstError::InvalidLayout(format!(
+                "unsupported layout {layout:?}"
+            )));
+        }
+        if layout.n_dims == 0 {
+            return Err(HstError::InvalidLayout("n_dims must be positive".into()));
+        }
+        if data.len() != layout.len() {
+            return Err(HstError::InvalidLayout(format!(
+                "expected {} floats, found {}",
+                layout.len(),
+                data.len()
+            )));
+        }
+        let bounds: Vec<(f64, f64)> = data[..layout.header_len]
+            .chunks_exact(2)
+            .map(|b| (b[0] as f64, b[1] as f64))
+            .collect();
+        let trees = data[layout.header_len..]
+            .chunks_exact(layout.tree_stride)
+            .map(|records| {
+                Ok(HalfSpaceTree {
+                    root: Node::read_flat(records, 0, 0, &layout)?,
+                    max_depth: layout.max_depth,
+                    n_dims: layout.n_dims,
+                    bounds: bounds.clone(),
+                    weighting: DepthWeighting::default(),
+                    method: ScoreMethod::default(),
+                    max_repeats: u32::MAX,
+                    transforms: vec![Transform::Identity; layout.n_dims],
+                    meta: HashMap::new(),
+                    volumes: OnceLock::new(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(HalfSpaceTrees::from_trees(trees, &bounds))
+    }
+}
+
+impl Node {
+    fn write_flat(&self, records: &mut [f32], i: usize, layout: &ForestLayout) -> Result<()> {
+        if self.oblique.is_some() {
+            return Err(HstError::InvalidLayout(
+                "oblique splits cannot be packed into the flat layout".into(),
+            ));
+        }
+        let rec = &mut records[i * layout.node_stride..(i + 1) * layout.node_stride];
+        rec[layout.dim_offset] = self.split_dim as f32;
+        rec[layout.value_offset] = self.split_val as f32;
+        rec[layout.mass_offset] = self.mass as f32;
+        match (&self.left, &self.right) {
+            (Some(l), Some(r)) => {
+                l.write_flat(records, 2 * i + 1, layout)?;
+                r.write_flat(records, 2 * i + 2, layout)?;
+            }
+            _ if self.depth < layout.max_depth => {
+                return Err(HstError::InvalidLayout(format!(
+                    "leaf at depth {} of a depth-{} tree (merged leaves cannot be packed)",
+                    self.depth, layout.max_depth
+                )));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn read_flat(records: &[f32], i: usize, depth: u32, layout: &ForestLayout) -> Result<Node> {
+        let rec = &records[i * layout.node_stride..(i + 1) * layout.node_stride];
+        let dim = rec[layout.dim_offset];
+        if !(dim >= 0.0 && (dim as usize) < layout.n_dims && dim.fract() == 0.0) {
+            return Err(HstError::InvalidLayout(format!(
+                "node {i} has invalid split dimension {dim}"
+            )));
+        }
+        let (left, right) = if depth < layout.max_depth {
+            (
+                Some(Box::new(Node::read_flat(
+                    records,
+                    2 * i + 1,
+                    depth + 1,
+                    layout,
+                )?)),
+                Some(Box::new(Node::read_flat(
+                    records,
+                    2 * i + 2,
+                    depth + 1,
+                    layout,
+                )?)),
+            )
+        } else {
+            (None, None)
+        };
+        Ok(Node {
+            split_dim: dim as usize,
+            split_val: rec[layout.value_offset] as f64,
+            oblique: None,
+            left,
+            right,
+            depth,
+            mass: rec[layout.mass_offset] as f64,
+            visits: 0.0,
+            sum: 0.0,
+            sum_sq: 0.0,
+        })
+    }
+}
@@ -0,0 +1,30 @@
+//! Hashing that stays fixed across platforms and Rust releases, for anything persisted.
+
+/// 64-bit FNV-1a over little-endian words, with a final avalanche step so that
+/// `finish() % n` spreads well for small `n`. Unlike `DefaultHasher`, its output is part
+/// of this crate's contract: persisted sketches and pools depend on it.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Fnv1a(u64);
+
+impl Fnv1a {
+    pub(crate) fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub(crate) fn write_u64(&mut self, v: u64) {
+        for b in v.to_le_bytes() {
+            self.0 ^= b as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub(crate) fn finish(self) -> u64 {
+        // MurmurHash3's 64-bit finalizer
+        let mut h = self.0;
+        h ^= h >> 33;
+        h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+        h ^= h >> 33;
+        h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+        h ^ (h >> 33)
+    }
+}
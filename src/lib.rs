@@ -21,6 +21,7 @@
 //!
 //! # Example
 //! ```
+//! use half_space_trees::HalfSpaceTrees;
 //! use rand::SeedableRng;
 //! use rand::rngs::StdRng;
 //!
@@ -54,153 +55,3785 @@
 
 use rand::Rng;
 use rand::distr::{Distribution, Uniform};
+use std::collections::{HashMap, VecDeque};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "arrow")]
+mod arrow;
+#[cfg(feature = "tokio")]
+mod r#async;
+mod calibrate;
+mod codegen;
+mod dual;
+mod error;
+pub mod eval;
+mod flat;
+mod fnv;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod monitor;
+#[cfg(feature = "onnx")]
+mod onnx;
+mod pool;
+mod projection;
+mod river;
+mod sketch;
+mod source;
+mod state;
+mod trace;
+mod window;
+
+pub use dual::DualTimescaleForest;
+pub use error::{HstError, Result};
+pub use flat::ForestLayout;
+#[cfg(feature = "mmap")]
+pub use mmap::MmapForest;
+pub use monitor::{ScoreDistributionMonitor, ScoreSnapshot};
+pub use projection::{ProjectedForest, RandomProjection};
+pub use river::RiverCompat;
+pub use state::StreamingState;
+pub use trace::{ScoreAggregation, ScoreTrace, SplitDecision, TreeTrace};
+pub use window::WindowedForest;
 
 pub type FeatureVector = [f64];
 
-#[derive(Debug)]
-pub struct HalfSpaceTrees {
-    trees: Vec<HalfSpaceTree>,
-}
+#[derive(Debug)]
+pub struct HalfSpaceTrees {
+    trees: Vec<HalfSpaceTree>,
+    // Per-tree weights for the score average
+    weights: Vec<f64>,
+    bounds: Vec<(f64, f64)>,
+    // EWMA state for `process_smoothed`
+    smoothed: Option<f64>,
+    // Most recent scores seen by `combined_severity`, oldest first
+    severity_window: VecDeque<f64>,
+    // Per-dimension running moments for `dimension_drift`
+    drift: Vec<DriftStats>,
+    // Per-tree total-mass change from the most recent insert
+    last_impact: Vec<f64>,
+    // `(alpha, baseline_interval)` for `insert_event`
+    event_decay: (f64, f64),
+    // Rounding step applied to `score` results, if any
+    score_quantum: Option<f64>,
+    // Leaf mass floor for `surprise`
+    surprise_floor: f64,
+    // Whether `score` pools leaf masses, per the last `set_score_method`
+    pooled_mass: bool,
+    // Exact-match frequency counts for `estimated_frequency`, if enabled
+    sketch: Option<sketch::CountMin>,
+    // Score-to-probability mapping for `probability`, if fitted
+    calibration: Option<calibrate::Isotonic>,
+    // Next tree for `amortized_decay_tick`
+    decay_cursor: usize,
+    // Per-insert weights for `insert`, and how many points it has inserted
+    weight_schedule: WeightSchedule,
+    inserts: u64,
+    // Leaf masses shared across trees, for `new_pooled_mass` forests
+    pool: Option<pool::MassPool>,
+    // Per-source leaf masses for `insert_tagged`, if enabled
+    sources: Option<source::SourceTracker>,
+    #[cfg(feature = "metrics")]
+    timing: Timing,
+}
+
+impl HalfSpaceTrees {
+    /// Create a forest of `n_trees` trees of depth `max_depth`.
+    /// `bounds` is a per‑dimension (min,max) range used to generate random splits.
+    pub fn new<R: Rng + ?Sized>(
+        n_trees: usize,
+        max_depth: u32,
+        bounds: &[(f64, f64)],
+        rng: &mut R,
+    ) -> Self {
+        Self::with_split_mode(n_trees, max_depth, bounds, SplitMode::AxisAligned, rng)
+    }
+
+    /// Like [`HalfSpaceTrees::new`], choosing how internal nodes split.
+    pub fn with_split_mode<R: Rng + ?Sized>(
+        n_trees: usize,
+        max_depth: u32,
+        bounds: &[(f64, f64)],
+        mode: SplitMode,
+        rng: &mut R,
+    ) -> Self {
+        let trees = (0..n_trees)
+            .map(|_| HalfSpaceTree::with_split_mode(max_depth, bounds, mode, rng))
+            .collect();
+        Self::from_trees(trees, bounds)
+    }
+
+    /// Like [`HalfSpaceTrees::new`], capping how often a dimension may split along any path
+    /// (see [`HalfSpaceTree::with_max_repeats`]).
+    pub fn with_max_repeats<R: Rng + ?Sized>(
+        n_trees: usize,
+        max_depth: u32,
+        bounds: &[(f64, f64)],
+        max_repeats_per_dim: u32,
+        rng: &mut R,
+    ) -> Self {
+        let trees = (0..n_trees)
+            .map(|_| HalfSpaceTree::with_max_repeats(max_depth, bounds, max_repeats_per_dim, rng))
+            .collect();
+        Self::from_trees(trees, bounds)
+    }
+
+    /// Like [`HalfSpaceTrees::new`], with splits drawn in per-dimension transformed space
+    /// (see [`HalfSpaceTree::with_transforms`]). Inserts and scores still take raw values.
+    pub fn with_transforms<R: Rng + ?Sized>(
+        n_trees: usize,
+        max_depth: u32,
+        bounds: &[(f64, f64)],
+        transforms: &[Transform],
+        rng: &mut R,
+    ) -> Self {
+        let trees = (0..n_trees)
+            .map(|_| HalfSpaceTree::with_transforms(max_depth, bounds, transforms, rng))
+            .collect();
+        Self::from_trees(trees, bounds)
+    }
+
+    /// Build and train a forest sized from the data itself.
+    ///
+    /// Heuristics:
+    ///   * bounds are the per-dimension data range padded by 10% on each side
+    ///     (±0.5 for constant dimensions) so splits cover slightly unseen values;
+    ///   * `max_depth = ceil(log2(n * target_leaves_per_point))`, clamped to `1..=20`,
+    ///     so a tree has roughly `target_leaves_per_point` leaves per training point;
+    ///   * `n_trees = 10 + 5 * n_dims`, clamped to `10..=100`, as more dimensions need
+    ///     more random trees before every dimension is split on often enough.
+    ///
+    /// Every row of `data` is then inserted once.
+    pub fn auto<R: Rng + ?Sized>(
+        data: &[Vec<f64>],
+        target_leaves_per_point: f64,
+        rng: &mut R,
+    ) -> Self {
+        assert!(!data.is_empty(), "data must not be empty");
+        assert!(
+            target_leaves_per_point > 0.0,
+            "target_leaves_per_point must be positive"
+        );
+        let n_dims = data[0].len();
+        let bounds = padded_bounds(data);
+        let leaves = (data.len() as f64 * target_leaves_per_point).max(2.0);
+        let max_depth = (leaves.log2().ceil() as u32).clamp(1, 20);
+        let n_trees = (10 + 5 * n_dims).clamp(10, 100);
+
+        let mut forest = Self::new(n_trees, max_depth, &bounds, rng);
+        for x in data {
+            forest.insert(x);
+        }
+        forest
+    }
+
+    /// Build a forest over bounds taken from `data` and insert `data`, guarding against
+    /// constant dimensions: any dimension whose observed range is below `epsilon` gets
+    /// collapsed bounds `(v, v)`, so no tree ever splits on it. Other dimensions get the
+    /// observed range padded by 10% on each side. Returns the forest and a mask marking
+    /// the constant dimensions.
+    pub fn from_data<R: Rng + ?Sized>(
+        data: &[Vec<f64>],
+        n_trees: usize,
+        max_depth: u32,
+        epsilon: f64,
+        rng: &mut R,
+    ) -> (Self, Vec<bool>) {
+        assert!(!data.is_empty(), "data must not be empty");
+        assert!(epsilon >= 0.0, "epsilon must be non-negative");
+        let n_dims = data[0].len();
+        let mut constant = vec![false; n_dims];
+        let bounds: Vec<(f64, f64)> = (0..n_dims)
+            .map(|d| {
+                let (lo, hi) = data
+                    .iter()
+                    .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), x| {
+                        (lo.min(x[d]), hi.max(x[d]))
+                    });
+                if hi - lo < epsilon || hi <= lo {
+                    constant[d] = true;
+                    let mid = 0.5 * (lo + hi);
+                    (mid, mid)
+                } else {
+                    let pad = 0.1 * (hi - lo);
+                    (lo - pad, hi + pad)
+                }
+            })
+            .collect();
+        let mut forest = Self::new(n_trees, max_depth, &bounds, rng);
+        for x in data {
+            forest.insert(x);
+        }
+        (forest, constant)
+    }
+
+    /// Wrap already-built trees, starting with fresh streaming state.
+    fn from_trees(trees: Vec<HalfSpaceTree>, bounds: &[(f64, f64)]) -> Self {
+        Self {
+            weights: vec![1.0; trees.len()],
+            trees,
+            bounds: bounds.to_vec(),
+            smoothed: None,
+            severity_window: VecDeque::new(),
+            drift: vec![DriftStats::default(); bounds.len()],
+            last_impact: Vec::new(),
+            event_decay: (0.999, 1.0),
+            score_quantum: None,
+            surprise_floor: Self::DEFAULT_SURPRISE_FLOOR,
+            pooled_mass: false,
+            sketch: None,
+            calibration: None,
+            decay_cursor: 0,
+            weight_schedule: WeightSchedule::Constant,
+            inserts: 0,
+            pool: None,
+            sources: None,
+            #[cfg(feature = "metrics")]
+            timing: Timing::default(),
+        }
+    }
+
+    /// An independent copy carrying everything `score` depends on (trees, weights and any
+    /// mass pool), but fresh streaming state.
+    #[cfg(feature = "tokio")]
+    fn scoring_clone(&self) -> Self {
+        let mut copy = Self::from_trees(self.trees.clone(), &self.bounds);
+        copy.weights = self.weights.clone();
+        copy.score_quantum = self.score_quantum;
+        copy.pooled_mass = self.pooled_mass;
+        copy.pool = self.pool.clone();
+        copy
+    }
+
+    /// Number of input dimensions.
+    pub fn n_dims(&self) -> usize {
+        self.bounds.len()
+    }
+
+    /// Insert a point (after any global decay you apply externally), weighted by the
+    /// [`WeightSchedule`]: unit weight unless [`HalfSpaceTrees::set_weight_schedule`]
+    /// says otherwise.
+    pub fn insert(&mut self, x: &FeatureVector) {
+        let weight = self.weight_schedule.weight(self.inserts);
+        self.inserts += 1;
+        self.insert_weighted(x, weight);
+    }
+
+    /// Weight `insert` gives each point, by how many points it has inserted so far.
+    /// The count is kept across schedule changes; other insert methods do not advance it.
+    pub fn set_weight_schedule(&mut self, schedule: WeightSchedule) {
+        if let WeightSchedule::Decreasing { scale } | WeightSchedule::Increasing { scale } =
+            schedule
+        {
+            assert!(scale > 0.0, "scale must be positive");
+        }
+        self.weight_schedule = schedule;
+    }
+
+    /// Insert a point contributing `weight` mass instead of 1.
+    pub fn insert_weighted(&mut self, x: &FeatureVector, weight: f64) {
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+        self.last_impact.clear();
+        for t in &mut self.trees {
+            let before = t.total_mass();
+            t.insert_weighted(x, weight);
+            self.last_impact.push(t.total_mass() - before);
+        }
+        if let Some(pool) = &mut self.pool {
+            pool.add(&self.trees, x, weight);
+        }
+        self.observe(x);
+        #[cfg(feature = "metrics")]
+        Timing::record(&self.timing.insert_nanos, &self.timing.inserts, start);
+    }
+
+    /// Feed an inserted point to the per-dimension drift trackers and frequency sketch.
+    fn observe(&mut self, x: &FeatureVector) {
+        for (d, &v) in self.drift.iter_mut().zip(x) {
+            d.observe(v);
+        }
+        if let Some(sketch) = &mut self.sketch {
+            sketch.add(x);
+        }
+    }
+
+    /// Start counting inserts per quantized point in a Count-Min sketch of `depth` rows by
+    /// `width` counters, bucketing each feature into cells of size `resolution`.
+    /// Density alone normalizes a payload that repeats verbatim; the sketch exposes it.
+    /// Replaces any existing sketch, so counts restart from zero.
+    pub fn enable_frequency_sketch(&mut self, width: usize, depth: usize, resolution: f64) {
+        self.sketch = Some(sketch::CountMin::new(width, depth, resolution));
+    }
+
+    /// Approximate number of inserts (of any weight) that fell in the same quantization
+    /// cell as `x` since the sketch was enabled. Never below the true count, and only
+    /// above it on hash collisions. Always 0 if the sketch is not enabled.
+    pub fn estimated_frequency(&self, x: &FeatureVector) -> u64 {
+        self.sketch.as_ref().map_or(0, |s| s.estimate(x))
+    }
+
+    /// Decay, score and insert `x` in a single root-to-leaf walk per tree, returning the
+    /// score `x` gets after the decay but before its own insert (what `decay(alpha)`,
+    /// `score(x)`, `insert(x)` would return, score method and weight schedule included).
+    /// Only the nodes on `x`'s path are decayed, along with the tracked source masses of
+    /// its leaves; nodes off the path keep their mass until a path passes through them or
+    /// `decay` is called. This halves tree passes but means off-path regions forget more
+    /// slowly and a node's mass no longer equals the sum of its children's. Timed as an
+    /// insert. Panics on a forest built with [`HalfSpaceTrees::new_pooled_mass`].
+    pub fn process_fused(&mut self, x: &FeatureVector, alpha: f64) -> f64 {
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+        assert!(alpha > 0.0 && alpha <= 1.0, "alpha must be in (0,1]");
+        assert!(
+            self.pool.is_none(),
+            "process_fused cannot decay a shared mass pool along one path"
+        );
+        let weight = self.weight_schedule.weight(self.inserts);
+        self.inserts += 1;
+        // Leaf masses after the decay, read before the walk adds `x`
+        let pooled: Option<Vec<f64>> = self
+            .pools_leaf_masses()
+            .then(|| self.trees.iter().map(|t| alpha * t.leaf_mass(x)).collect());
+        if let Some(sources) = &mut self.sources {
+            for (i, t) in self.trees.iter().enumerate() {
+                sources.decay_leaf(i, t.root.leaf_index(x), alpha);
+            }
+        }
+        let (mut s, mut w) = (0.0, 0.0);
+        self.last_impact.clear();
+        for (t, &tw) in self.trees.iter_mut().zip(&self.weights) {
+            let before = t.total_mass();
+            s += tw * t.process_fused_weighted(x, alpha, weight);
+            w += tw;
+            self.last_impact.push(t.total_mass() - before);
+        }
+        self.observe(x);
+        let mut score = match pooled {
+            Some(masses) => 1.0 / (1.0 + trimmed_mean(masses).1.max(0.0)),
+            None => s / w,
+        };
+        if let Some(q) = self.score_quantum {
+            score = (score / q).round() * q;
+        }
+        #[cfg(feature = "metrics")]
+        Timing::record(&self.timing.insert_nanos, &self.timing.inserts, start);
+        score
+    }
+
+    /// Insert a stream while decaying by `alpha` per insert, checking AUC on the labeled
+    /// validation set (`true` = anomaly) every `VALIDATION_INTERVAL` inserts. Whenever the
+    /// AUC falls more than 0.01 below the previous check, the forgetting rate `1 - alpha`
+    /// doubles (up to 0.5), so contamination the model has trained on washes out sooner.
+    /// Decay is applied in one batch per interval; a trailing partial interval is decayed
+    /// and checked once the stream ends. Returns the final per-insert alpha.
+    pub fn train_with_validation<I>(
+        &mut self,
+        stream: I,
+        alpha: f64,
+        val: &[Vec<f64>],
+        val_labels: &[bool],
+    ) -> f64
+    where
+        I: IntoIterator<Item = Vec<f64>>,
+    {
+        const VALIDATION_INTERVAL: usize = 100;
+        assert!(alpha > 0.0 && alpha <= 1.0, "alpha must be in (0,1]");
+        assert_eq!(
+            val.len(),
+            val_labels.len(),
+            "one label per validation point"
+        );
+        let mut alpha = alpha;
+        let mut previous: Option<f64> = None;
+        let mut pending = 0;
+        let mut stream = stream.into_iter().peekable();
+        while let Some(x) = stream.next() {
+            self.insert(&x);
+            pending += 1;
+            if pending < VALIDATION_INTERVAL && stream.peek().is_some() {
+                continue;
+            }
+            self.decay(alpha.powi(pending as i32));
+            pending = 0;
+            let scores: Vec<f64> = val.iter().map(|v| self.score(v)).collect();
+            let auc = eval::auc(&scores, val_labels);
+            if previous.is_some_and(|p| auc < p - 0.01) {
+                alpha = 1.0 - (2.0 * (1.0 - alpha)).min(0.5);
+            }
+            previous = Some(auc);
+        }
+        alpha
+    }
+
+    /// Undo one earlier unit-weight `insert(x)` by subtracting its mass along its paths.
+    /// Only meaningful if no decay has happened since that insert.
+    pub fn remove(&mut self, x: &FeatureVector) {
+        for t in &mut self.trees {
+            t.insert_weighted(x, -1.0);
+        }
+        if let Some(pool) = &mut self.pool {
+            pool.add(&self.trees, x, -1.0);
+        }
+    }
+
+    /// Configure `insert_event`: one event arriving `baseline_interval` after the previous
+    /// one applies `decay(alpha)`. Defaults to `alpha = 0.999` per unit interval.
+    pub fn set_event_decay(&mut self, alpha: f64, baseline_interval: f64) {
+        assert!(alpha > 0.0 && alpha <= 1.0, "alpha must be in (0,1]");
+        assert!(
+            baseline_interval > 0.0,
+            "baseline_interval must be positive"
+        );
+        self.event_decay = (alpha, baseline_interval);
+    }
+
+    /// Decay by `alpha^(inter_arrival / baseline_interval)`, then insert `x`.
+    /// Decay tracks elapsed time rather than event count, so a burst of events barely
+    /// decays the model while a long gap forgets proportionally more.
+    pub fn insert_event(&mut self, x: &FeatureVector, inter_arrival: f64) {
+        assert!(inter_arrival >= 0.0, "inter_arrival must be non-negative");
+        let (alpha, baseline) = self.event_decay;
+        self.decay(alpha.powf(inter_arrival / baseline));
+        self.insert(x);
+    }
+
+    /// Insert a set of correlated points (e.g. one session) as a single event:
+    /// each member gets `total_weight / group.len()`, so large groups cannot dominate.
+    pub fn insert_group(&mut self, group: &[Vec<f64>], total_weight: f64) {
+        if group.is_empty() {
+            return;
+        }
+        let w = total_weight / group.len() as f64;
+        for x in group {
+            self.insert_weighted(x, w);
+        }
+    }
+
+    /// How much each tree's total mass grew on the most recent insert (measured before any
+    /// later decay). A tree stuck at 0 is not receiving updates. Empty before the first insert.
+    pub fn last_insert_impact(&self) -> Vec<f64> {
+        self.last_impact.clone()
+    }
+
+    /// Time `iters` calls to `score(sample)` after a short warm-up and report per-call
+    /// latency percentiles, to check performance on the target hardware.
+    pub fn benchmark_score(&self, sample: &FeatureVector, iters: usize) -> LatencyStats {
+        assert!(iters > 0, "iters must be positive");
+        for _ in 0..iters.min(100) {
+            std::hint::black_box(self.score(std::hint::black_box(sample)));
+        }
+        let mut samples: Vec<Duration> = (0..iters)
+            .map(|_| {
+                let start = Instant::now();
+                std::hint::black_box(self.score(std::hint::black_box(sample)));
+                start.elapsed()
+            })
+            .collect();
+        samples.sort_unstable();
+        let at = |q: f64| samples[((iters - 1) as f64 * q).round() as usize];
+        LatencyStats {
+            min: samples[0],
+            median: at(0.5),
+            p99: at(0.99),
+            max: samples[iters - 1],
+        }
+    }
+
+    /// Distribution of the model's scores over `data` (typically its own training set),
+    /// handy for picking alert thresholds. Panics if `data` is empty.
+    pub fn self_score_summary(&self, data: &[Vec<f64>]) -> ScoreSummary {
+        assert!(!data.is_empty(), "data must not be empty");
+        let mut scores: Vec<f64> = data.iter().map(|x| self.score(x)).collect();
+        scores.sort_by(f64::total_cmp);
+        ScoreSummary {
+            min: scores[0],
+            mean: scores.iter().sum::<f64>() / scores.len() as f64,
+            median: quantile(&scores, 0.5),
+            p95: quantile(&scores, 0.95),
+            p99: quantile(&scores, 0.99),
+            max: scores[scores.len() - 1],
+        }
+    }
+
+    /// What `score(x)` would be after `ticks` further `decay(alpha)` calls, computed by
+    /// scaling masses by `alpha^ticks` on the fly; the forest is left untouched.
+    pub fn score_at_alpha(&self, x: &FeatureVector, alpha: f64, ticks: u32) -> f64 {
+        self.weighted_mean(|t| t.score_at_alpha(x, alpha, ticks))
+    }
+
+    /// [`HalfSpaceTrees::score_at_alpha`] together with its derivative with respect to
+    /// `alpha`, for tuning decay by gradient methods. Masses enter only through
+    /// `alpha^ticks`, so the derivative is exact: each rarity `1 / (1 + m s)` with
+    /// `s = alpha^ticks` changes by `-m / (1 + m s)^2 * ticks * alpha^(ticks - 1)`.
+    /// The derivative is 0 for `ticks = 0`.
+    pub fn score_dscore_dalpha(&self, x: &FeatureVector, alpha: f64, ticks: u32) -> (f64, f64) {
+        let (mut s, mut g, mut w) = (0.0, 0.0, 0.0);
+        for (t, &tw) in self.trees.iter().zip(&self.weights) {
+            let (score, grad) = t.score_dscore_dalpha(x, alpha, ticks);
+            s += tw * score;
+            g += tw * grad;
+            w += tw;
+        }
+        (s / w, g / w)
+    }
+
+    /// Insert `x` only if its current score is below `max_score`; returns whether it was accepted.
+    /// Keeps obvious anomalies from training themselves into looking normal.
+    pub fn insert_guarded(&mut self, x: &FeatureVector, max_score: f64) -> bool {
+        if self.score(x) < max_score {
+            self.insert(x);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Per-dimension drift: the recent (fast EWMA) mean of inserted values minus the
+    /// baseline (slow EWMA) mean, in units of the baseline standard deviation.
+    /// Values near 0 mean no shift; magnitudes of a few units indicate that dimension moved.
+    pub fn dimension_drift(&self) -> Vec<f64> {
+        self.drift
+            .iter()
+            .map(DriftStats::standardized_shift)
+            .collect()
+    }
+
+    /// Choose how leaf contents enter every tree's score (default [`ScoreMethod::Standard`]).
+    /// The forest remembers whether it pools leaf masses, so trees added later by
+    /// `add_tree` join the pooled aggregation whatever their own method.
+    pub fn set_score_method(&mut self, method: ScoreMethod) {
+        self.pooled_mass = method == ScoreMethod::PooledMass;
+        for t in &mut self.trees {
+            t.set_score_method(method);
+        }
+    }
+
+    /// Choose how depth enters every tree's score (default [`DepthWeighting::Linear`]).
+    pub fn set_depth_weighting(&mut self, weighting: DepthWeighting) {
+        for t in &mut self.trees {
+            t.set_depth_weighting(weighting);
+        }
+    }
+
+    /// Insert `x` with weight `1 / (1 + score / half_score)` and return that weight.
+    /// Unlike `insert_guarded`, suspicious points still count, just less, so genuinely
+    /// new regions grow slowly instead of never. A point scoring `half_score` gets weight 0.5.
+    pub fn insert_dampened(&mut self, x: &FeatureVector, half_score: f64) -> f64 {
+        assert!(half_score > 0.0, "half_score must be positive");
+        let weight = 1.0 / (1.0 + self.score(x) / half_score);
+        self.insert_weighted(x, weight);
+        weight
+    }
+
+    /// Multiply all node masses by `alpha` (0,1]. Call periodically to handle drift.
+    pub fn decay(&mut self, alpha: f64) {
+        for t in &mut self.trees {
+            t.decay(alpha);
+        }
+        if let Some(pool) = &mut self.pool {
+            pool.decay(alpha);
+        }
+        if let Some(sources) = &mut self.sources {
+            sources.decay(alpha);
+        }
+    }
+
+    /// Amortized `decay(alpha)`: each tick decays a single tree, in rotation, by
+    /// `alpha^n_trees`. Work per tick is one tree's nodes instead of the whole forest, and
+    /// after every `n_trees` ticks each tree has been decayed exactly as `n_trees` calls
+    /// to `decay(alpha)` would have done. In between, trees lag by up to one cycle.
+    pub fn amortized_decay_tick(&mut self, alpha: f64) {
+        if self.trees.is_empty() {
+            return;
+        }
+        let n = self.trees.len();
+        let i = self.decay_cursor % n;
+        self.trees[i].decay(alpha.powi(n as i32));
+        // Every tick spans one `decay(alpha)` worth of time for the shared buckets.
+        if let Some(pool) = &mut self.pool {
+            pool.decay(alpha);
+        }
+        self.decay_cursor = (i + 1) % n;
+    }
+
+    /// Repair masses after importing them from an untrusted source: negative masses are
+    /// clamped to 0 and NaN or infinite ones replaced by 0. Returns the number of nodes
+    /// repaired. Parents are not recomputed from their children.
+    pub fn sanitize_masses(&mut self) -> usize {
+        let repaired = self.trees.iter_mut().map(|t| t.sanitize_masses()).sum();
+        self.sync_pool();
+        repaired
+    }
+
+    /// Recompute any mass pool from the trees' leaf masses, after an update that
+    /// changed them other than by insert or uniform decay.
+    fn sync_pool(&mut self) {
+        if let Some(pool) = &mut self.pool {
+            pool.rebuild(&self.trees);
+        }
+    }
+
+    /// Half-life of a leaf's mass when `decay(alpha)` is applied once per insert:
+    /// `ln(0.5) / ln(alpha)` inserts, divided by `insert_rate` (inserts per second) to give
+    /// seconds. Pass `insert_rate = 1.0` to get the answer in inserts. Infinite for `alpha = 1`.
+    pub fn decay_half_life(&self, alpha: f64, insert_rate: f64) -> f64 {
+        assert!(alpha > 0.0 && alpha <= 1.0, "alpha must be in (0,1]");
+        assert!(insert_rate > 0.0, "insert_rate must be positive");
+        if alpha == 1.0 {
+            return f64::INFINITY;
+        }
+        0.5f64.ln() / alpha.ln() / insert_rate
+    }
+
+    /// Decay all masses by `alpha`, then add `prior` back to every leaf.
+    /// This keeps a soft floor so no leaf ever looks completely unseen.
+    pub fn decay_with_prior(&mut self, alpha: f64, prior: f64) {
+        for t in &mut self.trees {
+            t.decay_with_prior(alpha, prior);
+        }
+        self.sync_pool();
+    }
+
+    /// For each candidate rarity smoothing constant in `values` (the `1` in
+    /// `1 / (1 + mass)`), the standardized separation of the probe scores: mean score of
+    /// anomalies minus mean score of normal points, over their pooled standard deviation.
+    /// `labels[i]` is true if `probe[i]` is an anomaly. Dividing by the spread keeps the
+    /// measure comparable across constants, which rescale all scores.
+    /// Entries are 0 if either class is missing or the scores do not vary.
+    pub fn smoothing_sweep(&self, probe: &[Vec<f64>], labels: &[bool], values: &[f64]) -> Vec<f64> {
+        assert_eq!(probe.len(), labels.len(), "one label per probe point");
+        values
+            .iter()
+            .map(|&c| {
+                let scores: Vec<f64> = probe
+                    .iter()
+                    .map(|x| self.weighted_mean(|t| t.score_with_smoothing(x, c)))
+                    .collect();
+                separation(&scores, labels)
+            })
+            .collect()
+    }
+
+    /// Monte-Carlo estimate of the mean score of `x` plus independent Gaussian noise with
+    /// standard deviation `sigmas[d]` on each dimension, over `samples` draws. Differs from
+    /// `score(x)` mostly where `x` sits within a few sigmas of split thresholds.
+    pub fn expected_score_under_noise<R: Rng + ?Sized>(
+        &self,
+        x: &FeatureVector,
+        sigmas: &[f64],
+        samples: usize,
+        rng: &mut R,
+    ) -> f64 {
+        assert_eq!(sigmas.len(), x.len(), "one sigma per dimension");
+        assert!(samples > 0, "samples must be positive");
+        let mut noisy = x.to_vec();
+        let mut total = 0.0;
+        for _ in 0..samples {
+            for ((v, &xi), &sd) in noisy.iter_mut().zip(x).zip(sigmas) {
+                *v = xi + sd * standard_normal(rng);
+            }
+            total += self.score(&noisy);
+        }
+        total / samples as f64
+    }
+
+    /// Monte-Carlo mean score of `x` when each dimension goes missing independently with
+    /// probability `drop_prob`, over `samples` draws. A split on a missing dimension (or an
+    /// oblique split touching one) descends both children and averages their scores
+    /// weighted by child mass, so heavily dropped points drift toward the mass-weighted
+    /// average leaf score. Uses the plain leaf score, as with `Linear` depth weighting.
+    pub fn score_with_dropout<R: Rng + ?Sized>(
+        &self,
+        x: &FeatureVector,
+        drop_prob: f64,
+        samples: usize,
+        rng: &mut R,
+    ) -> f64 {
+        assert!(
+            (0.0..=1.0).contains(&drop_prob),
+            "drop_prob must be in [0,1]"
+        );
+        assert!(samples > 0, "samples must be positive");
+        let mut dropped = vec![false; x.len()];
+        let mut total = 0.0;
+        for _ in 0..samples {
+            dropped
+                .iter_mut()
+                .for_each(|d| *d = rng.random_bool(drop_prob));
+            total += self.weighted_mean(|t| t.score_with_dropped(x, &dropped));
+        }
+        total / samples as f64
+    }
+
+    /// Split the leaf score of `x` into `(rarity, recency)`: `rarity` is the score the
+    /// reached leaves would give from their undecayed visit counts, i.e. from raw
+    /// frequency alone, and `recency` the extra score due to decay having since eroded
+    /// that mass. They sum to the plain leaf score, as with `Linear` depth weighting. A
+    /// never-visited region is all rarity; a busy region that went quiet is mostly
+    /// recency. Masses loaded from a flat blob carry no visit history and count as
+    /// undecayed.
+    pub fn score_decomposition(&self, x: &FeatureVector) -> (f64, f64) {
+        (
+            self.weighted_mean(|t| t.score_decomposition(x).0),
+            self.weighted_mean(|t| t.score_decomposition(x).1),
+        )
+    }
+
+    /// Leave-one-dimension-out scores: entry `d` is the leaf score of `x` with every split
+    /// reading dimension `d` marginalized (both children averaged by mass, as in
+    /// [`HalfSpaceTrees::score_with_dropout`]). Unlike ablating to a neutral value, this
+    /// asks what the forest thinks of `x` knowing nothing about `d`; the dimension whose
+    /// omission lowers the score most is the key driver. Uses the plain leaf score, as
+    /// with `Linear` depth weighting.
+    pub fn lodo_scores(&self, x: &FeatureVector) -> Vec<f64> {
+        assert_eq!(x.len(), self.n_dims());
+        let mut dropped = vec![false; x.len()];
+        (0..x.len())
+            .map(|d| {
+                dropped[d] = true;
+                let score = self.weighted_mean(|t| t.score_with_dropped(x, &dropped));
+                dropped[d] = false;
+                score
+            })
+            .collect()
+    }
+
+    /// Leaf mass [`HalfSpaceTrees::surprise`] substitutes for smaller masses by default.
+    pub const DEFAULT_SURPRISE_FLOOR: f64 = 0.01;
+
+    /// Information-theoretic anomaly measure: `-ln` of the density estimate at `x`, the
+    /// weighted mean over trees of the reached leaf's share of its tree's mass divided by
+    /// the leaf's volume. Leaf masses below the floor set by
+    /// [`HalfSpaceTrees::set_surprise_floor`] count as the floor, so unseen regions get a
+    /// large but finite surprise. Higher means rarer; differences are in nats.
+    pub fn surprise(&self, x: &FeatureVector) -> f64 {
+        assert_eq!(x.len(), self.n_dims());
+        let floor = self.surprise_floor;
+        let density = self.weighted_mean(|t| {
+            let volume: f64 = t
+                .leaf_region(x)
+                .iter()
+                .map(|(lo, hi)| (hi - lo).max(0.0))
+                .product();
+            t.leaf_mass(x).max(floor) / (t.root.mass.max(floor) * volume.max(f64::MIN_POSITIVE))
+        });
+        -density.ln()
+    }
+
+    /// Set the leaf mass floor for [`HalfSpaceTrees::surprise`]; see
+    /// [`HalfSpaceTrees::DEFAULT_SURPRISE_FLOOR`].
+    pub fn set_surprise_floor(&mut self, floor: f64) {
+        assert!(floor > 0.0, "surprise floor must be positive");
+        self.surprise_floor = floor;
+    }
+
+    /// Draw a point from the learned density: a uniformly chosen tree among those holding
+    /// mass, then [`HalfSpaceTree::sample_normal`]. `None` for a forest without mass.
+    pub fn sample_normal<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<Vec<f64>> {
+        let massive: Vec<&HalfSpaceTree> =
+            self.trees.iter().filter(|t| t.total_mass() > 0.0).collect();
+        if massive.is_empty() {
+            return None;
+        }
+        massive[rng.random_range(0..massive.len())].sample_normal(rng)
+    }
+
+    /// Expected false-positive rate at `threshold` without labels: the fraction of
+    /// `samples` draws from [`HalfSpaceTrees::sample_normal`] whose score exceeds it.
+    /// Returns 0 for a forest without mass.
+    pub fn expected_fpr<R: Rng + ?Sized>(
+        &self,
+        threshold: f64,
+        samples: usize,
+        rng: &mut R,
+    ) -> f64 {
+        assert!(samples > 0, "samples must be positive");
+        let mut above = 0;
+        for _ in 0..samples {
+            match self.sample_normal(rng) {
+                Some(x) if self.score(&x) > threshold => above += 1,
+                Some(_) => {}
+                None => return 0.0,
+            }
+        }
+        above as f64 / samples as f64
+    }
+
+    /// Upper bound on the score of `x` under any perturbation of at most `eps` per feature.
+    /// Wherever a perturbation could cross a split both branches are explored: within
+    /// `eps` of the feature value, or for an oblique split within `eps * (|w0| + |w1|)` of
+    /// the projection `w·x`.
+    pub fn worst_case_score(&self, x: &FeatureVector, eps: f64) -> f64 {
+        self.weighted_mean(|t| t.worst_case_score(x, eps))
+    }
+
+    /// Bounding box of the learned support: per dimension, the extent of the non-empty
+    /// leaf regions. Every tree's extent contains all mass, so the trees' extents are
+    /// intersected, which is usually much tighter than the construction bounds.
+    /// Returns the construction bounds if no tree holds mass yet.
+    pub fn learned_extent(&self) -> Vec<(f64, f64)> {
+        let mut extent: Option<Vec<(f64, f64)>> = None;
+        for e in self.trees.iter().filter_map(|t| t.learned_extent()) {
+            match &mut extent {
+                Some(acc) => {
+                    for (a, b) in acc.iter_mut().zip(e) {
+                        a.0 = a.0.max(b.0);
+                        a.1 = a.1.min(b.1);
+                    }
+                }
+                None => extent = Some(e),
+            }
+        }
+        extent.unwrap_or_else(|| self.bounds.clone())
+    }
+
+    /// For each dimension `d`, the score of `x` with `x[d]` replaced by the midpoint of its
+    /// bounds and every other feature unchanged. A large drop from `score(x)` means that
+    /// feature drove the anomaly.
+    pub fn ablation_scores(&self, x: &FeatureVector) -> Vec<f64> {
+        let mut probe = x.to_vec();
+        self.bounds
+            .iter()
+            .enumerate()
+            .map(|(d, &(lo, hi))| {
+                probe[d] = 0.5 * (lo + hi);
+                let s = self.score(&probe);
+                probe[d] = x[d];
+                s
+            })
+            .collect()
+    }
+
+    /// Counterfactual hint: a copy of `x` with one feature moved just across a split so
+    /// that it lands in a better-populated leaf. Trees are tried from the highest-scoring
+    /// down, and within a tree the smallest edit first; the first edit that lowers the
+    /// forest score wins. Returns `x` unchanged if no single split flip helps.
+    pub fn nearest_normal_suggestion(&self, x: &FeatureVector) -> Vec<f64> {
+        let base = self.score(x);
+        let mut order: Vec<(usize, f64)> =
+            self.trees.iter().map(|t| t.score(x)).enumerate().collect();
+        order.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        let mut candidate = x.to_vec();
+        for (i, _) in order {
+            for (dim, v) in self.trees[i].split_flips(x) {
+                candidate[dim] = v;
+                if self.score(&candidate) < base {
+                    return candidate;
+                }
+                candidate[dim] = x[dim];
+            }
+        }
+        candidate
+    }
+
+    /// Counterfactual with a target: starting from `x`, repeatedly apply the single split
+    /// flip (see [`HalfSpaceTrees::nearest_normal_suggestion`]) that lowers the forest score
+    /// the most, preferring the smaller edit on ties, until the score falls below
+    /// `target_score`. Flips land on split thresholds, so the result stays within bounds.
+    /// Returns `None` if no flip lowers the score before the target is reached.
+    pub fn minimal_perturbation_to_normal(
+        &self,
+        x: &FeatureVector,
+        target_score: f64,
+    ) -> Option<Vec<f64>> {
+        let mut current = x.to_vec();
+        let mut score = self.score(&current);
+        while score >= target_score {
+            let mut best: Option<(f64, f64, usize, f64)> = None;
+            let mut candidate = current.clone();
+            for t in &self.trees {
+                for (dim, v) in t.split_flips(&current) {
+                    candidate[dim] = v;
+                    let s = self.score(&candidate);
+                    candidate[dim] = current[dim];
+                    let step = (v - current[dim]).abs();
+                    let better = match best {
+                        None => s < score,
+                        Some((bs, bstep, _, _)) => s < bs || (s == bs && step < bstep),
+                    };
+                    if better {
+                        best = Some((s, step, dim, v));
+                    }
+                }
+            }
+            let (s, _, dim, v) = best?;
+            current[dim] = v;
+            score = s;
+        }
+        Some(current)
+    }
+
+    /// `n x n` matrix whose `(i, j)` entry is the fraction of trees in which `xs[i]` and
+    /// `xs[j]` land in the same leaf. Symmetric with a unit diagonal.
+    pub fn agreement_matrix(&self, xs: &[Vec<f64>]) -> Vec<Vec<f64>> {
+        let n = xs.len();
+        let mut m = vec![vec![0.0; n]; n];
+        for t in &self.trees {
+            let leaves: Vec<&Node> = xs.iter().map(|x| t.leaf(x)).collect();
+            for i in 0..n {
+                for j in i + 1..n {
+                    if std::ptr::eq(leaves[i], leaves[j]) {
+                        m[i][j] += 1.0;
+                        m[j][i] += 1.0;
+                    }
+                }
+            }
+        }
+        let n_trees = self.trees.len().max(1) as f64;
+        for (i, row) in m.iter_mut().enumerate() {
+            for v in row.iter_mut() {
+                *v /= n_trees;
+            }
+            row[i] = 1.0;
+        }
+        m
+    }
+
+    /// `n_trees x n_trees` Pearson correlation matrix of the per-tree scores over `probe`.
+    /// High off-diagonal entries flag redundant trees; pairs involving a tree with constant
+    /// scores get 0. The diagonal is 1.
+    pub fn tree_correlation(&self, probe: &[Vec<f64>]) -> Vec<Vec<f64>> {
+        let per_tree: Vec<Vec<f64>> = self
+            .trees
+            .iter()
+            .map(|t| probe.iter().map(|x| t.score(x)).collect())
+            .collect();
+        let n = per_tree.len();
+        let mut m = vec![vec![1.0; n]; n];
+        for i in 0..n {
+            for j in i + 1..n {
+                let r = pearson(&per_tree[i], &per_tree[j]);
+                m[i][j] = r;
+                m[j][i] = r;
+            }
+        }
+        m
+    }
+
+    /// Re-randomize redundant trees until no pair's score correlation over `probe` (see
+    /// [`HalfSpaceTrees::tree_correlation`]) exceeds `max_correlation`, or until
+    /// `10 * n_trees` trees have been redrawn. Each round redraws the tree with the largest
+    /// total excess correlation, carrying its density over as [`HalfSpaceTrees::rebuild`]
+    /// does, and resets its weight. Returns the number of trees redrawn.
+    pub fn ensure_diversity<R: Rng + ?Sized>(
+        &mut self,
+        probe: &[Vec<f64>],
+        max_correlation: f64,
+        rng: &mut R,
+    ) -> usize {
+        let tree_scores =
+            |t: &HalfSpaceTree| -> Vec<f64> { probe.iter().map(|x| t.score(x)).collect() };
+        let mut per_tree: Vec<Vec<f64>> = self.trees.iter().map(tree_scores).collect();
+        let cap = 10 * self.trees.len();
+        let mut redrawn = 0;
+        while redrawn < cap {
+            let excess: Vec<f64> = (0..per_tree.len())
+                .map(|i| {
+                    (0..per_tree.len())
+                        .filter(|&j| j != i)
+                        .map(|j| (pearson(&per_tree[i], &per_tree[j]) - max_correlation).max(0.0))
+                        .sum()
+                })
+                .collect();
+            let Some((worst, &e)) = excess.iter().enumerate().max_by(|a, b| a.1.total_cmp(b.1))
+            else {
+                break;
+            };
+            if e <= 0.0 {
+                break;
+            }
+            let redrawn_tree = self.trees[worst].rebuilt(rng);
+            let old = std::mem::replace(&mut self.trees[worst], redrawn_tree);
+            self.weights[worst] = 1.0;
+            if let Some(pool) = &mut self.pool {
+                pool.add_leaves(&old, -1.0);
+                pool.add_leaves(&self.trees[worst], 1.0);
+            }
+            if let Some(sources) = &mut self.sources {
+                sources.tree_redrawn(worst);
+            }
+            per_tree[worst] = tree_scores(&self.trees[worst]);
+            redrawn += 1;
+        }
+        redrawn
+    }
+
+    /// Shrink the forest to at most `max_trees` trees, evicting the least useful first:
+    /// exact geometric duplicates of a kept tree go first, then the trees whose leaf
+    /// occupancy is most degenerate (lowest entropy, i.e. mass piled into few leaves).
+    /// Panics if `max_trees` is 0: an empty forest has no score.
+    pub fn enforce_tree_cap(&mut self, max_trees: usize) {
+        assert!(max_trees > 0, "tree cap must be positive");
+        while self.trees.len() > max_trees {
+            let duplicate = (1..self.trees.len())
+                .find(|&i| (0..i).any(|j| self.trees[i].is_compatible_with(&self.trees[j])));
+            let evict = duplicate.unwrap_or_else(|| {
+                self.trees
+                    .iter()
+                    .enumerate()
+                    .min_by(|a, b| a.1.occupancy_entropy().total_cmp(&b.1.occupancy_entropy()))
+                    .map(|(i, _)| i)
+                    .unwrap()
+            });
+            let evicted = self.trees.remove(evict);
+            self.weights.remove(evict);
+            if let Some(pool) = &mut self.pool {
+                pool.add_leaves(&evicted, -1.0);
+            }
+            if let Some(sources) = &mut self.sources {
+                sources.tree_removed(evict);
+            }
+        }
+    }
+
+    /// True if `other` has the same tree count, depths, dimensionality and bit-identical
+    /// split geometry, so masses could be combined node by node.
+    pub fn is_compatible_with(&self, other: &HalfSpaceTrees) -> bool {
+        self.bounds.len() == other.bounds.len()
+            && self.trees.len() == other.trees.len()
+            && self
+                .trees
+                .iter()
+                .zip(&other.trees)
+                .all(|(a, b)| a.is_compatible_with(b))
+    }
+
+    /// Fraction of trees (by count) whose construction bounds do not contain `x`.
+    /// Outside its bounds a tree can only route `x` to an edge leaf, so high values mean
+    /// the aggregate score extrapolates rather than measures.
+    pub fn oob_tree_fraction(&self, x: &FeatureVector) -> f64 {
+        if self.trees.is_empty() {
+            return 0.0;
+        }
+        let outside = self.trees.iter().filter(|t| !t.in_bounds(x)).count();
+        outside as f64 / self.trees.len() as f64
+    }
+
+    /// The trees making up this forest.
+    pub fn trees(&self) -> &[HalfSpaceTree] {
+        &self.trees
+    }
+
+    /// Average score across trees
+    pub fn score(&self, x: &FeatureVector) -> f64 {
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+        let mut s = match &self.pool {
+            Some(pool) => self.weighted_mean(|t| pool.score(t, x)),
+            None if self.pools_leaf_masses() => 1.0 / (1.0 + self.trimmed_leaf_mass(x).1.max(0.0)),
+            None => self.weighted_mean(|t| t.score(x)),
+        };
+        if let Some(q) = self.score_quantum {
+            s = (s / q).round() * q;
+        }
+        #[cfg(feature = "metrics")]
+        Timing::record(&self.timing.score_nanos, &self.timing.scores, start);
+        s
+    }
+
+    /// Fraction of reached leaf masses dropped at each end under [`ScoreMethod::PooledMass`]
+    /// (rounded down, so fewer than 10 trees trim nothing).
+    pub const POOLED_MASS_TRIM: f64 = 0.1;
+
+    /// Whether `score` aggregates by [`ScoreMethod::PooledMass`].
+    fn pools_leaf_masses(&self) -> bool {
+        self.pooled_mass && !self.trees.is_empty()
+    }
+
+    /// Masses trimmed from each end and the mean of the rest, for
+    /// [`ScoreMethod::PooledMass`].
+    fn trimmed_leaf_mass(&self, x: &FeatureVector) -> (usize, f64) {
+        trimmed_mean(self.trees.iter().map(|t| t.leaf_mass(x)).collect())
+    }
+
+    /// Weighted mean score of `x` over only the trees with an internal split on `dim`
+    /// (axis-aligned or as one leg of an oblique split), to focus on anomalies along that
+    /// dimension. Returns `f64::NAN` if no tree splits on `dim`.
+    pub fn score_for_dim(&self, x: &FeatureVector, dim: usize) -> f64 {
+        let (mut s, mut w) = (0.0, 0.0);
+        for (t, &tw) in self.trees.iter().zip(&self.weights) {
+            if t.uses_dim(dim) {
+                s += tw * t.score(x);
+                w += tw;
+            }
+        }
+        if w > 0.0 { s / w } else { f64::NAN }
+    }
+
+    /// Score with per-dimension importance: each tree's score is scaled by the mean of
+    /// `dim_weights` over the splits on `x`'s path, where each split counts by how much it
+    /// raised the rarity of `x` (an oblique split uses the mean of its two dimensions).
+    /// All-ones weights reproduce `score`; raising a dimension's weight amplifies trees
+    /// that isolated `x` by cutting along it.
+    pub fn score_weighted_dims(&self, x: &FeatureVector, dim_weights: &[f64]) -> f64 {
+        assert_eq!(dim_weights.len(), self.n_dims(), "one weight per dimension");
+        self.weighted_mean(|t| t.score(x) * t.path_dim_weight(x, dim_weights))
+    }
+
+    /// Approximate `score(x)` visiting at most `max_nodes` nodes across all trees.
+    /// Trees descend in lockstep, one level per round, so a tight budget shortens every
+    /// path evenly rather than dropping whole trees; if the budget is below the tree count
+    /// only the first `max_nodes` trees take part. Each tree then scores the deepest node
+    /// it reached as if it were the leaf (the linear formula, whose depth factor already
+    /// favours shallow stops). The result is a coarser density estimate: shallow nodes
+    /// pool mass over large regions. With a budget covering every path it equals `score`
+    /// under the default depth weighting and score method.
+    pub fn score_with_budget(&self, x: &FeatureVector, max_nodes: usize) -> f64 {
+        assert_eq!(x.len(), self.n_dims(), "point dimensionality mismatch");
+        let mut reached: Vec<&Node> = self.trees.iter().take(max_nodes).map(|t| &t.root).collect();
+        let mut visits = reached.len();
+        let mut progressed = true;
+        while visits < max_nodes && progressed {
+            progressed = false;
+            for node in reached.iter_mut() {
+                if visits == max_nodes {
+                    break;
+                }
+                if let (Some(l), Some(r)) = (&node.left, &node.right) {
+                    *node = if node.goes_left(x) { l } else { r };
+                    visits += 1;
+                    progressed = true;
+                }
+            }
+        }
+        let (mut s, mut w) = (0.0, 0.0);
+        for ((node, t), &tw) in reached.iter().zip(&self.trees).zip(&self.weights) {
+            s += tw * node.leaf_score(t.max_depth);
+            w += tw;
+        }
+        if w > 0.0 { s / w } else { 0.0 }
+    }
+
+    /// Jensen-Shannon divergence (nats, in `[0, ln 2]`) between where `x` lands and where
+    /// the mass lies, averaged over trees with the tree weights. Per tree, `x` is a point
+    /// mass on its reached leaf and the reference is the normalized leaf-mass distribution;
+    /// with `p` the reached leaf's share this is
+    /// `0.5 * (ln(2 / (1 + p)) + p * ln(2p / (1 + p)) + (1 - p) * ln 2)`,
+    /// falling from `ln 2` for an empty leaf to 0 when the leaf holds all the mass.
+    /// Trees holding no mass count as `ln 2`.
+    pub fn js_anomaly(&self, x: &FeatureVector) -> f64 {
+        self.weighted_mean(|t| {
+            let total = t.total_mass();
+            if total <= 0.0 {
+                return std::f64::consts::LN_2;
+            }
+            let p = (t.leaf_mass(x) / total).clamp(0.0, 1.0);
+            let point_term = (2.0 / (1.0 + p)).ln();
+            let reached_term = if p > 0.0 {
+                p * (2.0 * p / (1.0 + p)).ln()
+            } else {
+                0.0
+            };
+            0.5 * (point_term + reached_term + (1.0 - p) * std::f64::consts::LN_2)
+        })
+    }
+
+    /// Round `score` results to the nearest multiple of `quantum` (or stop rounding with
+    /// `None`). This is for reproducible comparisons downstream, where last-bit float
+    /// differences would otherwise break equality checks; it does not add accuracy.
+    pub fn set_score_quantum(&mut self, quantum: Option<f64>) {
+        if let Some(q) = quantum {
+            assert!(q > 0.0, "score quantum must be positive");
+        }
+        self.score_quantum = quantum;
+    }
+
+    /// Fit an isotonic (non-decreasing) mapping from raw score to anomaly probability on
+    /// labeled examples, where `labels[i]` is true if `scores[i]` came from an anomaly.
+    /// Replaces any earlier fit. Only meaningful while the model stays close to the one
+    /// that produced `scores`.
+    pub fn fit_isotonic(&mut self, scores: &[f64], labels: &[bool]) {
+        self.calibration = Some(calibrate::Isotonic::fit(scores, labels));
+    }
+
+    /// Calibrated probability that `x` is an anomaly, from the mapping fitted by
+    /// [`HalfSpaceTrees::fit_isotonic`]. Scores outside the fitted range take the value at
+    /// the nearest end. Panics if no mapping has been fitted.
+    pub fn probability(&self, x: &FeatureVector) -> f64 {
+        let calibration = self
+            .calibration
+            .as_ref()
+            .expect("fit_isotonic must be called before probability");
+        calibration.apply(self.score(x))
+    }
+
+    /// Mean of `f` over trees, weighted by the tree weights (uniform unless reweighted).
+    fn weighted_mean<F: Fn(&HalfSpaceTree) -> f64>(&self, f: F) -> f64 {
+        let (mut s, mut w) = (0.0, 0.0);
+        for (t, &tw) in self.trees.iter().zip(&self.weights) {
+            s += tw * f(t);
+            w += tw;
+        }
+        s / w
+    }
+
+    /// Standard deviation of the forest score of `x` over the `n` leave-one-tree-out
+    /// sub-forests. Low values mean no single tree dominates the score. A sub-forest left
+    /// with no weight (its missing tree carried all of it) has no score and is skipped.
+    pub fn loo_stability(&self, x: &FeatureVector) -> f64 {
+        if self.trees.len() < 2 {
+            return 0.0;
+        }
+        let scores: Vec<f64> = self.trees.iter().map(|t| t.score(x)).collect();
+        let total: f64 = scores.iter().zip(&self.weights).map(|(s, w)| s * w).sum();
+        let weight: f64 = self.weights.iter().sum();
+        let loo: Vec<f64> = scores
+            .iter()
+            .zip(&self.weights)
+            .filter(|&(_, w)| weight - w > 0.0)
+            .map(|(s, w)| (total - s * w) / (weight - w))
+            .collect();
+        if loo.is_empty() {
+            return 0.0;
+        }
+        let n = loo.len() as f64;
+        let mean = loo.iter().sum::<f64>() / n;
+        (loo.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt()
+    }
+
+    /// Shannon entropy (nats) of the per-tree shares of the forest score of `x`, each
+    /// tree's share being its weighted score over the total. At most `ln(n_trees)`, reached
+    /// when every tree agrees; low values mean a few trees carry the score.
+    pub fn contribution_entropy(&self, x: &FeatureVector) -> f64 {
+        let contributions: Vec<f64> = self
+            .trees
+            .iter()
+            .zip(&self.weights)
+            .map(|(t, w)| (w * t.score(x)).max(0.0))
+            .collect();
+        let total: f64 = contributions.iter().sum();
+        if total <= 0.0 {
+            return 0.0;
+        }
+        contributions
+            .iter()
+            .map(|c| c / total)
+            .filter(|&p| p > 0.0)
+            .map(|p| -p * p.ln())
+            .sum()
+    }
+
+    /// Participation ratio `(sum c)^2 / sum c^2` of the per-tree weighted score
+    /// contributions `c` to `x`'s score: `n_trees` when every tree contributes equally,
+    /// near 1 when a single tree dominates. 0 if no tree contributes.
+    pub fn effective_trees(&self, x: &FeatureVector) -> f64 {
+        let (sum, sum_sq) = self
+            .trees
+            .iter()
+            .zip(&self.weights)
+            .map(|(t, w)| (w * t.score(x)).max(0.0))
+            .fold((0.0, 0.0), |(s, sq), c| (s + c, sq + c * c));
+        if sum_sq <= 0.0 {
+            return 0.0;
+        }
+        sum * sum / sum_sq
+    }
+
+    /// The split decision on any tree path that `x` is closest to flipping, as
+    /// `(dimension, x[dimension] - split_value)`. A negative margin means `x` went left.
+    /// Returns `(0, f64::INFINITY)` if no tree has an internal node.
+    pub fn tightest_margin(&self, x: &FeatureVector) -> (usize, f64) {
+        self.trees
+            .iter()
+            .filter_map(|t| t.tightest_margin(x))
+            .min_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+            .unwrap_or((0, f64::INFINITY))
+    }
+
+    /// Fraction of `validation` points whose reached-leaf mass, averaged over trees,
+    /// exceeds `min_mass`, i.e. that land in regions the model has learned as dense.
+    pub fn coverage(&self, validation: &[Vec<f64>], min_mass: f64) -> f64 {
+        if validation.is_empty() || self.trees.is_empty() {
+            return 0.0;
+        }
+        let covered = validation
+            .iter()
+            .filter(|x| {
+                let mass: f64 = self.trees.iter().map(|t| t.leaf_mass(x)).sum();
+                mass / self.trees.len() as f64 > min_mass
+            })
+            .count();
+        covered as f64 / validation.len() as f64
+    }
+
+    /// Bytes held by tree nodes at each depth level, summed over all trees.
+    /// For full trees level `d` holds `n_trees * 2^d` nodes.
+    pub fn memory_by_level(&self) -> Vec<usize> {
+        let mut levels = Vec::new();
+        for t in &self.trees {
+            t.root.count_by_level(&mut levels);
+        }
+        levels
+            .into_iter()
+            .map(|n| n * std::mem::size_of::<Node>())
+            .collect()
+    }
+
+    /// Total leaf mass at each depth level, summed over all trees: where in depth the
+    /// learned mass resides. Full trees hold everything at their `max_depth`; leaves
+    /// collapsed by [`HalfSpaceTree::merge_similar_leaves`] sit higher. The entries sum to
+    /// the trees' summed total mass whenever parents equal their children's sum, which
+    /// `process_fused` and `sanitize_masses` do not preserve.
+    pub fn depth_mass_histogram(&self) -> Vec<f64> {
+        let mut hist = Vec::new();
+        for t in &self.trees {
+            let mut leaves = Vec::new();
+            t.root.leaves(&mut leaves);
+            for leaf in leaves {
+                let d = leaf.depth as usize;
+                if hist.len() <= d {
+                    hist.resize(d + 1, 0.0);
+                }
+                hist[d] += leaf.mass;
+            }
+        }
+        hist
+    }
+
+    /// Total bytes held by tree nodes across the forest.
+    pub fn memory_bytes(&self) -> usize {
+        self.memory_by_level().iter().sum()
+    }
+
+    /// `self.score(x) - reference.score(x)`: positive where the current model finds `x`
+    /// more anomalous than the baseline did, negative where it has since learned the region.
+    pub fn relative_score(&self, reference: &HalfSpaceTrees, x: &FeatureVector) -> f64 {
+        assert_eq!(
+            self.n_dims(),
+            reference.n_dims(),
+            "reference forest dimensionality mismatch"
+        );
+        self.score(x) - reference.score(x)
+    }
+
+    /// Running (weighted) mean score of `x` over the first `step`, `2 * step`, ... trees,
+    /// ending with the full forest. Shows how many trees the score needs to settle.
+    pub fn score_convergence(&self, x: &FeatureVector, step: usize) -> Vec<f64> {
+        assert!(step > 0, "step must be positive");
+        let (mut s, mut w) = (0.0, 0.0);
+        let mut out = Vec::with_capacity(self.trees.len().div_ceil(step));
+        for (i, (t, &tw)) in self.trees.iter().zip(&self.weights).enumerate() {
+            s += tw * t.score(x);
+            w += tw;
+            if (i + 1) % step == 0 || i + 1 == self.trees.len() {
+                out.push(s / w);
+            }
+        }
+        out
+    }
+
+    /// Score `probe`, apply `decay(alpha)` (this mutates the forest), score again, and
+    /// return the Spearman rank correlation of the two score lists. Near 1 means decay
+    /// leaves the anomaly ordering intact, as it should for stationary data.
+    pub fn ranking_stability(&mut self, probe: &[Vec<f64>], alpha: f64) -> f64 {
+        let before: Vec<f64> = probe.iter().map(|x| self.score(x)).collect();
+        self.decay(alpha);
+        let after: Vec<f64> = probe.iter().map(|x| self.score(x)).collect();
+        pearson(&ranks(&before), &ranks(&after))
+    }
+
+    /// Spearman rank correlation between the forest's scores and `baseline`'s over
+    /// `probe`, e.g. against a per-dimension z-score detector. Low agreement alongside a
+    /// good forest AUC means the trees pick up structure the baseline misses. Only the
+    /// orderings matter, so the two need not share a scale; 0 if either is constant.
+    pub fn compare_to_baseline<F: Fn(&[f64]) -> f64>(
+        &self,
+        baseline: F,
+        probe: &[Vec<f64>],
+    ) -> f64 {
+        let forest: Vec<f64> = probe.iter().map(|x| self.score(x)).collect();
+        let base: Vec<f64> = probe.iter().map(|x| baseline(x)).collect();
+        pearson(&ranks(&forest), &ranks(&base))
+    }
+
+    /// Estimated gain in separating `probe` (suspected anomalies) from normal traffic if
+    /// every leaf grew `extra_depth` more levels of random axis-aligned splits. Without the
+    /// original data, both the current and the deepened geometry are refit to the same
+    /// draws from the learned density, as many as the mean total mass (at most 10 000).
+    /// Draws follow [`HalfSpaceTree::sample_normal`] from a random tree holding mass,
+    /// except that each coordinate comes from a normal fit to the stored moments of the
+    /// deepest node on the path splitting on it, which keeps structure finer than the
+    /// leaves. Each refit is scored on `probe` and on 500 fresh draws, and the result is
+    /// the deepened forest's
+    /// [`eval::pairwise_ranking_accuracy`] minus the current one's, in [-1, 1]. Positive
+    /// values suggest more depth would resolve structure the current leaves blur; 0 for a
+    /// forest without mass. The forest itself is untouched.
+    pub fn deepen_benefit<R: Rng + ?Sized>(
+        &self,
+        probe: &[Vec<f64>],
+        extra_depth: u32,
+        rng: &mut R,
+    ) -> f64 {
+        assert!(extra_depth > 0, "extra_depth must be positive");
+        assert!(!probe.is_empty(), "probe must not be empty");
+        let mass =
+            self.trees.iter().map(|t| t.total_mass()).sum::<f64>() / self.trees.len().max(1) as f64;
+        let holders: Vec<&HalfSpaceTree> =
+            self.trees.iter().filter(|t| t.total_mass() > 0.0).collect();
+        if mass <= 0.0 || holders.is_empty() {
+            return 0.0;
+        }
+        let mut draw = |n: usize| -> Vec<Vec<f64>> {
+            (0..n)
+                .map(|_| {
+                    let t = holders[rng.random_range(0..holders.len())];
+                    t.sample_with_moments(rng).expect("tree has mass")
+                })
+                .collect()
+        };
+        let n = (mass.round() as usize).clamp(1, 10_000);
+        let (train, normal) = (draw(n), draw(probe.len().max(500)));
+
+        let refit = |trees: Vec<HalfSpaceTree>| -> f64 {
+            let mut forest = Self::from_trees(trees, &self.bounds);
+            forest.weights = self.weights.clone();
+            forest.decay(0.0);
+            for x in &train {
+                forest.insert_weighted(x, mass / n as f64);
+            }
+            let score =
+                |xs: &[Vec<f64>]| -> Vec<f64> { xs.iter().map(|x| forest.score(x)).collect() };
+            eval::pairwise_ranking_accuracy(&score(probe), &score(&normal))
+        };
+        let deeper: Vec<HalfSpaceTree> = self
+            .trees
+            .iter()
+            .map(|t| t.deepened(extra_depth, rng))
+            .collect();
+        refit(deeper) - refit(self.trees.clone())
+    }
+
+    /// For each candidate tree, how much adding it (with weight 1) would change the
+    /// standardized separation of `probe` scores, as defined for
+    /// [`HalfSpaceTrees::smoothing_sweep`]. Positive gains mark trees worth adding in a
+    /// greedy ensemble build; a tree scoring every probe alike gains nothing.
+    pub fn candidate_gain(
+        &self,
+        candidates: &[HalfSpaceTree],
+        probe: &[Vec<f64>],
+        labels: &[bool],
+    ) -> Vec<f64> {
+        assert_eq!(probe.len(), labels.len(), "one label per probe point");
+        let sums: Vec<f64> = probe
+            .iter()
+            .map(|x| {
+                self.trees
+                    .iter()
+                    .zip(&self.weights)
+                    .map(|(t, w)| w * t.score(x))
+                    .sum()
+            })
+            .collect();
+        let weight: f64 = self.weights.iter().sum();
+        let base: Vec<f64> = sums.iter().map(|s| s / weight).collect();
+        let base = separation(&base, labels);
+        candidates
+            .iter()
+            .map(|c| {
+                let with: Vec<f64> = probe
+                    .iter()
+                    .zip(&sums)
+                    .map(|(x, s)| (s + c.score(x)) / (weight + 1.0))
+                    .collect();
+                separation(&with, labels) - base
+            })
+            .collect()
+    }
+
+    /// Per-tree weights used when averaging scores.
+    pub fn tree_weights(&self) -> &[f64] {
+        &self.weights
+    }
+
+    /// Add a tree built over the same bounds, with weight 1.
+    pub fn add_tree(&mut self, tree: HalfSpaceTree) {
+        assert_eq!(tree.n_dims, self.n_dims(), "tree dimensionality mismatch");
+        if let Some(pool) = &mut self.pool {
+            pool.add_leaves(&tree, 1.0);
+        }
+        self.trees.push(tree);
+        self.weights.push(1.0);
+        if let Some(sources) = &mut self.sources {
+            sources.tree_added();
+        }
+    }
+
+    /// Weight each tree by how well its scores on `holdout` correlate (Pearson) with the
+    /// forest-mean score; trees with non-positive or undefined correlation get weight 0.
+    /// Falls back to uniform weights if no tree correlates positively.
+    pub fn reweight_by_holdout(&mut self, holdout: &[Vec<f64>]) {
+        let per_tree: Vec<Vec<f64>> = self
+            .trees
+            .iter()
+            .map(|t| holdout.iter().map(|x| t.score(x)).collect())
+            .collect();
+        let consensus: Vec<f64> = (0..holdout.len())
+            .map(|i| per_tree.iter().map(|s| s[i]).sum::<f64>() / per_tree.len() as f64)
+            .collect();
+        self.weights = per_tree
+            .iter()
+            .map(|s| pearson(s, &consensus).max(0.0))
+            .collect();
+        if self.weights.iter().all(|&w| w == 0.0) {
+            self.weights.iter_mut().for_each(|w| *w = 1.0);
+        }
+    }
+
+    /// Accumulated wall-clock time spent in `insert` and `score` (requires the `metrics` feature).
+    #[cfg(feature = "metrics")]
+    pub fn timing_stats(&self) -> TimingStats {
+        use std::sync::atomic::Ordering::Relaxed;
+        TimingStats {
+            insert_nanos: self.timing.insert_nanos.load(Relaxed),
+            inserts: self.timing.inserts.load(Relaxed),
+            score_nanos: self.timing.score_nanos.load(Relaxed),
+            scores: self.timing.scores.load(Relaxed),
+        }
+    }
+
+    /// Insert a sparse point given as `(index, value)` pairs; omitted indices default to 0.0.
+    pub fn insert_sparse<I>(&mut self, entries: I)
+    where
+        I: IntoIterator<Item = (usize, f64)>,
+    {
+        let x = densify(entries, self.n_dims());
+        self.insert(&x);
+    }
+
+    /// Score a sparse point given as `(index, value)` pairs; omitted indices default to 0.0.
+    pub fn score_sparse<I>(&self, entries: I) -> f64
+    where
+        I: IntoIterator<Item = (usize, f64)>,
+    {
+        let x = densify(entries, self.n_dims());
+        self.score(&x)
+    }
+
+    /// Score `x`, then decay and insert it, returning the score. The decay is chosen per
+    /// tree so that after the insert its mean leaf mass (root mass over its leaf count)
+    /// equals `target_leaf_mass`: no decay while the tree is still filling up, then
+    /// `alpha = (target_total - weight) / current_total`, where `weight` is what `insert`
+    /// gives `x` under the weight schedule. Scores thus stay in the same range whatever
+    /// the stream's rate. Panics if some tree's target total does not exceed `weight`, as
+    /// the decay would have to wipe that tree.
+    pub fn process_occupancy_controlled(
+        &mut self,
+        x: &FeatureVector,
+        target_leaf_mass: f64,
+    ) -> f64 {
+        assert!(target_leaf_mass > 0.0, "target_leaf_mass must be positive");
+        let weight = self.weight_schedule.weight(self.inserts);
+        let targets: Vec<f64> = self
+            .trees
+            .iter()
+            .map(|t| target_leaf_mass * t.root.leaf_count() as f64)
+            .collect();
+        assert!(
+            targets.iter().all(|&total| total > weight),
+            "target_leaf_mass too small to hold a single insert"
+        );
+        let score = self.score(x);
+        for (t, target_total) in self.trees.iter_mut().zip(targets) {
+            let current = t.total_mass();
+            if current > 0.0 {
+                t.decay(((target_total - weight) / current).min(1.0));
+            }
+        }
+        self.sync_pool();
+        self.insert(x);
+        score
+    }
+
+    /// Score `x`, then insert it, returning `(raw, smoothed)`.
+    /// The smoothed series is an EWMA: `s = beta * raw + (1 - beta) * s_prev`, with `beta` in (0,1].
+    /// The first call seeds the smoother with the raw score.
+    pub fn process_smoothed(&mut self, x: &FeatureVector, beta: f64) -> (f64, f64) {
+        assert!(beta > 0.0 && beta <= 1.0, "beta must be in (0,1]");
+        let raw = self.score(x);
+        let smoothed = match self.smoothed {
+            Some(prev) => beta * raw + (1.0 - beta) * prev,
+            None => raw,
+        };
+        self.smoothed = Some(smoothed);
+        self.insert(x);
+        (raw, smoothed)
+    }
+
+    /// Number of recent scores [`HalfSpaceTrees::combined_severity`] ranks against.
+    pub const SEVERITY_WINDOW: usize = 1024;
+
+    /// Alert severity of `x` in [0,1] requiring both rarity and agreement: the percentile
+    /// rank of its score among the last [`HalfSpaceTrees::SEVERITY_WINDOW`] scores passed
+    /// through this method (including its own), times the weighted fraction of trees whose
+    /// own score of `x` exceeds `per_tree_threshold`. A score a few trees push up on their
+    /// own ranks high but gets little consensus. Records the score; does not insert `x`.
+    pub fn combined_severity(&mut self, x: &FeatureVector, per_tree_threshold: f64) -> f64 {
+        let score = self.score(x);
+        if self.severity_window.len() == Self::SEVERITY_WINDOW {
+            self.severity_window.pop_front();
+        }
+        self.severity_window.push_back(score);
+        let at_or_below = self.severity_window.iter().filter(|&&s| s <= score).count();
+        let percentile = at_or_below as f64 / self.severity_window.len() as f64;
+        let consensus = self.weighted_mean(|t| {
+            if t.score(x) > per_tree_threshold {
+                1.0
+            } else {
+                0.0
+            }
+        });
+        percentile * consensus
+    }
+
+    /// The `k` highest-scoring points of `xs` as `(index, score)`, highest first.
+    /// Equal scores keep their input order (lower index first), so the ranking is
+    /// reproducible whatever the platform's float quirks in sorting.
+    pub fn top_k_anomalies(&self, xs: &[Vec<f64>], k: usize) -> Vec<(usize, f64)> {
+        let mut ranked: Vec<(usize, f64)> = xs.iter().map(|x| self.score(x)).enumerate().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked.truncate(k);
+        ranked
+    }
+
+    /// Filter a stream down to its anomalies: yields `(x, score)` for each point scoring
+    /// above `threshold`, in stream order. Under [`ProcessMode::ScoreThenInsert`] every
+    /// point, flagged or not, is inserted after being scored. The stream is consumed lazily.
+    pub fn anomalies<'a, I>(
+        &'a mut self,
+        iter: I,
+        threshold: f64,
+        mode: ProcessMode,
+    ) -> impl Iterator<Item = (Vec<f64>, f64)> + 'a
+    where
+        I: Iterator<Item = Vec<f64>> + 'a,
+    {
+        iter.filter_map(move |x| {
+            let score = self.score(&x);
+            if mode == ProcessMode::ScoreThenInsert {
+                self.insert(&x);
+            }
+            (score > threshold).then_some((x, score))
+        })
+    }
+
+    /// Mean Shannon entropy (nats) of the normalized leaf-mass distribution per tree.
+    /// High values mean mass is spread out; low values mean it collapsed into few leaves.
+    pub fn occupancy_entropy(&self) -> f64 {
+        if self.trees.is_empty() {
+            return 0.0;
+        }
+        let total: f64 = self.trees.iter().map(|t| t.occupancy_entropy()).sum();
+        total / self.trees.len() as f64
+    }
+
+    /// Learned mass along `dim`, averaged over trees, in `bins` equal-width bins spanning
+    /// the construction bounds of `dim`. Each leaf's mass is spread uniformly over its
+    /// region's extent on `dim`, so the histogram is only as sharp as the splits.
+    pub fn marginal(&self, dim: usize, bins: usize) -> Vec<f64> {
+        assert!(dim < self.n_dims(), "dim out of range");
+        assert!(bins > 0, "bins must be positive");
+        let (lo, hi) = self.bounds[dim];
+        let width = (hi - lo) / bins as f64;
+        let mut hist = vec![0.0; bins];
+        for t in &self.trees {
+            t.for_each_leaf_region(|leaf, region| {
+                let (a, b) = region[dim];
+                if leaf.mass <= 0.0 || b <= a {
+                    return;
+                }
+                for (i, h) in hist.iter_mut().enumerate() {
+                    let (bin_lo, bin_hi) = (lo + i as f64 * width, lo + (i + 1) as f64 * width);
+                    let overlap = b.min(bin_hi) - a.max(bin_lo);
+                    if overlap > 0.0 {
+                        *h += leaf.mass * overlap / (b - a);
+                    }
+                }
+            });
+        }
+        let n_trees = self.trees.len().max(1) as f64;
+        hist.iter_mut().for_each(|h| *h /= n_trees);
+        hist
+    }
+
+    /// Scale leaf masses so each tree's [`HalfSpaceTrees::marginal`] along `dim` over
+    /// `target_bins.len()` bins approaches `target_bins`, e.g. to correct a known sampling
+    /// bias. The target is a relative shape, rescaled to each tree's total mass. Every bin
+    /// gets the factor `target / current`, and a leaf is scaled by the average factor of
+    /// the bins it overlaps, so leaves wider than a bin only move part way. Parents are
+    /// recomputed from their children; bins without mass stay empty.
+    pub fn reweight_to_marginal(&mut self, dim: usize, target_bins: &[f64]) {
+        assert!(dim < self.n_dims(), "dim out of range");
+        assert!(!target_bins.is_empty(), "target_bins must not be empty");
+        assert!(
+            target_bins.iter().all(|t| t.is_finite() && *t >= 0.0),
+            "target_bins must be finite and non-negative"
+        );
+        let target_total: f64 = target_bins.iter().sum();
+        assert!(target_total > 0.0, "target_bins must not all be zero");
+        let (lo, hi) = self.bounds[dim];
+        let bins = target_bins.len();
+        let width = (hi - lo) / bins as f64;
+        for t in &mut self.trees {
+            let mut hist = vec![0.0; bins];
+            t.for_each_leaf_region(|leaf, region| {
+                let (a, b) = region[dim];
+                if leaf.mass <= 0.0 || b <= a {
+                    return;
+                }
+                for (i, h) in hist.iter_mut().enumerate() {
+                    let (bin_lo, bin_hi) = (lo + i as f64 * width, lo + (i + 1) as f64 * width);
+                    let overlap = b.min(bin_hi) - a.max(bin_lo);
+                    if overlap > 0.0 {
+                        *h += leaf.mass * overlap / (b - a);
+                    }
+                }
+            });
+            let mass: f64 = hist.iter().sum();
+            let factors: Vec<f64> = hist
+                .iter()
+                .zip(target_bins)
+                .map(|(&h, &target)| {
+                    if h > 0.0 {
+                        target / target_total * mass / h
+                    } else {
+                        1.0
+                    }
+                })
+                .collect();
+            let mut region = t.bounds.clone();
+            t.root
+                .reweight_leaves(&mut region, dim, (lo, width), &factors);
+        }
+        self.sync_pool();
+    }
+
+    /// Number of modes of the learned density along `dim`: peaks of
+    /// [`HalfSpaceTrees::marginal`] over `bins` bins, after smoothing with a `[1, 2, 1] / 4`
+    /// kernel. To ignore sampling wiggles, a peak counts only if the curve rises to it and
+    /// then falls from it by at least 10% of the highest bin (the range is zero beyond both
+    /// ends). Returns 0 for a forest without mass.
+    pub fn estimate_modes(&self, dim: usize, bins: usize) -> usize {
+        let hist = self.marginal(dim, bins);
+        let at = |i: isize| -> f64 {
+            if i < 0 || i >= bins as isize {
+                0.0
+            } else {
+                hist[i as usize]
+            }
+        };
+        let smooth: Vec<f64> = (0..bins as isize)
+            .map(|i| (at(i - 1) + 2.0 * at(i) + at(i + 1)) / 4.0)
+            .collect();
+        let delta = 0.1 * smooth.iter().copied().fold(0.0, f64::max);
+        if delta <= 0.0 {
+            return 0;
+        }
+        let (mut modes, mut trough, mut peak) = (0, 0.0, None);
+        for v in smooth.into_iter().chain([0.0]) {
+            match peak {
+                None => {
+                    trough = f64::min(trough, v);
+                    if v - trough >= delta {
+                        peak = Some(v);
+                    }
+                }
+                Some(p) if v > p => peak = Some(v),
+                Some(p) if p - v >= delta => {
+                    modes += 1;
+                    peak = None;
+                    trough = v;
+                }
+                Some(_) => {}
+            }
+        }
+        modes
+    }
+
+    /// Per bin of `dim`'s bounds (split into `bins` equal bins), whether it is a coverage
+    /// gap: ranges the model has never seen populated, where every score is extrapolation.
+    /// Each tree's non-empty leaves contain all inserted points, so a bin is a gap as soon
+    /// as one tree has no leaf holding mass that overlaps it. As with
+    /// [`HalfSpaceTrees::learned_extent`], intersecting trees gives much tighter answers
+    /// than any single coarse tree.
+    pub fn coverage_gaps(&self, dim: usize, bins: usize) -> Vec<bool> {
+        assert!(dim < self.n_dims(), "dim out of range");
+        assert!(bins > 0, "bins must be positive");
+        let (lo, hi) = self.bounds[dim];
+        let width = (hi - lo) / bins as f64;
+        let mut gaps = vec![self.trees.is_empty(); bins];
+        for t in &self.trees {
+            let mut covered = vec![false; bins];
+            t.for_each_leaf_region(|leaf, region| {
+                if leaf.mass <= 0.0 {
+                    return;
+                }
+                let (a, b) = region[dim];
+                for (i, c) in covered.iter_mut().enumerate() {
+                    let (bin_lo, bin_hi) = (lo + i as f64 * width, lo + (i + 1) as f64 * width);
+                    if a < bin_hi && b > bin_lo {
+                        *c = true;
+                    }
+                }
+            });
+            for (g, c) in gaps.iter_mut().zip(covered) {
+                *g |= !c;
+            }
+        }
+        gaps
+    }
+
+    /// Bounding box of the sparsest part of the space by consensus of all trees: the
+    /// bounds are cut into `grid_res` cells per dimension, each cell is scored at its
+    /// center by the whole forest, and the box covering every cell within 5% of the
+    /// highest score is returned. Averaging over trees smooths out the coarse, noisy
+    /// picture any single tree gives, and the 5% band keeps near-ties that differ only by
+    /// which leaves happen to straddle them. The grid has `grid_res^n_dims` cells, so keep
+    /// it small in high dimensions.
+    pub fn consensus_sparse_region(&self, grid_res: usize) -> Vec<(f64, f64)> {
+        assert!(grid_res > 0, "grid_res must be positive");
+        let n_dims = self.n_dims();
+        let cells = (0..n_dims).try_fold(1usize, |acc, _| acc.checked_mul(grid_res));
+        let cells = cells
+            .filter(|&c| c <= 1 << 20)
+            .expect("grid too large: at most 2^20 cells");
+        let width: Vec<f64> = self
+            .bounds
+            .iter()
+            .map(|(lo, hi)| (hi - lo) / grid_res as f64)
+            .collect();
+        let cell_of = |mut c: usize| -> Vec<usize> {
+            (0..n_dims)
+                .map(|_| {
+                    let i = c % grid_res;
+                    c /= grid_res;
+                    i
+                })
+                .collect()
+        };
+        let scores: Vec<f64> = (0..cells)
+            .map(|c| {
+                let center: Vec<f64> = cell_of(c)
+                    .iter()
+                    .zip(&self.bounds)
+                    .zip(&width)
+                    .map(|((&i, (lo, _)), w)| lo + (i as f64 + 0.5) * w)
+                    .collect();
+                self.score(&center)
+            })
+            .collect();
+        let best = scores.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let mut region = vec![(f64::INFINITY, f64::NEG_INFINITY); n_dims];
+        for (c, &s) in scores.iter().enumerate() {
+            if s < best - 0.05 * best.abs() {
+                continue;
+            }
+            for (((r, i), (lo, _)), w) in region
+                .iter_mut()
+                .zip(cell_of(c))
+                .zip(&self.bounds)
+                .zip(&width)
+            {
+                r.0 = r.0.min(lo + i as f64 * w);
+                r.1 = r.1.max(lo + (i + 1) as f64 * w);
+            }
+        }
+        region
+    }
+
+    /// Learned mass per orthant of the chosen `dims`, split at each dimension's bounds
+    /// midpoint, averaged over trees. Returns all `2^dims.len()` sign patterns in
+    /// lexicographic order (`true` = above the midpoint) with their mass; a leaf region
+    /// straddling a midpoint is shared in proportion to its width on each side. The masses
+    /// sum to the forest's mean total mass.
+    pub fn quadrant_mass(&self, dims: &[usize]) -> Vec<(Vec<bool>, f64)> {
+        assert!(dims.len() <= 16, "at most 16 dims");
+        assert!(dims.iter().all(|&d| d < self.n_dims()), "dim out of range");
+        let k = dims.len();
+        let patterns: Vec<Vec<bool>> = (0..1usize << k)
+            .map(|p| (0..k).map(|i| (p >> (k - 1 - i)) & 1 == 1).collect())
+            .collect();
+        let mut masses = vec![0.0; patterns.len()];
+        for t in &self.trees {
+            t.for_each_leaf_region(|leaf, region| {
+                if leaf.mass <= 0.0 {
+                    return;
+                }
+                let above: Vec<f64> = dims
+                    .iter()
+                    .map(|&d| {
+                        let (lo, hi) = self.bounds[d];
+                        let mid = 0.5 * (lo + hi);
+                        let (a, b) = region[d];
+                        if b > a {
+                            (b - a.max(mid)).max(0.0) / (b - a)
+                        } else if a >= mid {
+                            1.0
+                        } else {
+                            0.0
+                        }
+                    })
+                    .collect();
+                for (pattern, m) in patterns.iter().zip(&mut masses) {
+                    let share: f64 = pattern
+                        .iter()
+                        .zip(&above)
+                        .map(|(&up, &f)| if up { f } else { 1.0 - f })
+                        .product();
+                    *m += leaf.mass * share;
+                }
+            });
+        }
+        let n_trees = self.trees.len().max(1) as f64;
+        patterns
+            .into_iter()
+            .zip(masses)
+            .map(|(p, m)| (p, m / n_trees))
+            .collect()
+    }
+
+    /// Replace all learned mass with a warm start from the density `f`: in each tree,
+    /// every reachable leaf gets mass proportional to `f` at its region's center, scaled
+    /// so the tree holds `total_mass`. Negative values of `f` count as 0. A tree where `f`
+    /// is 0 everywhere is left empty.
+    pub fn seed_from_density<F: Fn(&[f64]) -> f64>(&mut self, f: F, total_mass: f64) {
+        assert!(total_mass >= 0.0, "total_mass must be non-negative");
+        for t in &mut self.trees {
+            t.seed_from_density(&f, total_mass);
+        }
+        self.sync_pool();
+    }
+
+    /// Indices of the trees where `x` passes within `eps` of a split on its path (in the
+    /// split's own coordinate, `w·x` for oblique splits), so a perturbation of about
+    /// `eps` could reroute it to a different leaf. Those trees' contributions are fragile.
+    pub fn unstable_trees(&self, x: &FeatureVector, eps: f64) -> Vec<usize> {
+        assert_eq!(x.len(), self.n_dims());
+        assert!(eps >= 0.0, "eps must be non-negative");
+        self.trees
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| {
+                let path = t.root.path(x);
+                path[..path.len() - 1]
+                    .iter()
+                    .any(|n| (n.coord(x) - n.split_val).abs() <= eps)
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Crude denoised copy of `x`: the centers of the leaf regions `x` reaches, averaged
+    /// over trees. Always inside the construction bounds; close to `x` where splits are fine.
+    pub fn reconstruct(&self, x: &FeatureVector) -> Vec<f64> {
+        let mut out = vec![0.0; self.n_dims()];
+        for t in &self.trees {
+            for (o, (lo, hi)) in out.iter_mut().zip(t.leaf_region(x)) {
+                *o += 0.5 * (lo + hi);
+            }
+        }
+        let n_trees = self.trees.len().max(1) as f64;
+        out.iter_mut().for_each(|o| *o /= n_trees);
+        out
+    }
+
+    /// Number of leaves, over all trees, whose rarity `1 / (1 + mass)` is below `eps`.
+    /// Such leaves score as essentially 0 no matter how much more mass arrives, so a
+    /// growing count is the cue to cap masses or decay more aggressively.
+    pub fn saturated_leaves(&self, eps: f64) -> usize {
+        let mut leaves = Vec::new();
+        for t in &self.trees {
+            t.root.leaves(&mut leaves);
+        }
+        leaves.iter().filter(|n| n.rarity() < eps).count()
+    }
+
+    /// Per dimension, the mean gap between consecutive axis-aligned split thresholds on
+    /// that dimension within a tree, counting the bounds as the outermost cuts and averaged
+    /// over trees. Smaller means the dimension is partitioned more finely; a dimension no
+    /// tree splits on reports its full width.
+    pub fn effective_resolution(&self) -> Vec<f64> {
+        let mut out = vec![0.0; self.n_dims()];
+        for t in &self.trees {
+            let mut cuts: Vec<Vec<f64>> =
+                self.bounds.iter().map(|&(lo, hi)| vec![lo, hi]).collect();
+            t.root.collect_thresholds(&mut cuts);
+            for (o, c) in out.iter_mut().zip(&mut cuts) {
+                c.sort_by(f64::total_cmp);
+                *o += (c[c.len() - 1] - c[0]) / (c.len() - 1) as f64;
+            }
+        }
+        let n_trees = self.trees.len().max(1) as f64;
+        out.iter_mut().for_each(|o| *o /= n_trees);
+        out
+    }
+
+    /// Add externally computed mass per axis-aligned box, as `(box, mass)` with the box
+    /// given per dimension as `(lo, hi)`. In each tree a box's mass is shared among the
+    /// leaves it overlaps in proportion to overlap volume; mass of a box lying entirely
+    /// outside the bounds is dropped. Oblique splits do not narrow leaf regions, so for
+    /// such trees the split is approximate.
+    pub fn apply_region_masses(&mut self, regions: &[(Vec<(f64, f64)>, f64)]) {
+        for t in &mut self.trees {
+            t.apply_region_masses(regions);
+        }
+        self.sync_pool();
+    }
+
+    /// One record per leaf of every tree, ordered by tree and then left to right.
+    pub fn leaf_table(&self) -> Vec<LeafRecord> {
+        let mut table = Vec::new();
+        for (i, t) in self.trees.iter().enumerate() {
+            let mut leaves = Vec::new();
+            t.root.leaves(&mut leaves);
+            for (j, (leaf, &volume)) in leaves.iter().zip(t.leaf_volumes()).enumerate() {
+                table.push(LeafRecord {
+                    tree: i,
+                    leaf: j,
+                    depth: leaf.depth,
+                    mass: leaf.mass,
+                    volume,
+                    rarity: leaf.rarity(),
+                });
+            }
+        }
+        table
+    }
+
+    /// Every tree's leaf masses in left-to-right order, concatenated tree by tree.
+    /// Keep one as the reference for [`HalfSpaceTrees::recent_activity`].
+    pub fn leaf_masses(&self) -> Vec<f64> {
+        self.trees.iter().flat_map(|t| t.leaf_masses()).collect()
+    }
+
+    /// How consistently the trees consider `x`'s region rare: per tree, the percentile
+    /// rank in [0,1) of the reached leaf's mass, i.e. the fraction of that tree's leaves
+    /// holding strictly less mass, averaged across trees. Near 0 means every tree put `x`
+    /// among its emptiest leaves (an empty leaf ranks 0 however many others are empty);
+    /// near 1, among its fullest. Ranks ignore mass scale, so trees with very different
+    /// totals weigh in equally.
+    pub fn rarity_consensus(&self, x: &FeatureVector) -> f64 {
+        assert_eq!(x.len(), self.n_dims());
+        self.weighted_mean(|t| {
+            let reached = t.leaf_mass(x);
+            let masses = t.leaf_masses();
+            let lighter = masses.iter().filter(|&&m| m < reached).count();
+            lighter as f64 / masses.len() as f64
+        })
+    }
+
+    /// How much mass the leaves `x` reaches have gained since `reference` (a
+    /// [`HalfSpaceTrees::leaf_masses`] snapshot), averaged across trees. Without decay
+    /// in between this is the number of inserts the region absorbed; decay can make it
+    /// negative.
+    pub fn recent_activity(&self, x: &FeatureVector, reference: &[f64]) -> f64 {
+        assert_eq!(x.len(), self.n_dims());
+        let n_leaves: usize = self.trees.iter().map(|t| t.root.leaf_count()).sum();
+        assert_eq!(
+            reference.len(),
+            n_leaves,
+            "reference must be a leaf mass snapshot"
+        );
+        if self.trees.is_empty() {
+            return 0.0;
+        }
+        let mut offset = 0;
+        let mut total = 0.0;
+        for t in &self.trees {
+            let then = reference[offset + t.root.leaf_position(x)];
+            total += t.leaf_mass(x) - then;
+            offset += t.root.leaf_count();
+        }
+        total / self.trees.len() as f64
+    }
+
+    /// Mean split balance across trees (see [`HalfSpaceTree::split_balance`]).
+    pub fn split_balance(&self) -> f64 {
+        if self.trees.is_empty() {
+            return 0.0;
+        }
+        let total: f64 = self.trees.iter().map(|t| t.split_balance()).sum();
+        total / self.trees.len() as f64
+    }
+
+    /// Replace every tree with one of freshly drawn splits over the same bounds, depth and
+    /// split mode, carrying the learned density over: each old leaf's mass is inserted at
+    /// its region's center. Tree weights reset to uniform since the trees are new.
+    pub fn rebuild<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        self.trees = self.trees.iter().map(|t| t.rebuilt(rng)).collect();
+        self.weights = vec![1.0; self.trees.len()];
+        self.sync_pool();
+        if let Some(sources) = &mut self.sources {
+            (0..self.trees.len()).for_each(|i| sources.tree_redrawn(i));
+        }
+    }
+
+    /// The `split_balance` that `rebuild(rng)` would produce, without mutating the forest.
+    /// Consumes `rng` exactly as `rebuild` would, so the same seed previews the same trees.
+    pub fn preview_rebuild_balance<R: Rng + ?Sized>(&self, rng: &mut R) -> f64 {
+        if self.trees.is_empty() {
+            return 0.0;
+        }
+        let total: f64 = self
+            .trees
+            .iter()
+            .map(|t| t.rebuilt(rng).split_balance())
+            .sum();
+        total / self.trees.len() as f64
+    }
+}
+
+/// Score distribution summary from [`HalfSpaceTrees::self_score_summary`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoreSummary {
+    pub min: f64,
+    pub mean: f64,
+    pub median: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+/// One row of [`HalfSpaceTrees::leaf_table`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LeafRecord {
+    /// Index of the tree in [`HalfSpaceTrees::trees`].
+    pub tree: usize,
+    /// Left-to-right position of the leaf within its tree.
+    pub leaf: usize,
+    pub depth: u32,
+    pub mass: f64,
+    /// Volume of the leaf's reconstructed region.
+    pub volume: f64,
+    /// `1 / (1 + mass)`.
+    pub rarity: f64,
+}
+
+/// Number of values dropped from each end and the mean of the rest, trimming
+/// [`HalfSpaceTrees::POOLED_MASS_TRIM`] of `masses` at each end.
+fn trimmed_mean(mut masses: Vec<f64>) -> (usize, f64) {
+    masses.sort_by(f64::total_cmp);
+    let k = (masses.len() as f64 * HalfSpaceTrees::POOLED_MASS_TRIM) as usize;
+    let kept = &masses[k..masses.len() - k];
+    (k, kept.iter().sum::<f64>() / kept.len() as f64)
+}
+
+/// Pearson correlation; 0 if either series is constant.
+fn pearson(a: &[f64], b: &[f64]) -> f64 {
+    let n = a.len() as f64;
+    let (ma, mb) = (a.iter().sum::<f64>() / n, b.iter().sum::<f64>() / n);
+    let (mut cov, mut va, mut vb) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        cov += (x - ma) * (y - mb);
+        va += (x - ma) * (x - ma);
+        vb += (y - mb) * (y - mb);
+    }
+    if va <= 0.0 || vb <= 0.0 {
+        return 0.0;
+    }
+    cov / (va * vb).sqrt()
+}
+
+/// One standard normal draw (Box-Muller).
+fn standard_normal<R: Rng + ?Sized>(rng: &mut R) -> f64 {
+    let u1: f64 = 1.0 - rng.random::<f64>();
+    let u2: f64 = rng.random();
+    (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+}
+
+/// Standardized separation of labeled scores (`true` = anomaly): the anomaly mean minus
+/// the normal mean over the pooled standard deviation. 0 if a class is missing or the
+/// scores do not vary.
+fn separation(scores: &[f64], labels: &[bool]) -> f64 {
+    let class = |want: bool| -> Vec<f64> {
+        scores
+            .iter()
+            .zip(labels)
+            .filter(|&(_, &l)| l == want)
+            .map(|(&s, _)| s)
+            .collect()
+    };
+    let (out, inl) = (class(true), class(false));
+    if out.is_empty() || inl.is_empty() {
+        return 0.0;
+    }
+    let mean = |v: &[f64]| v.iter().sum::<f64>() / v.len() as f64;
+    let ss = |v: &[f64], m: f64| v.iter().map(|s| (s - m).powi(2)).sum::<f64>();
+    let (mo, mi) = (mean(&out), mean(&inl));
+    let pooled = ((ss(&out, mo) + ss(&inl, mi)) / scores.len() as f64).sqrt();
+    if pooled > 0.0 {
+        (mo - mi) / pooled
+    } else {
+        0.0
+    }
+}
+
+/// 1-based ranks of `v` in ascending order, tied values sharing their average rank.
+fn ranks(v: &[f64]) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..v.len()).collect();
+    order.sort_by(|&a, &b| v[a].total_cmp(&v[b]));
+    let mut ranks = vec![0.0; v.len()];
+    let mut i = 0;
+    while i < order.len() {
+        let mut j = i;
+        while j < order.len() && v[order[j]] == v[order[i]] {
+            j += 1;
+        }
+        for &k in &order[i..j] {
+            ranks[k] = (i + j + 1) as f64 / 2.0;
+        }
+        i = j;
+    }
+    ranks
+}
+
+/// Nearest-rank quantile of an ascending, non-empty slice.
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    sorted[((sorted.len() - 1) as f64 * q).round() as usize]
+}
+
+/// Per-call latency summary from [`HalfSpaceTrees::benchmark_score`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencyStats {
+    pub min: Duration,
+    pub median: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+/// Snapshot of the `metrics` feature's operation counters.
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimingStats {
+    pub insert_nanos: u64,
+    pub inserts: u64,
+    pub score_nanos: u64,
+    pub scores: u64,
+}
+
+// Atomics so `score(&self)` can record without giving up `Sync`.
+#[cfg(feature = "metrics")]
+#[derive(Debug, Default)]
+struct Timing {
+    insert_nanos: std::sync::atomic::AtomicU64,
+    inserts: std::sync::atomic::AtomicU64,
+    score_nanos: std::sync::atomic::AtomicU64,
+    scores: std::sync::atomic::AtomicU64,
+}
+
+#[cfg(feature = "metrics")]
+impl Timing {
+    fn record(
+        nanos: &std::sync::atomic::AtomicU64,
+        count: &std::sync::atomic::AtomicU64,
+        start: Instant,
+    ) {
+        use std::sync::atomic::Ordering::Relaxed;
+        let elapsed = u64::try_from(start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+        nanos.fetch_add(elapsed, Relaxed);
+        count.fetch_add(1, Relaxed);
+    }
+}
+
+/// Decayed mean/variance pair, updated one observation at a time.
+#[derive(Debug, Clone, Copy, Default)]
+struct Ewm {
+    mean: f64,
+    var: f64,
+}
+
+impl Ewm {
+    fn update(&mut self, x: f64, w: f64) {
+        let diff = x - self.mean;
+        self.mean += w * diff;
+        self.var = (1.0 - w) * (self.var + w * diff * diff);
+    }
+}
+
+/// Fast vs slow running moments of one input dimension.
+#[derive(Debug, Clone, Copy, Default)]
+struct DriftStats {
+    recent: Ewm,
+    baseline: Ewm,
+    seen: bool,
+}
+
+impl DriftStats {
+    const RECENT_WEIGHT: f64 = 0.05;
+    const BASELINE_WEIGHT: f64 = 0.001;
+
+    fn observe(&mut self, x: f64) {
+        if !self.seen {
+            let init = Ewm { mean: x, var: 0.0 };
+            self.recent = init;
+            self.baseline = init;
+            self.seen = true;
+            return;
+        }
+        self.recent.update(x, Self::RECENT_WEIGHT);
+        self.baseline.update(x, Self::BASELINE_WEIGHT);
+    }
+
+    fn standardized_shift(&self) -> f64 {
+        let sd = self.baseline.var.sqrt().max(1e-12);
+        (self.recent.mean - self.baseline.mean) / sd
+    }
+}
+
+/// Per-dimension range of `data` padded by 10% on each side (0.5 for a constant
+/// dimension), as `HalfSpaceTrees::auto` uses for its bounds.
+fn padded_bounds(data: &[Vec<f64>]) -> Vec<(f64, f64)> {
+    (0..data[0].len())
+        .map(|d| {
+            let (lo, hi) = data
+                .iter()
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), x| {
+                    (lo.min(x[d]), hi.max(x[d]))
+                });
+            let pad = if hi > lo { 0.1 * (hi - lo) } else { 0.5 };
+            (lo - pad, hi + pad)
+        })
+        .collect()
+}
+
+/// Number of dimensions whose bounds leave room to split.
+fn varying(bounds: &[(f64, f64)]) -> usize {
+    bounds.iter().filter(|(lo, hi)| hi > lo).count()
+}
+
+/// A uniformly random dimension among those with `lo < hi`, or 0 if there is none.
+/// Draws exactly as `random_range(0..n_dims)` when every dimension varies.
+fn random_varying_dim<R: Rng + ?Sized>(bounds: &[(f64, f64)], rng: &mut R) -> usize {
+    let live = varying(bounds);
+    if live == bounds.len() {
+        return rng.random_range(0..bounds.len());
+    }
+    if live == 0 {
+        return 0;
+    }
+    let k = rng.random_range(0..live);
+    (0..bounds.len())
+        .filter(|&d| bounds[d].1 > bounds[d].0)
+        .nth(k)
+        .unwrap()
+}
+
+/// Build the implicit dense vector for a sparse `(index, value)` stream.
+fn densify<I>(entries: I, n_dims: usize) -> Vec<f64>
+where
+    I: IntoIterator<Item = (usize, f64)>,
+{
+    let mut x = vec![0.0; n_dims];
+    for (i, v) in entries {
+        assert!(
+            i < n_dims,
+            "sparse index {i} out of range for {n_dims} dims"
+        );
+        x[i] = v;
+    }
+    x
+}
+
+/// A single random tree. `M` is an optional per-leaf payload (see
+/// [`HalfSpaceTree::set_leaf_meta`]); plain trees use `()` and never store any.
+#[derive(Debug, Clone)]
+pub struct HalfSpaceTree<M = ()> {
+    root: Node,
+    max_depth: u32,
+    n_dims: usize,
+    // Construction bounds, used to reconstruct node regions
+    bounds: Vec<(f64, f64)>,
+    weighting: DepthWeighting,
+    method: ScoreMethod,
+    // Splits per dimension allowed along a path (`u32::MAX` if uncapped), for `rebuilt`
+    max_repeats: u32,
+    // Per-dimension scale the splits were drawn in
+    transforms: Vec<Transform>,
+    // Leaf payloads keyed by `Node::leaf_index`
+    meta: HashMap<usize, M>,
+    // Lazily computed `leaf_volumes`; reset whenever splits move
+    volumes: OnceLock<Vec<f64>>,
+}
+
+/// Whether streaming helpers train on the points they score.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProcessMode {
+    /// Score each point against the model as it stands; never insert.
+    ScoreOnly,
+    /// Score each point, then insert it.
+    #[default]
+    ScoreThenInsert,
+}
+
+/// Monotone per-dimension rescaling that split thresholds are drawn in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Transform {
+    /// Raw values.
+    #[default]
+    Identity,
+    /// `ln(1 + x)`, for non-negative heavy-tailed features such as sizes or counts.
+    Log1p,
+    /// `sign(x) * ln(1 + |x|)`, a log scale for heavy tails on both sides of zero.
+    Symlog,
+}
+
+impl Transform {
+    /// Map a raw value into the transformed space.
+    pub fn apply(self, x: f64) -> f64 {
+        match self {
+            Transform::Identity => x,
+            Transform::Log1p => x.ln_1p(),
+            Transform::Symlog => x.signum() * x.abs().ln_1p(),
+        }
+    }
+
+    fn invert(self, y: f64) -> f64 {
+        match self {
+            Transform::Identity => y,
+            Transform::Log1p => y.exp_m1(),
+            Transform::Symlog => y.signum() * y.abs().exp_m1(),
+        }
+    }
+}
+
+/// Weight [`HalfSpaceTrees::insert`] gives the `n`-th point it inserts (counting from 0).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum WeightSchedule {
+    /// Every point weighs 1.
+    #[default]
+    Constant,
+    /// Weight `scale / (scale + n)`: early points build the structure and later ones only
+    /// refine it, halving their influence after `scale` inserts.
+    Decreasing { scale: f64 },
+    /// Weight `(scale + n) / scale`: later points outweigh early ones, doubling after
+    /// `scale` inserts.
+    Increasing { scale: f64 },
+}
+
+impl WeightSchedule {
+    pub fn weight(self, n: u64) -> f64 {
+        match self {
+            Self::Constant => 1.0,
+            Self::Decreasing { scale } => scale / (scale + n as f64),
+            Self::Increasing { scale } => (scale + n as f64) / scale,
+        }
+    }
+}
+
+/// Geometry of internal-node splits, fixed at construction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SplitMode {
+    /// Threshold one random feature.
+    #[default]
+    AxisAligned,
+    /// Threshold the projection of two random features onto a random unit direction,
+    /// which can isolate anomalies that break a correlation between features.
+    /// Needs at least two dimensions; otherwise falls back to axis-aligned splits.
+    Oblique,
+}
+
+/// How a leaf's contents enter a tree's score.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScoreMethod {
+    /// Rarity from leaf mass alone.
+    #[default]
+    Standard,
+    /// Rarity inflated by the spread of the leaf's points along its split dimension:
+    /// the score is multiplied by `1 + 2 * sd / width`, where `width` is the leaf's extent
+    /// on that dimension. A leaf whose mass straddles its region (high spread) is a
+    /// poorer fit than one whose points are tightly clustered.
+    VarianceAware,
+    /// Pool evidence before the nonlinearity: [`HalfSpaceTrees::score`] takes the reached
+    /// leaf masses of all trees, drops the lightest and heaviest
+    /// [`HalfSpaceTrees::POOLED_MASS_TRIM`] of them, and applies rarity `1 / (1 + mass)`
+    /// once to the mean of the rest. One degenerate tree then shifts the mean mass a
+    /// little rather than adding its own extreme score. Tree weights and depth weighting
+    /// do not enter; a tree scored on its own, and exported code, use `Standard`.
+    PooledMass,
+}
+
+/// How depth enters a tree's score.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DepthWeighting {
+    /// Rarity of the reached leaf only, scaled up linearly for shallow leaves.
+    #[default]
+    Linear,
+    /// Weighted mean of the rarity of every node on the path, where a node `k` levels
+    /// above the bottom of the tree weighs `1/(k+1)`. The leaf counts fully and the root
+    /// least, so sparse shallow regions alone cannot dominate the score.
+    Harmonic,
+}
+
+impl HalfSpaceTree {
+    pub fn new<R: Rng + ?Sized>(max_depth: u32, bounds: &[(f64, f64)], rng: &mut R) -> Self {
+        Self::with_split_mode(max_depth, bounds, SplitMode::AxisAligned, rng)
+    }
+
+    pub fn with_split_mode<R: Rng + ?Sized>(
+        max_depth: u32,
+        bounds: &[(f64, f64)],
+        mode: SplitMode,
+        rng: &mut R,
+    ) -> Self {
+        Self::randomized(max_depth, bounds, mode, u32::MAX, rng)
+    }
+
+    /// Axis-aligned tree in which no dimension splits more than `max_repeats_per_dim`
+    /// times along any root-to-leaf path; a node whose drawn dimension is used up redraws
+    /// it. Keeps paths from degenerating into one feature in low dimensions. Needs
+    /// `max_repeats_per_dim * varying_dims >= max_depth` so every path can be completed.
+    pub fn with_max_repeats<R: Rng + ?Sized>(
+        max_depth: u32,
+        bounds: &[(f64, f64)],
+        max_repeats_per_dim: u32,
+        rng: &mut R,
+    ) -> Self {
+        assert!(
+            max_repeats_per_dim as u64 * varying(bounds) as u64 >= max_depth as u64,
+            "max_repeats_per_dim too small to fill a path of max_depth splits"
+        );
+        Self::randomized(
+            max_depth,
+            bounds,
+            SplitMode::AxisAligned,
+            max_repeats_per_dim,
+            rng,
+        )
+    }
+
+    fn randomized<R: Rng + ?Sized>(
+        max_depth: u32,
+        bounds: &[(f64, f64)],
+        mode: SplitMode,
+        max_repeats: u32,
+        rng: &mut R,
+    ) -> Self {
+        assert!(!bounds.is_empty(), "bounds must not be empty");
+        let n_dims = bounds.len();
+        let mut repeats = vec![0; n_dims];
+        let root = Node::randomized(0, max_depth, bounds, mode, &mut repeats, max_repeats, rng);
+        Self {
+            root,
+            max_depth,
+            n_dims,
+            bounds: bounds.to_vec(),
+            weighting: DepthWeighting::default(),
+            method: ScoreMethod::default(),
+            max_repeats,
+            transforms: vec![Transform::Identity; n_dims],
+            meta: HashMap::new(),
+            volumes: OnceLock::new(),
+        }
+    }
+
+    /// Axis-aligned tree whose splits are drawn uniformly in the transformed space of each
+    /// dimension, then mapped back to raw thresholds. As every transform is increasing,
+    /// routing a raw point is identical to routing its transformed image, so inserts and
+    /// scores take raw values while e.g. a `Log1p` dimension is cut evenly per decade.
+    pub fn with_transforms<R: Rng + ?Sized>(
+        max_depth: u32,
+        bounds: &[(f64, f64)],
+        transforms: &[Transform],
+        rng: &mut R,
+    ) -> Self {
+        assert_eq!(
+            transforms.len(),
+            bounds.len(),
+            "one transform per dimension"
+        );
+        let scaled: Vec<(f64, f64)> = bounds
+            .iter()
+            .zip(transforms)
+            .map(|(&(lo, hi), t)| {
+                if *t == Transform::Log1p {
+                    assert!(lo >= 0.0, "Log1p dimensions need non-negative bounds");
+                }
+                (t.apply(lo), t.apply(hi))
+            })
+            .collect();
+        let mut tree = Self::with_split_mode(max_depth, &scaled, SplitMode::AxisAligned, rng);
+        tree.root
+            .map_thresholds(&|d, v| transforms[d].invert(v).clamp(bounds[d].0, bounds[d].1));
+        tree.bounds = bounds.to_vec();
+        tree.transforms = transforms.to_vec();
+        tree.volumes = OnceLock::new();
+        tree
+    }
+
+    /// Tree in the style of river's `HalfSpaceTrees`: each split picks a uniform random
+    /// feature and a threshold uniform in the middle `1 - 2 * padding` of the node's own
+    /// region on that feature, so regions shrink with depth.
+    fn padded<R: Rng + ?Sized>(
+        max_depth: u32,
+        bounds: &[(f64, f64)],
+        padding: f64,
+        rng: &mut R,
+    ) -> Self {
+        assert!(!bounds.is_empty(), "bounds must not be empty");
+        let mut region = bounds.to_vec();
+        Self {
+            root: Node::padded(0, max_depth, &mut region, padding, rng),
+            max_depth,
+            n_dims: bounds.len(),
+            bounds: bounds.to_vec(),
+            weighting: DepthWeighting::default(),
+            method: ScoreMethod::default(),
+            max_repeats: u32::MAX,
+            transforms: vec![Transform::Identity; bounds.len()],
+            meta: HashMap::new(),
+            volumes: OnceLock::new(),
+        }
+    }
+
+    /// This tree, able to carry a payload of type `M` on each leaf.
+    pub fn with_leaf_meta<M>(self) -> HalfSpaceTree<M> {
+        HalfSpaceTree {
+            root: self.root,
+            max_depth: self.max_depth,
+            n_dims: self.n_dims,
+            bounds: self.bounds,
+            weighting: self.weighting,
+            method: self.method,
+            max_repeats: self.max_repeats,
+            transforms: self.transforms,
+            meta: HashMap::new(),
+            volumes: self.volumes,
+        }
+    }
+
+    /// A tree with new random splits of the same shape, scoring settings and cap on
+    /// splits per dimension, seeded with this tree's leaf masses placed at their regions'
+    /// centers.
+    fn rebuilt<R: Rng + ?Sized>(&self, rng: &mut R) -> HalfSpaceTree {
+        let mode = if self.root.oblique.is_some() {
+            SplitMode::Oblique
+        } else {
+            SplitMode::AxisAligned
+        };
+        let mut tree = if self.transforms.iter().all(|&t| t == Transform::Identity) {
+            HalfSpaceTree::randomized(self.max_depth, &self.bounds, mode, self.max_repeats, rng)
+        } else {
+            HalfSpaceTree::with_transforms(self.max_depth, &self.bounds, &self.transforms, rng)
+        };
+        tree.weighting = self.weighting;
+        tree.method = self.method;
+        self.for_each_leaf_region(|leaf, region| {
+            if leaf.mass > 0.0 {
+                let center: Vec<f64> = region.iter().map(|(lo, hi)| 0.5 * (lo + hi)).collect();
+                tree.insert_weighted(&center, leaf.mass);
+            }
+        });
+        tree
+    }
+}
+
+impl<M> HalfSpaceTree<M> {
+    /// Attach `m` to the leaf `x` routes to, replacing any earlier payload there.
+    pub fn set_leaf_meta(&mut self, x: &FeatureVector, m: M) {
+        assert_eq!(x.len(), self.n_dims);
+        self.meta.insert(self.root.leaf_index(x), m);
+    }
+
+    /// The payload attached to the leaf `x` routes to, if any.
+    pub fn leaf_meta(&self, x: &FeatureVector) -> Option<&M> {
+        assert_eq!(x.len(), self.n_dims);
+        self.meta.get(&self.root.leaf_index(x))
+    }
+
+    pub fn insert(&mut self, x: &FeatureVector) {
+        self.insert_weighted(x, 1.0);
+    }
+
+    pub fn insert_weighted(&mut self, x: &FeatureVector, weight: f64) {
+        assert_eq!(x.len(), self.n_dims);
+        self.root.insert(x, weight);
+    }
+
+    /// The branch decisions `x` takes from the root down to its leaf, `true` for left:
+    /// `max_depth` entries, or fewer below leaves collapsed by
+    /// [`HalfSpaceTree::merge_similar_leaves`]. Cache it to replay inserts with
+    /// [`HalfSpaceTree::insert_path`].
+    pub fn path(&self, x: &FeatureVector) -> Vec<bool> {
+        assert_eq!(x.len(), self.n_dims);
+        let mut node = &self.root;
+        let mut path = Vec::with_capacity(self.max_depth as usize);
+        while let (Some(l), Some(r)) = (&node.left, &node.right) {
+            let left = node.goes_left(x);
+            path.push(left);
+            node = if left { l } else { r };
+        }
+        path
+    }
+
+    /// Add `weight` to the mass of every node along a cached `path` (see
+    /// [`HalfSpaceTree::path`]) without comparing any coordinates. Masses end up as
+    /// `insert_weighted` would leave them; per-node coordinate moments are not updated,
+    /// since the point itself is not known.
+    ///
+    /// # Panics
+    /// If `path` does not end exactly at a leaf; nothing is updated then.
+    pub fn insert_path(&mut self, path: &[bool], weight: f64) {
+        let mut node = &self.root;
+        for &left in path {
+            let next = if left { &node.left } else { &node.right };
+            node = next.as_deref().expect("path runs past a leaf");
+        }
+        assert!(node.left.is_none(), "path ends above a leaf");
+        let mut node = &mut self.root;
+        node.mass += weight;
+        node.visits += weight;
+        for &left in path {
+            let next = if left {
+                &mut node.left
+            } else {
+                &mut node.right
+            };
+            node = next.as_mut().expect("path was checked");
+            node.mass += weight;
+            node.visits += weight;
+        }
+    }
+
+    pub fn decay(&mut self, alpha: f64) {
+        self.root.decay(alpha);
+    }
+
+    /// See [`HalfSpaceTrees::sanitize_masses`].
+    pub fn sanitize_masses(&mut self) -> usize {
+        self.root.sanitize_masses()
+    }
+
+    pub fn decay_with_prior(&mut self, alpha: f64, prior: f64) {
+        self.root.decay_with_prior(alpha, prior);
+    }
+
+    /// See [`HalfSpaceTrees::process_fused`].
+    pub fn process_fused(&mut self, x: &FeatureVector, alpha: f64) -> f64 {
+        self.process_fused_weighted(x, alpha, 1.0)
+    }
+
+    fn process_fused_weighted(&mut self, x: &FeatureVector, alpha: f64, weight: f64) -> f64 {
+        assert_eq!(x.len(), self.n_dims);
+        // Spread is read before the walk mutates the path (a no-op for `Standard`).
+        let spread = self.spread_factor(x);
+        let acc = (0.0, 0.0);
+        self.root
+            .process_fused(x, alpha, weight, self.max_depth, self.weighting, acc)
+            * spread
+    }
+
+    pub fn set_depth_weighting(&mut self, weighting: DepthWeighting) {
+        self.weighting = weighting;
+    }
+
+    /// Score as if `alpha` decay had been applied `ticks` more times, without mutating.
+    pub fn score_at_alpha(&self, x: &FeatureVector, alpha: f64, ticks: u32) -> f64 {
+        assert_eq!(x.len(), self.n_dims);
+        let scale = alpha.powf(ticks as f64);
+        self.root
+            .score_scaled(x, self.max_depth, self.weighting, scale, 1.0)
+            * self.spread_factor(x)
+    }
+
+    /// A copy where every leaf is replaced by a full subtree of `extra_depth` levels of
+    /// random axis-aligned splits within its region. New nodes start without mass and
+    /// leaf payloads are dropped, as leaf indices change.
+    pub fn deepened<R: Rng + ?Sized>(&self, extra_depth: u32, rng: &mut R) -> Self {
+        let mut tree = Self {
+            root: self.root.clone(),
+            max_depth: self.max_depth + extra_depth,
+            n_dims: self.n_dims,
+            bounds: self.bounds.clone(),
+            weighting: self.weighting,
+            method: self.method,
+            // The added splits ignore any cap
+            max_repeats: u32::MAX,
+            transforms: self.transforms.clone(),
+            meta: HashMap::new(),
+            volumes: OnceLock::new(),
+        };
+        let mut region = self.bounds.clone();
+        tree.root.deepen(extra_depth, &mut region, rng);
+        tree
+    }
+
+    /// Collapse sibling leaf pairs whose masses differ by at most `rel_tol` of the larger
+    /// into their parent, which becomes a leaf holding their summed mass. Such pairs add
+    /// no discrimination, so this shrinks the tree where it learned nothing. Runs
+    /// bottom-up, so a parent merged this way can merge again with its own sibling.
+    /// Leaf payloads are dropped, as leaf indices change. Scores in merged regions move,
+    /// since the new leaf is shallower and holds both halves' mass. A merged tree no
+    /// longer fits [`HalfSpaceTrees::to_flat_f32`]'s layout.
+    pub fn merge_similar_leaves(&mut self, rel_tol: f64) {
+        assert!(rel_tol >= 0.0, "rel_tol must be non-negative");
+        self.root.merge_similar_leaves(rel_tol);
+        self.meta.clear();
+        self.volumes = OnceLock::new();
+    }
+
+    /// Like [`HalfSpaceTree::sample_normal`], but sharper: each coordinate is drawn from
+    /// a normal fit to the moments of the deepest node on the path splitting on that
+    /// dimension, truncated to the leaf region (by rejection, falling back to uniform).
+    /// Dimensions no node on the path splits on stay uniform.
+    fn sample_with_moments<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<Vec<f64>> {
+        if self.root.mass <= 0.0 {
+            return None;
+        }
+        let mut region = self.bounds.clone();
+        let mut moments = vec![None; self.n_dims];
+        let mut node = &self.root;
+        loop {
+            if node.oblique.is_none() && node.mass > 0.0 {
+                moments[node.split_dim] = Some((node.sum / node.mass, node.variance().sqrt()));
+            }
+            let (Some(l), Some(r)) = (&node.left, &node.right) else {
+                break;
+            };
+            let total = l.mass.max(0.0) + r.mass.max(0.0);
+            let left = if total > 0.0 {
+                rng.random::<f64>() * total < l.mass.max(0.0)
+            } else {
+                rng.random_bool(0.5)
+            };
+            if node.oblique.is_none() {
+                let (lo, hi) = &mut region[node.split_dim];
+                if left {
+                    *hi = hi.min(node.split_val);
+                } else {
+                    *lo = lo.max(node.split_val);
+                }
+            }
+            node = if left { l } else { r };
+        }
+        let mut coord = |(lo, hi): (f64, f64), fit: Option<(f64, f64)>| -> f64 {
+            if hi <= lo {
+                return lo;
+            }
+            if let Some((mean, sd)) = fit {
+                for _ in 0..16 {
+                    let v = mean + sd * standard_normal(rng);
+                    if (lo..hi).contains(&v) {
+                        return v;
+                    }
+                }
+            }
+            rng.random_range(lo..hi)
+        };
+        Some(
+            region
+                .into_iter()
+                .zip(moments)
+                .map(|(r, m)| coord(r, m))
+                .collect(),
+        )
+    }
+
+    /// See [`HalfSpaceTrees::score_dscore_dalpha`].
+    pub fn score_dscore_dalpha(&self, x: &FeatureVector, alpha: f64, ticks: u32) -> (f64, f64) {
+        assert_eq!(x.len(), self.n_dims);
+        let scale = alpha.powf(ticks as f64);
+        let dscale = if ticks == 0 {
+            0.0
+        } else {
+            ticks as f64 * alpha.powf(ticks as f64 - 1.0)
+        };
+        let (score, grad) = self
+            .root
+            .score_scaled_grad(x, self.max_depth, self.weighting, scale);
+        let spread = self.spread_factor(x);
+        (score * spread, grad * dscale * spread)
+    }
+
+    /// Score with rarity `1 / (smoothing + mass)` in place of `1 / (1 + mass)`.
+    pub fn score_with_smoothing(&self, x: &FeatureVector, smoothing: f64) -> f64 {
+        assert_eq!(x.len(), self.n_dims);
+        assert!(smoothing > 0.0, "smoothing must be positive");
+        self.root
+            .score_scaled(x, self.max_depth, self.weighting, 1.0, smoothing)
+            * self.spread_factor(x)
+    }
+
+    pub fn worst_case_score(&self, x: &FeatureVector, eps: f64) -> f64 {
+        assert_eq!(x.len(), self.n_dims);
+        self.root.worst_case_score(x, eps, self.max_depth)
+    }
+
+    /// Leaf score of `x` marginalizing over the dimensions marked in `dropped`; see
+    /// [`HalfSpaceTrees::score_with_dropout`].
+    pub fn score_with_dropped(&self, x: &FeatureVector, dropped: &[bool]) -> f64 {
+        assert_eq!(x.len(), self.n_dims);
+        assert_eq!(dropped.len(), self.n_dims, "one flag per dimension");
+        self.root.score_with_dropped(x, dropped, self.max_depth)
+    }
+
+    /// See [`HalfSpaceTrees::score_decomposition`].
+    pub fn score_decomposition(&self, x: &FeatureVector) -> (f64, f64) {
+        let leaf = self.leaf(x);
+        let depth_factor = leaf.depth_factor(self.max_depth);
+        let undecayed = depth_factor / (1.0 + leaf.visits.max(leaf.mass).max(0.0));
+        (undecayed, leaf.leaf_score(self.max_depth) - undecayed)
+    }
+
+    /// The leaf `x` routes to.
+    fn leaf(&self, x: &FeatureVector) -> &Node {
+        assert_eq!(x.len(), self.n_dims);
+        self.root.leaf(x)
+    }
+
+    /// Axis-aligned region of the leaf `x` reaches, within the construction bounds.
+    /// Oblique splits on the path do not narrow it.
+    pub fn leaf_region(&self, x: &FeatureVector) -> Vec<(f64, f64)> {
+        assert_eq!(x.len(), self.n_dims);
+        let mut region = self.bounds.clone();
+        let mut node = &self.root;
+        while let (Some(l), Some(r)) = (&node.left, &node.right) {
+            let left = node.goes_left(x);
+            if node.oblique.is_none() {
+                let (lo, hi) = &mut region[node.split_dim];
+                if left {
+                    *hi = hi.min(node.split_val);
+                } else {
+                    *lo = lo.max(node.split_val);
+                }
+            }
+            node = if left { l } else { r };
+        }
+        region
+    }
+
+    /// Draw a point from the tree's learned density: descend choosing each child with
+    /// probability proportional to its mass, then sample uniformly in the reached leaf's
+    /// region. Oblique splits do not narrow the region, so below them the sample may fall
+    /// outside the leaf. `None` for a tree without mass.
+    pub fn sample_normal<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<Vec<f64>> {
+        if self.root.mass <= 0.0 {
+            return None;
+        }
+        let mut region = self.bounds.clone();
+        let mut node = &self.root;
+        while let (Some(l), Some(r)) = (&node.left, &node.right) {
+            let total = l.mass.max(0.0) + r.mass.max(0.0);
+            let left = if total > 0.0 {
+                rng.random::<f64>() * total < l.mass.max(0.0)
+            } else {
+                rng.random_bool(0.5)
+            };
+            if node.oblique.is_none() {
+                let (lo, hi) = &mut region[node.split_dim];
+                if left {
+                    *hi = hi.min(node.split_val);
+                } else {
+                    *lo = lo.max(node.split_val);
+                }
+            }
+            node = if left { l } else { r };
+        }
+        Some(
+            region
+                .iter()
+                .map(|&(lo, hi)| {
+                    if hi > lo {
+                        rng.random_range(lo..hi)
+                    } else {
+                        lo
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    /// Closest-to-boundary split decision on `x`'s path as `(dim, signed margin)`.
+    /// Oblique splits report their first dimension and the margin along `w·x`.
+    pub fn tightest_margin(&self, x: &FeatureVector) -> Option<(usize, f64)> {
+        assert_eq!(x.len(), self.n_dims);
+        let path = self.root.path(x);
+        path[..path.len() - 1]
+            .iter()
+            .map(|n| (n.split_dim, n.coord(x) - n.split_val))
+            .min_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+    }
+
+    /// Mean of `dim_weights` over the splits on `x`'s path, each split weighted by how much
+    /// it raised the rarity of `x` (child rarity minus node rarity, if positive).
+    /// 1 if no split on the path made `x` rarer.
+    fn path_dim_weight(&self, x: &FeatureVector, dim_weights: &[f64]) -> f64 {
+        let path = self.root.path(x);
+        let (mut num, mut den) = (0.0, 0.0);
+        for w in path.windows(2) {
+            let gain = (w[1].rarity() - w[0].rarity()).max(0.0);
+            let importance = match &w[0].oblique {
+                Some(o) => 0.5 * (dim_weights[o.dims[0]] + dim_weights[o.dims[1]]),
+                None => dim_weights[w[0].split_dim],
+            };
+            num += gain * importance;
+            den += gain;
+        }
+        if den > 0.0 { num / den } else { 1.0 }
+    }
+
+    /// Single-feature edits that push `x` across one axis-aligned split on its path,
+    /// smallest first.
+    /// Each is `(dim, new_value)`; the new value sits just on the far side of the split.
+    fn split_flips(&self, x: &FeatureVector) -> Vec<(usize, f64)> {
+        let path = self.root.path(x);
+        let mut flips: Vec<(usize, f64)> = path[..path.len() - 1]
+            .iter()
+            .filter(|n| n.oblique.is_none())
+            .map(|n| {
+                let v = if x[n.split_dim] < n.split_val {
+                    n.split_val
+                } else {
+                    n.split_val.next_down()
+                };
+                (n.split_dim, v)
+            })
+            .collect();
+        flips.sort_by(|a, b| (a.1 - x[a.0]).abs().total_cmp(&(b.1 - x[b.0]).abs()));
+        flips
+    }
+
+    /// True if both trees have the same depth, dimensionality and split geometry.
+    pub fn is_compatible_with<N>(&self, other: &HalfSpaceTree<N>) -> bool {
+        self.max_depth == other.max_depth
+            && self.n_dims == other.n_dims
+            && self.root.same_geometry(&other.root)
+    }
+
+    /// True if every feature of `x` lies within this tree's construction bounds (inclusive).
+    pub fn in_bounds(&self, x: &FeatureVector) -> bool {
+        assert_eq!(x.len(), self.n_dims);
+        x.iter()
+            .zip(&self.bounds)
+            .all(|(v, (lo, hi))| lo <= v && v <= hi)
+    }
 
-impl HalfSpaceTrees {
-    /// Create a forest of `n_trees` trees of depth `max_depth`.
-    /// `bounds` is a per‑dimension (min,max) range used to generate random splits.
-    pub fn new<R: Rng + ?Sized>(
-        n_trees: usize,
-        max_depth: u32,
-        bounds: &[(f64, f64)],
-        rng: &mut R,
-    ) -> Self {
-        let trees = (0..n_trees)
-            .map(|_| HalfSpaceTree::new(max_depth, bounds, rng))
-            .collect();
-        Self { trees }
+    /// True if any internal node splits on `dim`.
+    pub fn uses_dim(&self, dim: usize) -> bool {
+        self.root.uses_dim(dim)
     }
 
-    /// Insert a point with unit weight (after any global decay you apply externally).
-    pub fn insert(&mut self, x: &FeatureVector) {
-        for t in &mut self.trees {
-            t.insert(x);
-        }
+    /// Per-dimension bounding box of the regions of all leaves with positive mass,
+    /// or `None` if the tree holds no mass.
+    pub fn learned_extent(&self) -> Option<Vec<(f64, f64)>> {
+        let mut extent: Option<Vec<(f64, f64)>> = None;
+        self.for_each_leaf_region(|leaf, region| {
+            if leaf.mass <= 0.0 {
+                return;
+            }
+            match &mut extent {
+                Some(e) => {
+                    for (acc, r) in e.iter_mut().zip(region) {
+                        acc.0 = acc.0.min(r.0);
+                        acc.1 = acc.1.max(r.1);
+                    }
+                }
+                None => extent = Some(region.to_vec()),
+            }
+        });
+        extent
     }
 
-    /// Multiply all node masses by `alpha` (0,1]. Call periodically to handle drift.
-    pub fn decay(&mut self, alpha: f64) {
-        for t in &mut self.trees {
-            t.decay(alpha);
-        }
+    /// Visit every leaf with its reconstructed axis-aligned region.
+    fn for_each_leaf_region<F>(&self, mut f: F)
+    where
+        F: FnMut(&Node, &[(f64, f64)]),
+    {
+        let mut region = self.bounds.clone();
+        self.root.for_each_leaf_region(&mut region, &mut f);
+    }
+
+    /// Volume of each leaf's reconstructed region, in left-to-right leaf order.
+    /// Unreachable leaves have volume 0, so for axis-aligned trees the volumes sum to
+    /// the volume of the bounding box. Computed once and cached; methods that change the
+    /// tree's structure, such as `merge_similar_leaves`, invalidate the cache.
+    pub fn leaf_volumes(&self) -> &[f64] {
+        self.volumes.get_or_init(|| {
+            let mut volumes = Vec::new();
+            self.for_each_leaf_region(|_, region| {
+                volumes.push(region.iter().map(|(lo, hi)| (hi - lo).max(0.0)).product());
+            });
+            volumes
+        })
+    }
+
+    /// Mass each leaf would hold, in left-to-right order, if `total_mass` were spread
+    /// uniformly over the bounding box: `total_mass * leaf_volume / bounding_volume`.
+    /// Sums to `total_mass` for axis-aligned trees; all zero if the box has no volume.
+    pub fn expected_uniform_mass(&self, total_mass: f64) -> Vec<f64> {
+        let bounding: f64 = self
+            .bounds
+            .iter()
+            .map(|(lo, hi)| (hi - lo).max(0.0))
+            .product();
+        self.leaf_volumes()
+            .iter()
+            .map(|v| {
+                if bounding > 0.0 {
+                    total_mass * v / bounding
+                } else {
+                    0.0
+                }
+            })
+            .collect()
+    }
+
+    /// Total (decayed) mass held by the tree, i.e. the root's mass.
+    pub fn total_mass(&self) -> f64 {
+        self.root.mass
+    }
+
+    /// Leaf masses in left-to-right order.
+    pub fn leaf_masses(&self) -> Vec<f64> {
+        let mut leaves = Vec::new();
+        self.root.leaves(&mut leaves);
+        leaves.iter().map(|n| n.mass).collect()
     }
 
-    /// Average score across trees
     pub fn score(&self, x: &FeatureVector) -> f64 {
-        let mut s = 0.0;
-        for t in &self.trees {
-            s += t.score(x);
+        assert_eq!(x.len(), self.n_dims);
+        self.root.score(x, self.max_depth, self.weighting) * self.spread_factor(x)
+    }
+
+    pub fn set_score_method(&mut self, method: ScoreMethod) {
+        self.method = method;
+    }
+
+    /// Mass of the leaf `x` reaches.
+    pub fn leaf_mass(&self, x: &FeatureVector) -> f64 {
+        self.leaf(x).mass
+    }
+
+    /// Variance of the split-dimension coordinate among points in the leaf `x` reaches.
+    pub fn leaf_variance(&self, x: &FeatureVector) -> f64 {
+        self.leaf(x).variance()
+    }
+
+    /// Score multiplier from the configured [`ScoreMethod`].
+    fn spread_factor(&self, x: &FeatureVector) -> f64 {
+        match self.method {
+            ScoreMethod::Standard | ScoreMethod::PooledMass => 1.0,
+            ScoreMethod::VarianceAware => {
+                let path = self.root.path(x);
+                let leaf = path[path.len() - 1];
+                let dim = leaf.split_dim;
+                let (mut lo, mut hi) = self.bounds[dim];
+                for w in path.windows(2) {
+                    if w[0].split_dim == dim && w[0].oblique.is_none() {
+                        if std::ptr::eq(w[1], w[0].left.as_deref().unwrap()) {
+                            hi = hi.min(w[0].split_val);
+                        } else {
+                            lo = lo.max(w[0].split_val);
+                        }
+                    }
+                }
+                leaf.spread_factor(hi - lo)
+            }
         }
-        s / (self.trees.len() as f64)
     }
-}
 
-#[derive(Debug)]
-pub struct HalfSpaceTree {
-    root: Node,
-    max_depth: u32,
-    n_dims: usize,
-}
+    pub fn insert_sparse<I>(&mut self, entries: I)
+    where
+        I: IntoIterator<Item = (usize, f64)>,
+    {
+        let x = densify(entries, self.n_dims);
+        self.insert(&x);
+    }
 
-impl HalfSpaceTree {
-    pub fn new<R: Rng + ?Sized>(max_depth: u32, bounds: &[(f64, f64)], rng: &mut R) -> Self {
-        assert!(!bounds.is_empty(), "bounds must not be empty");
-        let n_dims = bounds.len();
-        let root = Node::randomized(0, max_depth, bounds, rng);
-        Self {
-            root,
-            max_depth,
-            n_dims,
+    pub fn score_sparse<I>(&self, entries: I) -> f64
+    where
+        I: IntoIterator<Item = (usize, f64)>,
+    {
+        let x = densify(entries, self.n_dims);
+        self.score(&x)
+    }
+
+    /// Shannon entropy (nats) of the normalized leaf masses; 0 for a tree with no mass.
+    pub fn occupancy_entropy(&self) -> f64 {
+        let mut leaves = Vec::new();
+        self.root.leaves(&mut leaves);
+        let total: f64 = leaves.iter().map(|n| n.mass.max(0.0)).sum();
+        if total <= 0.0 {
+            return 0.0;
         }
+        leaves
+            .iter()
+            .map(|n| n.mass.max(0.0) / total)
+            .filter(|&p| p > 0.0)
+            .map(|p| -p * p.ln())
+            .sum()
     }
 
-    pub fn insert(&mut self, x: &FeatureVector) {
-        assert_eq!(x.len(), self.n_dims);
-        self.root.insert(x);
+    /// Mean over internal nodes holding mass of `2 * min(left, right) / (left + right)`:
+    /// 1 when every split divides its mass evenly, 0 when all mass goes one way.
+    /// Returns 0 for a tree with no mass.
+    pub fn split_balance(&self) -> f64 {
+        let (mut sum, mut n) = (0.0, 0usize);
+        self.root.balance_into(&mut sum, &mut n);
+        if n == 0 { 0.0 } else { sum / n as f64 }
     }
 
-    pub fn decay(&mut self, alpha: f64) {
-        self.root.decay(alpha);
+    /// `(split_dim, split_val, depth)` of the internal node whose children's masses differ
+    /// the most, i.e. where the tree most clearly separates data from empty space. Ties go
+    /// to the split lying closest to the mean coordinate of the points reaching its node,
+    /// i.e. the one hugging the data most closely. Axis-aligned
+    /// splits falling outside the region their ancestors leave are skipped, as they send
+    /// everything one way without separating anything. For an oblique
+    /// split, `split_dim` is its first dimension and `split_val` a threshold on `w·x`.
+    /// Panics if no internal node splits its region.
+    pub fn most_informative_split(&self) -> (usize, f64, u32) {
+        let mut best: Option<(f64, f64, &Node)> = None;
+        let mut region = self.bounds.clone();
+        self.root.most_informative_into(&mut region, &mut best);
+        let (_, _, node) = best.expect("tree has no effective internal node");
+        (node.split_dim, node.split_val, node.depth)
     }
 
-    pub fn score(&self, x: &FeatureVector) -> f64 {
-        assert_eq!(x.len(), self.n_dims);
-        self.root.score(x, self.max_depth)
+    /// See [`HalfSpaceTrees::apply_region_masses`].
+    pub fn apply_region_masses(&mut self, regions: &[(Vec<(f64, f64)>, f64)]) {
+        let mut deposits = Vec::new();
+        for (bx, mass) in regions {
+            assert_eq!(bx.len(), self.n_dims, "box dimensionality mismatch");
+            let mut overlaps = Vec::new();
+            self.for_each_leaf_region(|_, region| {
+                let cell: Vec<(f64, f64)> = region
+                    .iter()
+                    .zip(bx)
+                    .map(|(r, b)| (r.0.max(b.0), r.1.min(b.1)))
+                    .collect();
+                let volume: f64 = cell.iter().map(|(lo, hi)| (hi - lo).max(0.0)).product();
+                if volume > 0.0 {
+                    let center: Vec<f64> = cell.iter().map(|(lo, hi)| 0.5 * (lo + hi)).collect();
+                    overlaps.push((center, volume));
+                }
+            });
+            let total: f64 = overlaps.iter().map(|(_, v)| v).sum();
+            for (center, volume) in overlaps {
+                deposits.push((center, mass * volume / total));
+            }
+        }
+        for (center, mass) in deposits {
+            self.insert_weighted(&center, mass);
+        }
+    }
+
+    /// See [`HalfSpaceTrees::seed_from_density`].
+    pub fn seed_from_density<F: Fn(&[f64]) -> f64>(&mut self, f: F, total_mass: f64) {
+        let mut seeds = Vec::new();
+        self.for_each_leaf_region(|_, region| {
+            // Splits are drawn over the full bounds, so some regions are empty and unreachable.
+            if region.iter().all(|(lo, hi)| lo < hi) {
+                let center: Vec<f64> = region.iter().map(|(lo, hi)| 0.5 * (lo + hi)).collect();
+                let density = f(&center).max(0.0);
+                seeds.push((center, density));
+            }
+        });
+        self.decay(0.0);
+        let total: f64 = seeds.iter().map(|(_, d)| d).sum();
+        if total <= 0.0 {
+            return;
+        }
+        for (center, density) in seeds {
+            if density > 0.0 {
+                self.insert_weighted(&center, total_mass * density / total);
+            }
+        }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct Node {
     // Split definition (valid for internal nodes)
     split_dim: usize,
     split_val: f64,
+    // Set for oblique splits, which compare the projection `w·x` against `split_val`
+    oblique: Option<Oblique>,
     // Tree structure
     left: Option<Box<Node>>,
     right: Option<Box<Node>>,
     // Stats
     depth: u32,
-    mass: f64, // exponentially decayed count
+    mass: f64,   // exponentially decayed count
+    visits: f64, // undecayed count, to tell rarity from forgetting
+    // Decayed first/second moments of x[split_dim] over inserts
+    sum: f64,
+    sum_sq: f64,
+}
+
+/// Direction of an oblique split over a 2-feature subspace.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Oblique {
+    dims: [usize; 2],
+    w: [f64; 2],
+}
+
+impl Oblique {
+    fn project(&self, x: &FeatureVector) -> f64 {
+        self.w[0] * x[self.dims[0]] + self.w[1] * x[self.dims[1]]
+    }
 }
 
 impl Node {
+    /// `repeats[d]` counts the axis splits on `d` among this node's ancestors; internal
+    /// nodes redraw their dimension until it is below `max_repeats`.
     fn randomized<R: Rng + ?Sized>(
         depth: u32,
         max_depth: u32,
         bounds: &[(f64, f64)],
+        mode: SplitMode,
+        repeats: &mut [u32],
+        max_repeats: u32,
         rng: &mut R,
     ) -> Self {
         // On construction we create a *full* binary tree to max_depth with random splits.
-        let n_dims = bounds.len();
-        let split_dim = rng.random_range(0..n_dims);
-        let (lo, hi) = bounds[split_dim];
-        let between = Uniform::try_from(lo..hi).unwrap();
-        let split_val = between.sample(rng);
+        // Leaves never route, so they always get an axis split (used for their moments).
+        let (split_dim, split_val, oblique) =
+            if mode == SplitMode::Oblique && varying(bounds) >= 2 && depth < max_depth {
+                Self::random_oblique(bounds, rng)
+            } else {
+                let mut split_dim = random_varying_dim(bounds, rng);
+                while depth < max_depth && repeats[split_dim] >= max_repeats {
+                    split_dim = random_varying_dim(bounds, rng);
+                }
+                let (lo, hi) = bounds[split_dim];
+                match Uniform::try_from(lo..hi) {
+                    Ok(between) => (split_dim, between.sample(rng), None),
+                    // Every dimension is constant; nothing to split.
+                    Err(_) => (split_dim, lo, None),
+                }
+            };
 
         if depth == max_depth {
             return Self {
                 split_dim,
                 split_val,
+                oblique,
                 left: None,
                 right: None,
                 depth,
                 mass: 0.0,
+                visits: 0.0,
+                sum: 0.0,
+                sum_sq: 0.0,
             };
         }
-        let left = Box::new(Node::randomized(depth + 1, max_depth, bounds, rng));
-        let right = Box::new(Node::randomized(depth + 1, max_depth, bounds, rng));
+        let axis = oblique.is_none();
+        if axis {
+            repeats[split_dim] += 1;
+        }
+        let mut child = || {
+            Box::new(Node::randomized(
+                depth + 1,
+                max_depth,
+                bounds,
+                mode,
+                repeats,
+                max_repeats,
+                rng,
+            ))
+        };
+        let (left, right) = (child(), child());
+        if axis {
+            repeats[split_dim] -= 1;
+        }
         Self {
             split_dim,
             split_val,
+            oblique,
             left: Some(left),
             right: Some(right),
             depth,
             mass: 0.0,
+            visits: 0.0,
+            sum: 0.0,
+            sum_sq: 0.0,
         }
     }
 
-    fn insert(&mut self, x: &FeatureVector) {
+    /// See [`HalfSpaceTree::padded`]. Leaves keep a dummy split.
+    fn padded<R: Rng + ?Sized>(
+        depth: u32,
+        max_depth: u32,
+        region: &mut [(f64, f64)],
+        padding: f64,
+        rng: &mut R,
+    ) -> Self {
+        let mut node = Self {
+            split_dim: 0,
+            split_val: region[0].0,
+            oblique: None,
+            left: None,
+            right: None,
+            depth,
+            mass: 0.0,
+            visits: 0.0,
+            sum: 0.0,
+            sum_sq: 0.0,
+        };
+        if depth == max_depth {
+            return node;
+        }
+        let dim = rng.random_range(0..region.len());
+        let saved = region[dim];
+        let (a, b) = saved;
+        let at = if b > a {
+            rng.random_range(a + padding * (b - a)..=b - padding * (b - a))
+        } else {
+            a
+        };
+        node.split_dim = dim;
+        node.split_val = at;
+        region[dim].1 = at;
+        node.left = Some(Box::new(Node::padded(
+            depth + 1,
+            max_depth,
+            region,
+            padding,
+            rng,
+        )));
+        region[dim] = (at, saved.1);
+        node.right = Some(Box::new(Node::padded(
+            depth + 1,
+            max_depth,
+            region,
+            padding,
+            rng,
+        )));
+        region[dim] = saved;
+        node
+    }
+
+    /// A random unit direction over two distinct dimensions, thresholded uniformly
+    /// within the range the projection takes over the bounding box.
+    fn random_oblique<R: Rng + ?Sized>(
+        bounds: &[(f64, f64)],
+        rng: &mut R,
+    ) -> (usize, f64, Option<Oblique>) {
+        let (d0, d1) = if varying(bounds) == bounds.len() {
+            let d0 = rng.random_range(0..bounds.len());
+            (d0, (d0 + rng.random_range(1..bounds.len())) % bounds.len())
+        } else {
+            let d0 = random_varying_dim(bounds, rng);
+            let d1 = loop {
+                let d = random_varying_dim(bounds, rng);
+                if d != d0 {
+                    break d;
+                }
+            };
+            (d0, d1)
+        };
+        let theta = rng.random_range(0.0..std::f64::consts::TAU);
+        let oblique = Oblique {
+            dims: [d0, d1],
+            w: [theta.cos(), theta.sin()],
+        };
+        let mut lo = 0.0;
+        let mut hi = 0.0;
+        for (w, d) in oblique.w.iter().zip(oblique.dims) {
+            let (a, b) = (w * bounds[d].0, w * bounds[d].1);
+            lo += a.min(b);
+            hi += a.max(b);
+        }
+        let split_val = if hi > lo {
+            rng.random_range(lo..hi)
+        } else {
+            lo
+        };
+        (d0, split_val, Some(oblique))
+    }
+
+    /// The coordinate this node splits on: `x[split_dim]`, or `w·x` for oblique splits.
+    fn coord(&self, x: &FeatureVector) -> f64 {
+        match &self.oblique {
+            Some(o) => o.project(x),
+            None => x[self.split_dim],
+        }
+    }
+
+    fn goes_left(&self, x: &FeatureVector) -> bool {
+        self.coord(x) < self.split_val
+    }
+
+    fn insert(&mut self, x: &FeatureVector, weight: f64) {
         // Update local mass then descend
-        self.mass += 1.0;
+        self.mass += weight;
+        self.visits += weight;
+        let v = self.coord(x);
+        self.sum += weight * v;
+        self.sum_sq += weight * v * v;
+        let left = self.goes_left(x);
+        if let (Some(l), Some(r)) = (&mut self.left, &mut self.right) {
+            if left {
+                l.insert(x, weight);
+            } else {
+                r.insert(x, weight);
+            }
+        }
+    }
+
+    /// Collect references to all leaves under this node, left to right.
+    fn leaves<'a>(&'a self, out: &mut Vec<&'a Node>) {
+        match (&self.left, &self.right) {
+            (Some(l), Some(r)) => {
+                l.leaves(out);
+                r.leaves(out);
+            }
+            _ => out.push(self),
+        }
+    }
+
+    /// Depth-first walk over leaves, narrowing `region` by each split on the way down.
+    fn for_each_leaf_region<F>(&self, region: &mut [(f64, f64)], f: &mut F)
+    where
+        F: FnMut(&Node, &[(f64, f64)]),
+    {
+        match (&self.left, &self.right) {
+            (Some(l), Some(r)) if self.oblique.is_some() => {
+                // An oblique cut does not shrink the axis-aligned box.
+                l.for_each_leaf_region(region, f);
+                r.for_each_leaf_region(region, f);
+            }
+            (Some(l), Some(r)) => {
+                let saved = region[self.split_dim];
+                region[self.split_dim].1 = saved.1.min(self.split_val);
+                l.for_each_leaf_region(region, f);
+                region[self.split_dim] = (saved.0.max(self.split_val), saved.1);
+                r.for_each_leaf_region(region, f);
+                region[self.split_dim] = saved;
+            }
+            _ => f(self, region),
+        }
+    }
+
+    /// See [`HalfSpaceTree::merge_similar_leaves`].
+    fn merge_similar_leaves(&mut self, rel_tol: f64) {
+        let (Some(l), Some(r)) = (&mut self.left, &mut self.right) else {
+            return;
+        };
+        l.merge_similar_leaves(rel_tol);
+        r.merge_similar_leaves(rel_tol);
+        let both_leaves = l.left.is_none() && r.left.is_none();
+        let (a, b) = (l.mass, r.mass);
+        if both_leaves && (a - b).abs() <= rel_tol * a.abs().max(b.abs()) {
+            self.mass = a + b;
+            self.left = None;
+            self.right = None;
+        }
+    }
+
+    /// See [`HalfSpaceTree::deepened`]; `region` is this node's reconstructed box.
+    fn deepen<R: Rng + ?Sized>(&mut self, extra: u32, region: &mut [(f64, f64)], rng: &mut R) {
         match (&mut self.left, &mut self.right) {
+            (Some(l), Some(r)) if self.oblique.is_some() => {
+                l.deepen(extra, region, rng);
+                r.deepen(extra, region, rng);
+            }
             (Some(l), Some(r)) => {
-                if x[self.split_dim] < self.split_val {
-                    l.insert(x);
-                } else {
-                    r.insert(x);
+                let saved = region[self.split_dim];
+                region[self.split_dim].1 = saved.1.min(self.split_val);
+                l.deepen(extra, region, rng);
+                region[self.split_dim] = (saved.0.max(self.split_val), saved.1);
+                r.deepen(extra, region, rng);
+                region[self.split_dim] = saved;
+            }
+            _ => {
+                let mut repeats = vec![0; region.len()];
+                *self = Node::randomized(
+                    self.depth,
+                    self.depth + extra,
+                    region,
+                    SplitMode::AxisAligned,
+                    &mut repeats,
+                    u32::MAX,
+                    rng,
+                );
+            }
+        }
+    }
+
+    /// Add this subtree's node count per depth into `levels`.
+    fn count_by_level(&self, levels: &mut Vec<usize>) {
+        let d = self.depth as usize;
+        if levels.len() <= d {
+            levels.resize(d + 1, 0);
+        }
+        levels[d] += 1;
+        if let (Some(l), Some(r)) = (&self.left, &self.right) {
+            l.count_by_level(levels);
+            r.count_by_level(levels);
+        }
+    }
+
+    /// Replace every axis-aligned threshold `v` on dimension `d` below this node by `f(d, v)`.
+    fn map_thresholds<F: Fn(usize, f64) -> f64>(&mut self, f: &F) {
+        if self.oblique.is_none() {
+            self.split_val = f(self.split_dim, self.split_val);
+        }
+        if let (Some(l), Some(r)) = (&mut self.left, &mut self.right) {
+            l.map_thresholds(f);
+            r.map_thresholds(f);
+        }
+    }
+
+    /// Push the threshold of every axis-aligned internal split below this node onto
+    /// `cuts[split_dim]`.
+    fn collect_thresholds(&self, cuts: &mut [Vec<f64>]) {
+        if let (Some(l), Some(r)) = (&self.left, &self.right) {
+            if self.oblique.is_none() {
+                cuts[self.split_dim].push(self.split_val);
+            }
+            l.collect_thresholds(cuts);
+            r.collect_thresholds(cuts);
+        }
+    }
+
+    /// Accumulate the balance of every internal node with positive mass below this one.
+    fn balance_into(&self, sum: &mut f64, n: &mut usize) {
+        if let (Some(l), Some(r)) = (&self.left, &self.right) {
+            let total = l.mass + r.mass;
+            if total > 0.0 {
+                *sum += 2.0 * l.mass.min(r.mass) / total;
+                *n += 1;
+            }
+            l.balance_into(sum, n);
+            r.balance_into(sum, n);
+        }
+    }
+
+    /// Keep in `best` the internal node with the largest child mass difference, the split
+    /// nearest its points' mean winning ties, skipping axis splits outside `region`.
+    fn most_informative_into<'a>(
+        &'a self,
+        region: &mut [(f64, f64)],
+        best: &mut Option<(f64, f64, &'a Node)>,
+    ) {
+        let (Some(l), Some(r)) = (&self.left, &self.right) else {
+            return;
+        };
+        let (lo, hi) = region[self.split_dim];
+        if self.oblique.is_some() || (lo < self.split_val && self.split_val < hi) {
+            let diff = (l.mass - r.mass).abs();
+            let gap = if self.mass > 0.0 {
+                (self.split_val - self.sum / self.mass).abs()
+            } else {
+                f64::INFINITY
+            };
+            match best {
+                Some((d, g, _)) if *d > diff || (*d == diff && *g <= gap) => {}
+                _ => *best = Some((diff, gap, self)),
+            }
+        }
+        if self.oblique.is_some() {
+            l.most_informative_into(region, best);
+            r.most_informative_into(region, best);
+            return;
+        }
+        let saved = region[self.split_dim];
+        region[self.split_dim].1 = saved.1.min(self.split_val);
+        l.most_informative_into(region, best);
+        region[self.split_dim] = (saved.0.max(self.split_val), saved.1);
+        r.most_informative_into(region, best);
+        region[self.split_dim] = saved;
+    }
+
+    fn uses_dim(&self, dim: usize) -> bool {
+        match (&self.left, &self.right) {
+            (Some(l), Some(r)) => {
+                let here = match &self.oblique {
+                    Some(o) => o.dims.contains(&dim),
+                    None => self.split_dim == dim,
+                };
+                here || l.uses_dim(dim) || r.uses_dim(dim)
+            }
+            _ => false,
+        }
+    }
+
+    /// Structural equality of the split layout; masses are ignored.
+    fn same_geometry(&self, other: &Node) -> bool {
+        if self.depth != other.depth {
+            return false;
+        }
+        match (&self.left, &self.right, &other.left, &other.right) {
+            (Some(l), Some(r), Some(ol), Some(or)) => {
+                self.split_dim == other.split_dim
+                    && self.split_val.to_bits() == other.split_val.to_bits()
+                    && self.oblique == other.oblique
+                    && l.same_geometry(ol)
+                    && r.same_geometry(or)
+            }
+            (None, None, None, None) => true,
+            _ => false,
+        }
+    }
+
+    /// Scale each leaf by the overlap-weighted mean of `factors` over the `dim` bins
+    /// starting at `bins.0` with width `bins.1`, and recompute parents (moments scaled
+    /// alongside). Returns the new mass.
+    fn reweight_leaves(
+        &mut self,
+        region: &mut [(f64, f64)],
+        dim: usize,
+        bins: (f64, f64),
+        factors: &[f64],
+    ) -> f64 {
+        let new_mass = match (&mut self.left, &mut self.right) {
+            (Some(l), Some(r)) if self.oblique.is_some() => {
+                l.reweight_leaves(region, dim, bins, factors)
+                    + r.reweight_leaves(region, dim, bins, factors)
+            }
+            (Some(l), Some(r)) => {
+                let saved = region[self.split_dim];
+                region[self.split_dim].1 = saved.1.min(self.split_val);
+                let left = l.reweight_leaves(region, dim, bins, factors);
+                region[self.split_dim] = (saved.0.max(self.split_val), saved.1);
+                let right = r.reweight_leaves(region, dim, bins, factors);
+                region[self.split_dim] = saved;
+                left + right
+            }
+            _ => {
+                let (a, b) = region[dim];
+                if b <= a {
+                    return self.mass;
                 }
+                let (lo, width) = bins;
+                let factor: f64 = factors
+                    .iter()
+                    .enumerate()
+                    .map(|(i, f)| {
+                        let (bin_lo, bin_hi) = (lo + i as f64 * width, lo + (i + 1) as f64 * width);
+                        f * (b.min(bin_hi) - a.max(bin_lo)).max(0.0)
+                    })
+                    .sum::<f64>()
+                    / (b - a);
+                self.mass * factor
             }
-            _ => {}
+        };
+        if self.mass > 0.0 {
+            let scale = new_mass / self.mass;
+            self.sum *= scale;
+            self.sum_sq *= scale;
+        }
+        self.mass = new_mass;
+        new_mass
+    }
+
+    /// Zero every negative or non-finite mass in this subtree; returns how many were fixed.
+    fn sanitize_masses(&mut self) -> usize {
+        let mut repaired = 0;
+        if !self.mass.is_finite() || self.mass < 0.0 {
+            self.mass = 0.0;
+            repaired += 1;
+        }
+        if let (Some(l), Some(r)) = (&mut self.left, &mut self.right) {
+            repaired += l.sanitize_masses() + r.sanitize_masses();
         }
+        repaired
     }
 
     fn decay(&mut self, alpha: f64) {
         self.mass *= alpha;
+        self.sum *= alpha;
+        self.sum_sq *= alpha;
         if let Some(l) = &mut self.left {
             l.decay(alpha);
         }
@@ -209,24 +3842,274 @@ impl Node {
         }
     }
 
-    fn score(&self, x: &FeatureVector, max_depth: u32) -> f64 {
+    /// Decay, then add `prior` to each leaf; internal nodes absorb their leaves' additions
+    /// so a node's mass stays the sum of its children's. Returns the mass added below.
+    fn decay_with_prior(&mut self, alpha: f64, prior: f64) -> f64 {
+        self.mass *= alpha;
+        self.sum *= alpha;
+        self.sum_sq *= alpha;
+        let added = match (&mut self.left, &mut self.right) {
+            (Some(l), Some(r)) => {
+                l.decay_with_prior(alpha, prior) + r.decay_with_prior(alpha, prior)
+            }
+            _ => prior,
+        };
+        self.mass += added;
+        added
+    }
+
+    /// Decay this node, recurse along `x`'s path, then add `x`; returns the score computed
+    /// from the decayed masses. `acc` carries the harmonic numerator/denominator.
+    fn process_fused(
+        &mut self,
+        x: &FeatureVector,
+        alpha: f64,
+        weight: f64,
+        max_depth: u32,
+        weighting: DepthWeighting,
+        acc: (f64, f64),
+    ) -> f64 {
+        self.mass *= alpha;
+        self.sum *= alpha;
+        self.sum_sq *= alpha;
+        let w = 1.0 / ((max_depth - self.depth) as f64 + 1.0);
+        let acc = (acc.0 + w * self.rarity(), acc.1 + w);
+        let left = self.goes_left(x);
+        let score = match (&mut self.left, &mut self.right) {
+            (Some(l), Some(r)) => {
+                let child = if left { l } else { r };
+                child.process_fused(x, alpha, weight, max_depth, weighting, acc)
+            }
+            _ => match weighting {
+                DepthWeighting::Linear => self.leaf_score(max_depth),
+                DepthWeighting::Harmonic => acc.0 / acc.1 * self.depth_factor(max_depth),
+            },
+        };
+        let v = self.coord(x);
+        self.mass += weight;
+        self.visits += weight;
+        self.sum += weight * v;
+        self.sum_sq += weight * v * v;
+        score
+    }
+
+    fn score(&self, x: &FeatureVector, max_depth: u32, weighting: DepthWeighting) -> f64 {
+        self.score_scaled(x, max_depth, weighting, 1.0, 1.0)
+    }
+
+    /// Score as if every mass were multiplied by `scale`, with rarity
+    /// `1 / (smoothing + mass)` instead of the usual `1 / (1 + mass)`.
+    fn score_scaled(
+        &self,
+        x: &FeatureVector,
+        max_depth: u32,
+        weighting: DepthWeighting,
+        scale: f64,
+        smoothing: f64,
+    ) -> f64 {
         // Traverse to a leaf (or max depth) and compute a rarity score from leaf mass and depth.
-        let mut node = self;
-        loop {
-            match (&node.left, &node.right) {
-                (Some(l), Some(r)) => {
-                    node = if x[node.split_dim] < node.split_val {
-                        l
-                    } else {
-                        r
-                    };
+        match weighting {
+            DepthWeighting::Linear => {
+                let leaf = self.leaf(x);
+                leaf.rarity_scaled(scale, smoothing) * leaf.depth_factor(max_depth)
+            }
+            DepthWeighting::Harmonic => {
+                let (mut num, mut den) = (0.0, 0.0);
+                let mut node = self;
+                loop {
+                    let w = 1.0 / ((max_depth - node.depth) as f64 + 1.0);
+                    num += w * node.rarity_scaled(scale, smoothing);
+                    den += w;
+                    match (&node.left, &node.right) {
+                        (Some(l), Some(r)) => {
+                            node = if node.goes_left(x) { l } else { r };
+                        }
+                        _ => break,
+                    }
+                }
+                num / den * node.depth_factor(max_depth)
+            }
+        }
+    }
+
+    /// `score_scaled` with unit smoothing, and its derivative with respect to `scale`.
+    fn score_scaled_grad(
+        &self,
+        x: &FeatureVector,
+        max_depth: u32,
+        weighting: DepthWeighting,
+        scale: f64,
+    ) -> (f64, f64) {
+        match weighting {
+            DepthWeighting::Linear => {
+                let leaf = self.leaf(x);
+                let (r, dr) = leaf.rarity_scaled_grad(scale);
+                let factor = leaf.depth_factor(max_depth);
+                (r * factor, dr * factor)
+            }
+            DepthWeighting::Harmonic => {
+                let (mut num, mut dnum, mut den) = (0.0, 0.0, 0.0);
+                let mut node = self;
+                loop {
+                    let w = 1.0 / ((max_depth - node.depth) as f64 + 1.0);
+                    let (r, dr) = node.rarity_scaled_grad(scale);
+                    num += w * r;
+                    dnum += w * dr;
+                    den += w;
+                    match (&node.left, &node.right) {
+                        (Some(l), Some(r)) => {
+                            node = if node.goes_left(x) { l } else { r };
+                        }
+                        _ => break,
+                    }
                 }
-                _ => break,
+                let factor = node.depth_factor(max_depth);
+                (num / den * factor, dnum / den * factor)
+            }
+        }
+    }
+
+    /// `rarity_scaled` with unit smoothing, and its derivative with respect to `scale`.
+    fn rarity_scaled_grad(&self, scale: f64) -> (f64, f64) {
+        let m = self.mass.max(0.0);
+        let r = 1.0 / (1.0 + m * scale);
+        (r, -m * r * r)
+    }
+
+    /// Heap-order id of the leaf `x` routes to (this node 0, children of `i` at `2i + 1`
+    /// and `2i + 2`), unique among leaves whatever the tree's shape.
+    fn leaf_index(&self, x: &FeatureVector) -> usize {
+        let mut node = self;
+        let mut index = 0;
+        while let (Some(l), Some(r)) = (&node.left, &node.right) {
+            let left = node.goes_left(x);
+            index = 2 * index + if left { 1 } else { 2 };
+            node = if left { l } else { r };
+        }
+        index
+    }
+
+    /// Left-to-right position of the leaf `x` routes to, as in `leaves`.
+    fn leaf_position(&self, x: &FeatureVector) -> usize {
+        let mut node = self;
+        let mut position = 0;
+        while let (Some(l), Some(r)) = (&node.left, &node.right) {
+            if node.goes_left(x) {
+                node = l;
+            } else {
+                position += l.leaf_count();
+                node = r;
             }
         }
-        let depth_factor = 1.0 + (max_depth - node.depth) as f64 / (max_depth as f64 + 1.0);
+        position
+    }
+
+    fn leaf_count(&self) -> usize {
+        match (&self.left, &self.right) {
+            (Some(l), Some(r)) => l.leaf_count() + r.leaf_count(),
+            _ => 1,
+        }
+    }
+
+    /// The leaf `x` routes to.
+    fn leaf(&self, x: &FeatureVector) -> &Node {
+        let mut node = self;
+        while let (Some(l), Some(r)) = (&node.left, &node.right) {
+            node = if node.goes_left(x) { l } else { r };
+        }
+        node
+    }
+
+    /// Nodes visited by `x` from this node down to its leaf (inclusive).
+    fn path(&self, x: &FeatureVector) -> Vec<&Node> {
+        let mut node = self;
+        let mut path = vec![node];
+        while let (Some(l), Some(r)) = (&node.left, &node.right) {
+            node = if node.goes_left(x) { l } else { r };
+            path.push(node);
+        }
+        path
+    }
+
+    /// Rarity score of this node treated as the reached leaf.
+    fn leaf_score(&self, max_depth: u32) -> f64 {
+        self.rarity() * self.depth_factor(max_depth)
+    }
+
+    fn depth_factor(&self, max_depth: u32) -> f64 {
+        1.0 + (max_depth - self.depth) as f64 / (max_depth as f64 + 1.0)
+    }
+
+    /// Mass-weighted variance of `x[split_dim]` over the points that reached this node.
+    fn variance(&self) -> f64 {
+        if self.mass <= 0.0 {
+            return 0.0;
+        }
+        let mean = self.sum / self.mass;
+        (self.sum_sq / self.mass - mean * mean).max(0.0)
+    }
+
+    /// `ScoreMethod::VarianceAware` multiplier given this leaf's width along its split dimension.
+    fn spread_factor(&self, width: f64) -> f64 {
+        if width <= 0.0 {
+            return 1.0;
+        }
+        1.0 + 2.0 * self.variance().sqrt() / width
+    }
+
+    fn rarity(&self) -> f64 {
+        self.rarity_scaled(1.0, 1.0)
+    }
+
+    fn rarity_scaled(&self, scale: f64, smoothing: f64) -> f64 {
         // Smooth rarity: small mass -> high score; clamp to avoid division blow‑ups.
-        let rarity = 1.0 / (1.0 + node.mass.max(0.0));
-        rarity * depth_factor
+        1.0 / (smoothing + (self.mass * scale).max(0.0))
+    }
+
+    /// Leaf score of `x`, averaging both children by mass wherever the split reads a
+    /// dropped dimension.
+    fn score_with_dropped(&self, x: &FeatureVector, dropped: &[bool], max_depth: u32) -> f64 {
+        let (Some(l), Some(r)) = (&self.left, &self.right) else {
+            return self.leaf_score(max_depth);
+        };
+        let blind = match &self.oblique {
+            Some(o) => o.dims.iter().any(|&d| dropped[d]),
+            None => dropped[self.split_dim],
+        };
+        if !blind {
+            let next = if self.goes_left(x) { l } else { r };
+            return next.score_with_dropped(x, dropped, max_depth);
+        }
+        let (wl, wr) = if l.mass + r.mass > 0.0 {
+            (l.mass.max(0.0), r.mass.max(0.0))
+        } else {
+            (1.0, 1.0)
+        };
+        (wl * l.score_with_dropped(x, dropped, max_depth)
+            + wr * r.score_with_dropped(x, dropped, max_depth))
+            / (wl + wr)
+    }
+
+    /// Max leaf score over every branch `x` could take if each feature moved by up to `eps`.
+    fn worst_case_score(&self, x: &FeatureVector, eps: f64, max_depth: u32) -> f64 {
+        match (&self.left, &self.right) {
+            (Some(l), Some(r)) => {
+                let v = self.coord(x);
+                // A perturbation of `eps` per feature moves `w·x` by up to `eps·(|w0| + |w1|)`
+                let reach = match &self.oblique {
+                    Some(o) => eps * (o.w[0].abs() + o.w[1].abs()),
+                    None => eps,
+                };
+                if (v - self.split_val).abs() <= reach {
+                    l.worst_case_score(x, eps, max_depth)
+                        .max(r.worst_case_score(x, eps, max_depth))
+                } else if v < self.split_val {
+                    l.worst_case_score(x, eps, max_depth)
+                } else {
+                    r.worst_case_score(x, eps, max_depth)
+                }
+            }
+            _ => self.leaf_score(max_depth),
+        }
     }
 }
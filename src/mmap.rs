@@ -0,0 +1,190 @@
+//! Memory-mapped read-only forests for inference (requires the `mmap` feature, unix only).
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::fd::AsRawFd;
+use std::path::Path;
+
+use crate::flat::MAX_FLAT_DEPTH;
+use crate::{FeatureVector, ForestLayout, HalfSpaceTrees, HstError, Result};
+
+const MAGIC: &[u8; 8] = b"HSTFLAT1";
+// Magic plus `n_trees`, `max_depth` and `n_dims` as little-endian u64s
+const HEADER_BYTES: usize = 32;
+
+impl HalfSpaceTrees {
+    /// Write the forest's [`HalfSpaceTrees::to_flat_f32`] blob to `path` for
+    /// [`MmapForest::open`]: an 8-byte magic, `n_trees`, `max_depth` and `n_dims` as
+    /// little-endian `u64`s, then the blob as little-endian `f32`s. Forests the flat
+    /// layout cannot hold fail with [`io::ErrorKind::InvalidInput`].
+    pub fn save_flat(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let (data, layout) = self
+            .to_flat_f32()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let mut bytes = Vec::with_capacity(HEADER_BYTES + 4 * data.len());
+        bytes.extend_from_slice(MAGIC);
+        for v in [layout.n_trees, layout.max_depth as usize, layout.n_dims] {
+            bytes.extend_from_slice(&(v as u64).to_le_bytes());
+        }
+        for v in data {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        File::create(path)?.write_all(&bytes)
+    }
+}
+
+/// A forest scored straight from a memory-mapped [`HalfSpaceTrees::save_flat`] file,
+/// without building any nodes on the heap. Pages are loaded lazily by the OS and shared
+/// between processes mapping the same file.
+///
+/// Scores match [`HalfSpaceTrees::from_flat_f32`] on the same blob with default settings:
+/// uniform tree weights, `Linear` depth weighting and the `Standard` method. The file must
+/// not be modified while mapped.
+#[derive(Debug)]
+pub struct MmapForest {
+    ptr: *mut libc::c_void,
+    len: usize,
+    layout: ForestLayout,
+}
+
+// The mapping is private and read-only, so sharing it across threads is sound.
+unsafe impl Send for MmapForest {}
+unsafe impl Sync for MmapForest {}
+
+impl MmapForest {
+    /// Map `path` and validate its header, length and split dimensions.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::open(path).map_err(|e| HstError::Io(e.to_string()))?;
+        let len = file
+            .metadata()
+            .map_err(|e| HstError::Io(e.to_string()))?
+            .len() as usize;
+        if len < HEADER_BYTES {
+            return Err(HstError::InvalidLayout("file shorter than header".into()));
+        }
+        // SAFETY: a fresh read-only private mapping of a file we hold open; the result is
+        // checked before use and unmapped in `Drop`.
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(HstError::Io(io::Error::last_os_error().to_string()));
+        }
+        let mut forest = Self {
+            ptr,
+            len,
+            layout: ForestLayout::new(0, 0, 0),
+        };
+        forest.layout = forest.read_layout()?;
+        forest.validate()?;
+        Ok(forest)
+    }
+
+    pub fn layout(&self) -> ForestLayout {
+        self.layout
+    }
+
+    pub fn n_dims(&self) -> usize {
+        self.layout.n_dims
+    }
+
+    /// Mean over trees of the reached leaf's rarity `1 / (1 + mass)`.
+    pub fn score(&self, x: &FeatureVector) -> f64 {
+        let layout = &self.layout;
+        assert_eq!(x.len(), layout.n_dims);
+        if layout.n_trees == 0 {
+            return 0.0;
+        }
+        let mut total = 0.0;
+        for tree in 0..layout.n_trees {
+            let start = layout.header_len + tree * layout.tree_stride;
+            let mut i = 0;
+            for _ in 0..layout.max_depth {
+                let rec = start + i * layout.node_stride;
+                let dim = self.float(rec + layout.dim_offset) as usize;
+                let val = self.float(rec + layout.value_offset) as f64;
+                i = if x[dim] < val { 2 * i + 1 } else { 2 * i + 2 };
+            }
+            let mass = self.float(start + i * layout.node_stride + layout.mass_offset) as f64;
+            total += 1.0 / (1.0 + mass.max(0.0));
+        }
+        total / layout.n_trees as f64
+    }
+
+    fn bytes(&self) -> &[u8] {
+        // SAFETY: `ptr` maps `len` readable bytes for as long as `self` lives.
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+
+    /// The `i`-th float of the blob following the header.
+    fn float(&self, i: usize) -> f32 {
+        let at = HEADER_BYTES + 4 * i;
+        f32::from_le_bytes(self.bytes()[at..at + 4].try_into().unwrap())
+    }
+
+    fn read_layout(&self) -> Result<ForestLayout> {
+        let bytes = self.bytes();
+        if &bytes[..8] != MAGIC {
+            return Err(HstError::InvalidLayout("not a flat forest file".into()));
+        }
+        let field = |k: usize| u64::from_le_bytes(bytes[8 * k..8 * k + 8].try_into().unwrap());
+        let (n_trees, max_depth, n_dims) = (field(1), field(2), field(3));
+        if n_dims == 0
+            || max_depth > MAX_FLAT_DEPTH as u64
+            || n_trees > u32::MAX as u64
+            || n_dims > u32::MAX as u64
+        {
+            return Err(HstError::InvalidLayout(format!(
+                "implausible shape: {n_trees} trees, depth {max_depth}, {n_dims} dims"
+            )));
+        }
+        // Checked in u128 first so a corrupt header cannot overflow the layout arithmetic.
+        let floats = 2 * n_dims as u128 + n_trees as u128 * 3 * ((1u128 << (max_depth + 1)) - 1);
+        let expected = HEADER_BYTES as u128 + 4 * floats;
+        if self.len as u128 != expected {
+            return Err(HstError::InvalidLayout(format!(
+                "expected {expected} bytes, found {}",
+                self.len
+            )));
+        }
+        Ok(ForestLayout::new(
+            n_trees as usize,
+            max_depth as u32,
+            n_dims as usize,
+        ))
+    }
+
+    /// Check every internal node's split dimension once, so scoring can index freely.
+    fn validate(&self) -> Result<()> {
+        let layout = &self.layout;
+        let internal = (1usize << layout.max_depth) - 1;
+        for tree in 0..layout.n_trees {
+            let start = layout.header_len + tree * layout.tree_stride;
+            for i in 0..internal {
+                let dim = self.float(start + i * layout.node_stride + layout.dim_offset);
+                if !(dim >= 0.0 && (dim as usize) < layout.n_dims && dim.fract() == 0.0) {
+                    return Err(HstError::InvalidLayout(format!(
+                        "tree {tree} node {i} has invalid split dimension {dim}"
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for MmapForest {
+    fn drop(&mut self) {
+        // SAFETY: `ptr` and `len` came from a successful `mmap` and are unmapped once.
+        unsafe {
+            libc::munmap(self.ptr, self.len);
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! Score-distribution monitoring over time.
+
+use crate::{FeatureVector, HalfSpaceTrees};
+
+/// Decayed histogram of recent scores, for watching the score distribution drift.
+///
+/// Every [`ScoreDistributionMonitor::observe`] first decays all bins by `alpha`, then
+/// counts the score in its bin; scores outside the range land in the nearest end bin.
+/// Take a [`ScoreDistributionMonitor::snapshot`] now and compare against it later with
+/// [`ScoreDistributionMonitor::divergence_from`].
+#[derive(Debug, Clone)]
+pub struct ScoreDistributionMonitor {
+    range: (f64, f64),
+    counts: Vec<f64>,
+    alpha: f64,
+}
+
+/// Normalized score histogram captured by [`ScoreDistributionMonitor::snapshot`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoreSnapshot {
+    range: (f64, f64),
+    probabilities: Vec<f64>,
+}
+
+impl ScoreSnapshot {
+    /// Fraction of the decayed mass in each bin; all zero before any observation.
+    pub fn probabilities(&self) -> &[f64] {
+        &self.probabilities
+    }
+}
+
+impl ScoreDistributionMonitor {
+    /// `bins` equal-width bins over `[lo, hi)`, decayed by `alpha` in (0,1] per score.
+    pub fn new(bins: usize, lo: f64, hi: f64, alpha: f64) -> Self {
+        assert!(bins > 0, "bins must be positive");
+        assert!(lo < hi, "lo must be below hi");
+        assert!(alpha > 0.0 && alpha <= 1.0, "alpha must be in (0,1]");
+        Self {
+            range: (lo, hi),
+            counts: vec![0.0; bins],
+            alpha,
+        }
+    }
+
+    pub fn observe(&mut self, score: f64) {
+        self.counts.iter_mut().for_each(|c| *c *= self.alpha);
+        let (lo, hi) = self.range;
+        let bins = self.counts.len();
+        let bin = ((score - lo) / (hi - lo) * bins as f64).floor();
+        // NaN casts to 0 and lands in the first bin.
+        self.counts[(bin.max(0.0) as usize).min(bins - 1)] += 1.0;
+    }
+
+    pub fn snapshot(&self) -> ScoreSnapshot {
+        let total: f64 = self.counts.iter().sum();
+        ScoreSnapshot {
+            range: self.range,
+            probabilities: self
+                .counts
+                .iter()
+                .map(|c| if total > 0.0 { c / total } else { 0.0 })
+                .collect(),
+        }
+    }
+
+    /// Jensen-Shannon divergence (base 2, so in [0,1]) between the current distribution
+    /// and `past`: 0 for identical histograms, 1 for disjoint ones. Empty histograms
+    /// count as identical to anything.
+    pub fn divergence_from(&self, past: &ScoreSnapshot) -> f64 {
+        assert!(
+            past.range == self.range && past.probabilities.len() == self.counts.len(),
+            "snapshot taken with different bins"
+        );
+        let now = self.snapshot();
+        let (p, q) = (&now.probabilities, &past.probabilities);
+        if p.iter().all(|&v| v == 0.0) || q.iter().all(|&v| v == 0.0) {
+            return 0.0;
+        }
+        let kl_to_mid = |a: &[f64], b: &[f64]| -> f64 {
+            a.iter()
+                .zip(b)
+                .filter(|(x, _)| **x > 0.0)
+                .map(|(x, y)| x * (2.0 * x / (x + y)).log2())
+                .sum()
+        };
+        (0.5 * (kl_to_mid(p, q) + kl_to_mid(q, p))).clamp(0.0, 1.0)
+    }
+}
+
+impl HalfSpaceTrees {
+    /// Score `x`, then insert it and record the score in `monitor`. Returns the score.
+    pub fn process_monitored(
+        &mut self,
+        x: &FeatureVector,
+        monitor: &mut ScoreDistributionMonitor,
+    ) -> f64 {
+        let score = self.score(x);
+        self.insert(x);
+        monitor.observe(score);
+        score
+    }
+}
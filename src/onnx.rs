@@ -0,0 +1,261 @@
+//! ONNX export of the scoring function (requires the `onnx` feature).
+//!
+//! The protobuf is written by hand: the model needs only a handful of message types, so
+//! pulling in a protobuf toolchain is not worth it.
+
+use crate::{HalfSpaceTree, HalfSpaceTrees, Node};
+
+// `onnx.proto` field numbers and enum values used below
+const IR_VERSION: u64 = 8;
+const ML_OPSET: u64 = 3;
+const DEFAULT_OPSET: u64 = 17;
+const ATTR_INT: u64 = 2;
+const ATTR_STRING: u64 = 3;
+const ATTR_FLOATS: u64 = 6;
+const ATTR_INTS: u64 = 7;
+const ATTR_STRINGS: u64 = 8;
+const ELEM_FLOAT: u64 = 1;
+
+impl HalfSpaceTrees {
+    /// Serialize the forest's scoring as an ONNX model: one `ai.onnx.ml`
+    /// `TreeEnsembleRegressor` mapping input `X` (`float[N, n_dims]`) to output `Y`
+    /// (`float[N, 1]`).
+    ///
+    /// This is an approximation frozen at export time. Each leaf carries its precomputed
+    /// score, including depth weighting and the `VarianceAware` spread, scaled by its
+    /// tree's normalized weight and summed over trees. Masses are static, so later inserts
+    /// and decays are not reflected. Splits and scores are rounded to `f32`, so points
+    /// within rounding of a split may route differently. The score quantum is not applied.
+    ///
+    /// # Panics
+    /// If any tree has oblique splits, which `TreeEnsembleRegressor` cannot express.
+    pub fn to_onnx(&self) -> Vec<u8> {
+        let total_weight: f64 = self.weights.iter().sum();
+        let mut ensemble = Ensemble::default();
+        for (tree_id, (t, &w)) in self.trees.iter().zip(&self.weights).enumerate() {
+            let scale = if total_weight > 0.0 {
+                w / total_weight
+            } else {
+                0.0
+            };
+            let mut region = t.bounds.clone();
+            ensemble.tree_id = tree_id as u64;
+            ensemble.next_id = 0;
+            ensemble.add(&t.root, t, &mut region, (0.0, 0.0), scale);
+        }
+
+        let mut node = Proto::default();
+        node.string(1, "X");
+        node.string(2, "Y");
+        node.string(3, "half_space_trees");
+        node.string(4, "TreeEnsembleRegressor");
+        node.string(7, "ai.onnx.ml");
+        for attr in ensemble.attributes() {
+            node.message(5, attr);
+        }
+
+        let mut graph = Proto::default();
+        graph.message(1, node);
+        graph.string(2, "half_space_trees");
+        graph.message(11, tensor_value_info("X", self.n_dims() as u64));
+        graph.message(12, tensor_value_info("Y", 1));
+
+        let mut model = Proto::default();
+        model.varint(1, IR_VERSION);
+        model.string(2, "half-space-trees");
+        model.string(3, env!("CARGO_PKG_VERSION"));
+        model.message(7, graph);
+        for (domain, version) in [("", DEFAULT_OPSET), ("ai.onnx.ml", ML_OPSET)] {
+            let mut opset = Proto::default();
+            opset.string(1, domain);
+            opset.varint(2, version);
+            model.message(8, opset);
+        }
+        model.0
+    }
+}
+
+/// `TreeEnsembleRegressor` attribute arrays, filled in preorder.
+#[derive(Default)]
+struct Ensemble {
+    tree_id: u64,
+    next_id: u64,
+    tree_ids: Vec<u64>,
+    node_ids: Vec<u64>,
+    feature_ids: Vec<u64>,
+    values: Vec<f32>,
+    modes: Vec<&'static str>,
+    true_ids: Vec<u64>,
+    false_ids: Vec<u64>,
+    leaf_tree_ids: Vec<u64>,
+    leaf_node_ids: Vec<u64>,
+    leaf_weights: Vec<f32>,
+}
+
+impl Ensemble {
+    /// Append `node`'s subtree, returning its node id. `acc` is as for
+    /// [`Node::exported_leaf_score`], excluding `node`.
+    fn add<M>(
+        &mut self,
+        node: &Node,
+        tree: &HalfSpaceTree<M>,
+        region: &mut [(f64, f64)],
+        acc: (f64, f64),
+        scale: f64,
+    ) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        let slot = self.node_ids.len();
+        self.tree_ids.push(self.tree_id);
+        self.node_ids.push(id);
+        self.feature_ids.push(node.split_dim as u64);
+        self.values.push(node.split_val as f32);
+        self.true_ids.push(0);
+        self.false_ids.push(0);
+        let w = 1.0 / ((tree.max_depth - node.depth) as f64 + 1.0);
+        let acc = (acc.0 + w * node.rarity(), acc.1 + w);
+        match (&node.left, &node.right) {
+            (Some(l), Some(r)) => {
+                assert!(
+                    node.oblique.is_none(),
+                    "oblique splits cannot be exported to ONNX"
+                );
+                self.modes.push("BRANCH_LT");
+                let saved = region[node.split_dim];
+                region[node.split_dim].1 = saved.1.min(node.split_val);
+                self.true_ids[slot] = self.add(l, tree, region, acc, scale);
+                region[node.split_dim] = (saved.0.max(node.split_val), saved.1);
+                self.false_ids[slot] = self.add(r, tree, region, acc, scale);
+                region[node.split_dim] = saved;
+            }
+            _ => {
+                self.modes.push("LEAF");
+                self.leaf_tree_ids.push(self.tree_id);
+                self.leaf_node_ids.push(id);
+                self.leaf_weights
+                    .push((scale * node.exported_leaf_score(tree, region, acc)) as f32);
+            }
+        }
+        id
+    }
+
+    fn attributes(&self) -> Vec<Proto> {
+        let n_leaves = self.leaf_node_ids.len();
+        vec![
+            ints_attr("nodes_treeids", &self.tree_ids),
+            ints_attr("nodes_nodeids", &self.node_ids),
+            ints_attr("nodes_featureids", &self.feature_ids),
+            floats_attr("nodes_values", &self.values),
+            strings_attr("nodes_modes", &self.modes),
+            ints_attr("nodes_truenodeids", &self.true_ids),
+            ints_attr("nodes_falsenodeids", &self.false_ids),
+            ints_attr("target_treeids", &self.leaf_tree_ids),
+            ints_attr("target_nodeids", &self.leaf_node_ids),
+            ints_attr("target_ids", &vec![0; n_leaves]),
+            floats_attr("target_weights", &self.leaf_weights),
+            int_attr("n_targets", 1),
+            string_attr("aggregate_function", "SUM"),
+            string_attr("post_transform", "NONE"),
+        ]
+    }
+}
+
+/// `ValueInfoProto` for a float tensor of shape `[N, width]`.
+fn tensor_value_info(name: &str, width: u64) -> Proto {
+    let mut batch = Proto::default();
+    batch.string(2, "N");
+    let mut cols = Proto::default();
+    cols.varint(1, width);
+    let mut shape = Proto::default();
+    shape.message(1, batch);
+    shape.message(1, cols);
+    let mut tensor = Proto::default();
+    tensor.varint(1, ELEM_FLOAT);
+    tensor.message(2, shape);
+    let mut ty = Proto::default();
+    ty.message(1, tensor);
+    let mut info = Proto::default();
+    info.string(1, name);
+    info.message(2, ty);
+    info
+}
+
+fn attr(name: &str, ty: u64) -> Proto {
+    let mut a = Proto::default();
+    a.string(1, name);
+    a.varint(20, ty);
+    a
+}
+
+fn int_attr(name: &str, v: u64) -> Proto {
+    let mut a = attr(name, ATTR_INT);
+    a.varint(3, v);
+    a
+}
+
+fn string_attr(name: &str, v: &str) -> Proto {
+    let mut a = attr(name, ATTR_STRING);
+    a.string(4, v);
+    a
+}
+
+fn ints_attr(name: &str, vs: &[u64]) -> Proto {
+    let mut a = attr(name, ATTR_INTS);
+    vs.iter().for_each(|&v| a.varint(8, v));
+    a
+}
+
+fn floats_attr(name: &str, vs: &[f32]) -> Proto {
+    let mut a = attr(name, ATTR_FLOATS);
+    vs.iter().for_each(|&v| a.float(7, v));
+    a
+}
+
+fn strings_attr(name: &str, vs: &[&str]) -> Proto {
+    let mut a = attr(name, ATTR_STRINGS);
+    vs.iter().for_each(|v| a.string(9, v));
+    a
+}
+
+/// A protobuf message under construction. Repeated scalars are written unpacked, as
+/// `onnx.proto` is proto2.
+#[derive(Default)]
+struct Proto(Vec<u8>);
+
+impl Proto {
+    fn raw_varint(&mut self, mut v: u64) {
+        while v >= 0x80 {
+            self.0.push(v as u8 | 0x80);
+            v >>= 7;
+        }
+        self.0.push(v as u8);
+    }
+
+    fn tag(&mut self, field: u64, wire_type: u64) {
+        self.raw_varint(field << 3 | wire_type);
+    }
+
+    fn varint(&mut self, field: u64, v: u64) {
+        self.tag(field, 0);
+        self.raw_varint(v);
+    }
+
+    fn float(&mut self, field: u64, v: f32) {
+        self.tag(field, 5);
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn bytes(&mut self, field: u64, v: &[u8]) {
+        self.tag(field, 2);
+        self.raw_varint(v.len() as u64);
+        self.0.extend_from_slice(v);
+    }
+
+    fn string(&mut self, field: u64, v: &str) {
+        self.bytes(field, v.as_bytes());
+    }
+
+    fn message(&mut self, field: u64, m: Proto) {
+        self.bytes(field, &m.0);
+    }
+}
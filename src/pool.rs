@@ -0,0 +1,113 @@
+//! Leaf statistics pooled across trees by region hash.
+
+use rand::Rng;
+
+use crate::fnv::Fnv1a;
+use crate::{FeatureVector, HalfSpaceTree, HalfSpaceTrees};
+
+/// Shared leaf masses: every tree's leaf maps to a bucket by a hash of its reconstructed
+/// region, and all trees read and write the bucket's mass instead of their own.
+#[derive(Debug, Clone)]
+pub(crate) struct MassPool {
+    pub(crate) buckets: Vec<f64>,
+}
+
+impl MassPool {
+    /// Region corners are hashed at `f32` precision, so buckets survive a round trip
+    /// through [`HalfSpaceTrees::to_flat_f32`], which rounds splits and bounds to `f32`.
+    fn bucket(&self, tree: &HalfSpaceTree, x: &FeatureVector) -> usize {
+        self.region_bucket(&tree.leaf_region(x))
+    }
+
+    fn region_bucket(&self, region: &[(f64, f64)]) -> usize {
+        let mut h = Fnv1a::new();
+        for &(lo, hi) in region {
+            h.write_u64((lo as f32).to_bits() as u64);
+            h.write_u64((hi as f32).to_bits() as u64);
+        }
+        (h.finish() % self.buckets.len() as u64) as usize
+    }
+
+    pub(crate) fn add(&mut self, trees: &[HalfSpaceTree], x: &FeatureVector, weight: f64) {
+        for t in trees {
+            let b = self.bucket(t, x);
+            self.buckets[b] += weight;
+        }
+    }
+
+    /// Add `sign` times each of `tree`'s leaf masses to the bucket that leaf maps to. A
+    /// bucket always holds the summed mass of the leaves mapping to it, so a tree whose
+    /// masses change other than by insert or uniform decay is taken out with `-1`,
+    /// updated, and put back with `1`.
+    pub(crate) fn add_leaves(&mut self, tree: &HalfSpaceTree, sign: f64) {
+        tree.for_each_leaf_region(|leaf, region| {
+            let b = self.region_bucket(region);
+            self.buckets[b] += sign * leaf.mass;
+        });
+    }
+
+    /// Recompute every bucket from the trees' current leaf masses.
+    pub(crate) fn rebuild(&mut self, trees: &[HalfSpaceTree]) {
+        self.buckets.fill(0.0);
+        for t in trees {
+            self.add_leaves(t, 1.0);
+        }
+    }
+
+    pub(crate) fn decay(&mut self, alpha: f64) {
+        self.buckets.iter_mut().for_each(|m| *m *= alpha);
+    }
+
+    /// Rarity of the bucket `tree` sends `x` to: the `Linear` leaf score of a full-depth
+    /// leaf, without any depth factor.
+    pub(crate) fn score(&self, tree: &HalfSpaceTree, x: &FeatureVector) -> f64 {
+        1.0 / (1.0 + self.buckets[self.bucket(tree, x)].max(0.0))
+    }
+}
+
+impl HalfSpaceTrees {
+    /// A forest whose trees have independent random geometry but pool their leaf masses
+    /// in `n_buckets` shared buckets, keyed by a hash of each leaf's reconstructed region.
+    /// Every insert adds its weight once per tree to the bucket that tree's leaf maps to,
+    /// so regions that hash together accumulate evidence from all trees, and rare regions
+    /// fill up `n_trees` times faster than a single tree's leaf would. Fewer buckets mean
+    /// less memory and more sharing between unrelated regions.
+    ///
+    /// [`HalfSpaceTrees::score`] reads each tree's leaf rarity `1 / (1 + mass)` from the
+    /// pool. Every method that changes tree masses or geometry keeps the pool up to date,
+    /// except `process_fused`, which panics on a pooled forest as its decay touches only
+    /// one path. Trees still track their own masses too, which every other query reads.
+    /// The pool is
+    /// part of the [`crate::StreamingState`], so a forest reloaded from
+    /// [`HalfSpaceTrees::to_flat_f32`] regains it on [`HalfSpaceTrees::import_state`].
+    pub fn new_pooled_mass<R: Rng + ?Sized>(
+        n_trees: usize,
+        max_depth: u32,
+        bounds: &[(f64, f64)],
+        n_buckets: usize,
+        rng: &mut R,
+    ) -> Self {
+        assert!(n_buckets > 0, "n_buckets must be positive");
+        let mut forest = Self::new(n_trees, max_depth, bounds, rng);
+        forest.pool = Some(MassPool {
+            buckets: vec![0.0; n_buckets],
+        });
+        forest
+    }
+
+    /// Per tree, the pooled bucket `x`'s leaf maps to and that bucket's mass, or `None`
+    /// unless built with [`HalfSpaceTrees::new_pooled_mass`].
+    pub fn pooled_leaf_masses(&self, x: &FeatureVector) -> Option<Vec<(usize, f64)>> {
+        assert_eq!(x.len(), self.n_dims());
+        let pool = self.pool.as_ref()?;
+        Some(
+            self.trees
+                .iter()
+                .map(|t| {
+                    let b = pool.bucket(t, x);
+                    (b, pool.buckets[b])
+                })
+                .collect(),
+        )
+    }
+}
@@ -0,0 +1,128 @@
+//! Random projection front end for high-dimensional inputs.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::{FeatureVector, HalfSpaceTrees, standard_normal};
+
+/// A fixed random linear map from `in_dims` to `out_dims` dimensions.
+///
+/// Entries are scaled by `1 / sqrt(out_dims)` so squared distances are preserved in
+/// expectation (Johnson-Lindenstrauss). The same seed always yields the same matrix.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RandomProjection {
+    // Row-major `out_dims x in_dims`
+    matrix: Vec<Vec<f64>>,
+}
+
+impl RandomProjection {
+    /// Dense projection with independent standard normal entries.
+    pub fn gaussian(in_dims: usize, out_dims: usize, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        Self::build(in_dims, out_dims, || standard_normal(&mut rng))
+    }
+
+    /// Sparse projection (Achlioptas): entries are `±sqrt(3)` with probability 1/6 each and
+    /// 0 otherwise, so two thirds of the multiply-adds can be skipped.
+    pub fn sparse_sign(in_dims: usize, out_dims: usize, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        Self::build(in_dims, out_dims, || match rng.random_range(0..6) {
+            0 => 3f64.sqrt(),
+            1 => -(3f64.sqrt()),
+            _ => 0.0,
+        })
+    }
+
+    fn build(in_dims: usize, out_dims: usize, mut entry: impl FnMut() -> f64) -> Self {
+        assert!(in_dims > 0 && out_dims > 0, "dimensions must be positive");
+        let scale = 1.0 / (out_dims as f64).sqrt();
+        let matrix = (0..out_dims)
+            .map(|_| (0..in_dims).map(|_| scale * entry()).collect())
+            .collect();
+        Self { matrix }
+    }
+
+    pub fn in_dims(&self) -> usize {
+        self.matrix[0].len()
+    }
+
+    pub fn out_dims(&self) -> usize {
+        self.matrix.len()
+    }
+
+    /// The tightest per-output ranges containing the projection of every point inside
+    /// the input box `bounds`, suitable as the wrapped forest's bounds.
+    pub fn projected_bounds(&self, bounds: &[(f64, f64)]) -> Vec<(f64, f64)> {
+        assert_eq!(
+            bounds.len(),
+            self.in_dims(),
+            "input dimensionality mismatch"
+        );
+        self.matrix
+            .iter()
+            .map(|row| {
+                row.iter().zip(bounds).fold((0.0, 0.0), |(lo, hi), (w, b)| {
+                    let (a, c) = (w * b.0, w * b.1);
+                    (lo + a.min(c), hi + a.max(c))
+                })
+            })
+            .collect()
+    }
+
+    pub fn project(&self, x: &FeatureVector) -> Vec<f64> {
+        assert_eq!(x.len(), self.in_dims(), "input dimensionality mismatch");
+        self.matrix
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .zip(x)
+                    .filter(|(w, _)| **w != 0.0)
+                    .map(|(w, v)| w * v)
+                    .sum()
+            })
+            .collect()
+    }
+}
+
+/// A forest over randomly projected inputs: `insert` and `score` take points in the
+/// original space and project them first.
+///
+/// The forest's bounds live in the projected space; see
+/// [`RandomProjection::projected_bounds`] to derive them from the input bounds.
+#[derive(Debug)]
+pub struct ProjectedForest {
+    projection: RandomProjection,
+    forest: HalfSpaceTrees,
+}
+
+impl ProjectedForest {
+    /// Wrap a forest built over `projection.out_dims()` dimensions.
+    pub fn new(projection: RandomProjection, forest: HalfSpaceTrees) -> Self {
+        assert_eq!(
+            forest.n_dims(),
+            projection.out_dims(),
+            "forest must be built over the projected dimensions"
+        );
+        Self { projection, forest }
+    }
+
+    pub fn insert(&mut self, x: &FeatureVector) {
+        self.forest.insert(&self.projection.project(x));
+    }
+
+    pub fn score(&self, x: &FeatureVector) -> f64 {
+        self.forest.score(&self.projection.project(x))
+    }
+
+    pub fn projection(&self) -> &RandomProjection {
+        &self.projection
+    }
+
+    pub fn forest(&self) -> &HalfSpaceTrees {
+        &self.forest
+    }
+
+    pub fn into_inner(self) -> HalfSpaceTrees {
+        self.forest
+    }
+}
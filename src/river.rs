@@ -0,0 +1,110 @@
+//! Scoring compatible with river's `anomaly.HalfSpaceTrees`, for migration checks.
+
+use rand::Rng;
+
+use crate::{FeatureVector, HalfSpaceTree};
+
+/// Half-space trees with river's window mechanics and score.
+///
+/// Mirrors river's algorithm:
+///   * trees of height `height` whose splits pick a uniform random feature and a threshold
+///     uniform in the middle 70% (`padding = 0.15`) of the node's region on it;
+///   * each `insert` adds 1 to the *latest* mass along every tree path; after every
+///     `window_size` inserts the latest masses become the *reference* masses and reset;
+///   * `score` walks each tree, adding `reference_mass * 2^depth` per node and stopping
+///     after the first node whose reference mass is below `0.1 * window_size`; the sum is
+///     divided by `n_trees * window_size * (2^(height + 1) - 1)` and returned as `1 - sum`,
+///     so higher still means more anomalous. Before the first window completes it is 0.
+///
+/// River draws splits from Python's RNG, so trees are not bit-identical for the same
+/// seed; scores agree with river's wherever the tree geometry does, e.g. for points on
+/// opposite sides of every split.
+#[derive(Debug, Clone)]
+pub struct RiverCompat {
+    latest: Vec<HalfSpaceTree>,
+    reference: Vec<HalfSpaceTree>,
+    height: u32,
+    window_size: usize,
+    // Inserts since the last window swap
+    counter: usize,
+    first_window: bool,
+}
+
+impl RiverCompat {
+    /// River's defaults are `n_trees = 10`, `height = 8`, `window_size = 250`, with
+    /// features scaled to `[0, 1]`.
+    pub fn new<R: Rng + ?Sized>(
+        n_trees: usize,
+        height: u32,
+        window_size: usize,
+        bounds: &[(f64, f64)],
+        rng: &mut R,
+    ) -> Self {
+        assert!(window_size > 0, "window_size must be positive");
+        let latest: Vec<HalfSpaceTree> = (0..n_trees)
+            .map(|_| HalfSpaceTree::padded(height, bounds, 0.15, rng))
+            .collect();
+        Self {
+            reference: latest.clone(),
+            latest,
+            height,
+            window_size,
+            counter: 0,
+            first_window: true,
+        }
+    }
+
+    pub fn insert(&mut self, x: &FeatureVector) {
+        for t in &mut self.latest {
+            t.insert(x);
+        }
+        self.counter += 1;
+        if self.counter == self.window_size {
+            for (latest, reference) in self.latest.iter_mut().zip(&mut self.reference) {
+                reference.clone_from(latest);
+                latest.decay(0.0);
+            }
+            self.first_window = false;
+            self.counter = 0;
+        }
+    }
+
+    pub fn score(&self, x: &FeatureVector) -> f64 {
+        self.score_blended_window(x, 0.0)
+    }
+
+    /// Like [`RiverCompat::score`], but each node's mass crossfades from the reference
+    /// window toward the partially filled latest window: with `f` the fraction of the
+    /// current window seen so far, the mass used is
+    /// `(1 - latest_weight * f) * reference + latest_weight * latest`.
+    /// `latest_weight = 0` is the plain score; at `latest_weight = 1` the blend reaches the
+    /// finished window exactly as it becomes the reference, so the score no longer jumps
+    /// at the swap. Small steps remain where a blended mass crosses the `0.1 * window_size`
+    /// cut-off and the walk reaches one node deeper.
+    pub fn score_blended_window(&self, x: &FeatureVector, latest_weight: f64) -> f64 {
+        assert!(
+            (0.0..=1.0).contains(&latest_weight),
+            "latest_weight must be in [0,1]"
+        );
+        if self.first_window || self.reference.is_empty() {
+            return 0.0;
+        }
+        let f = self.counter as f64 / self.window_size as f64;
+        let size_limit = 0.1 * self.window_size as f64;
+        let mut score = 0.0;
+        for (reference, latest) in self.reference.iter().zip(&self.latest) {
+            assert_eq!(x.len(), reference.n_dims);
+            for (r, l) in reference.root.path(x).into_iter().zip(latest.root.path(x)) {
+                let mass = (1.0 - latest_weight * f) * r.mass + latest_weight * l.mass;
+                score += mass * 2f64.powi(r.depth as i32);
+                if mass < size_limit {
+                    break;
+                }
+            }
+        }
+        let max_score = self.reference.len() as f64
+            * self.window_size as f64
+            * (2f64.powi(self.height as i32 + 1) - 1.0);
+        1.0 - score / max_score
+    }
+}
@@ -0,0 +1,52 @@
+//! Count-Min sketch over quantized feature vectors.
+
+use crate::FeatureVector;
+use crate::fnv::Fnv1a;
+
+/// Approximate per-key counts in `depth` rows of `width` counters.
+///
+/// Estimates never undercount; with `n` total updates each overcounts by at most
+/// `e * n / width` with probability `1 - exp(-depth)`.
+#[derive(Debug, Clone)]
+pub(crate) struct CountMin {
+    pub(crate) counts: Vec<Vec<u64>>,
+    // Cell size used to quantize each feature before hashing
+    pub(crate) resolution: f64,
+}
+
+impl CountMin {
+    pub(crate) fn new(width: usize, depth: usize, resolution: f64) -> Self {
+        assert!(
+            width > 0 && depth > 0,
+            "sketch width and depth must be positive"
+        );
+        assert!(resolution > 0.0, "resolution must be positive");
+        Self {
+            counts: vec![vec![0; width]; depth],
+            resolution,
+        }
+    }
+
+    pub(crate) fn add(&mut self, x: &FeatureVector) {
+        for row in 0..self.counts.len() {
+            let col = self.column(row, x);
+            self.counts[row][col] += 1;
+        }
+    }
+
+    pub(crate) fn estimate(&self, x: &FeatureVector) -> u64 {
+        (0..self.counts.len())
+            .map(|row| self.counts[row][self.column(row, x)])
+            .min()
+            .unwrap_or(0)
+    }
+
+    fn column(&self, row: usize, x: &FeatureVector) -> usize {
+        let mut h = Fnv1a::new();
+        h.write_u64(row as u64);
+        for &v in x {
+            h.write_u64((v / self.resolution).floor() as i64 as u64);
+        }
+        (h.finish() % self.counts[row].len() as u64) as usize
+    }
+}
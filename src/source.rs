@@ -0,0 +1,131 @@
+//! Per-source breakdown of leaf masses.
+
+use std::collections::HashMap;
+
+use crate::{FeatureVector, HalfSpaceTrees};
+
+/// Mass each tracked source contributed, per tree and leaf. The forest keeps `leaves`
+/// aligned with its trees as they are added, evicted or redrawn.
+#[derive(Debug, Clone)]
+pub(crate) struct SourceTracker {
+    pub(crate) max_sources: usize,
+    // Tracked source ids, in order of first appearance
+    pub(crate) ids: Vec<u16>,
+    // Per tree: leaf index -> mass per tracked source (aligned with `ids`)
+    pub(crate) leaves: Vec<HashMap<usize, Vec<f64>>>,
+}
+
+impl HalfSpaceTrees {
+    /// Source id reported by [`HalfSpaceTrees::source_mass`] for mass from untagged
+    /// inserts and from sources beyond the tracking cap.
+    pub const UNTRACKED_SOURCE: u16 = u16::MAX;
+
+    /// Start attributing leaf mass to the source ids passed to
+    /// [`HalfSpaceTrees::insert_tagged`]. At most `max_sources` distinct ids are tracked,
+    /// first come first served, which bounds memory at `max_sources` floats per touched
+    /// leaf; later ids count as [`HalfSpaceTrees::UNTRACKED_SOURCE`]. Mass inserted before
+    /// enabling is untracked too.
+    pub fn enable_source_tracking(&mut self, max_sources: usize) {
+        assert!(max_sources > 0, "max_sources must be positive");
+        self.sources = Some(SourceTracker {
+            max_sources,
+            ids: Vec::new(),
+            leaves: vec![HashMap::new(); self.trees.len()],
+        });
+    }
+
+    /// [`HalfSpaceTrees::insert`] `x` on behalf of `source`.
+    ///
+    /// # Panics
+    /// If source tracking is not enabled, or `source` is
+    /// [`HalfSpaceTrees::UNTRACKED_SOURCE`].
+    pub fn insert_tagged(&mut self, x: &FeatureVector, source: u16) {
+        assert_ne!(source, Self::UNTRACKED_SOURCE, "source id is reserved");
+        let weight = self.weight_schedule.weight(self.inserts);
+        let tracker = self.sources.as_mut().expect("source tracking not enabled");
+        let slot = match tracker.ids.iter().position(|&id| id == source) {
+            Some(slot) => Some(slot),
+            None if tracker.ids.len() < tracker.max_sources => {
+                tracker.ids.push(source);
+                Some(tracker.ids.len() - 1)
+            }
+            None => None,
+        };
+        if let Some(slot) = slot {
+            let n = tracker.ids.len();
+            for (t, leaves) in self.trees.iter().zip(&mut tracker.leaves) {
+                let masses = leaves.entry(t.root.leaf_index(x)).or_default();
+                masses.resize(n, 0.0);
+                masses[slot] += weight;
+            }
+        }
+        self.insert(x);
+    }
+
+    /// Mass of the leaves `x` reaches broken down by source, averaged over trees: every
+    /// tracked source with mass there, plus [`HalfSpaceTrees::UNTRACKED_SOURCE`] for the
+    /// rest of the leaf mass. The entries sum to the trees' mean leaf mass at `x`.
+    /// Only `decay` and the path decay of `process_fused` are mirrored in the breakdown;
+    /// other ways of shrinking mass (`remove`, `amortized_decay_tick`) leave tracked
+    /// sources high and may push the untracked share to 0. Trees added, or redrawn by `rebuild` and
+    /// `ensure_diversity`, start with all their mass untracked. Empty unless source
+    /// tracking is enabled.
+    pub fn source_mass(&self, x: &FeatureVector) -> Vec<(u16, f64)> {
+        assert_eq!(x.len(), self.n_dims());
+        let Some(tracker) = &self.sources else {
+            return Vec::new();
+        };
+        let n_trees = self.trees.len().max(1) as f64;
+        let mut tracked = vec![0.0; tracker.ids.len()];
+        let mut untracked = 0.0;
+        for (t, leaves) in self.trees.iter().zip(&tracker.leaves) {
+            let leaf = t.root.leaf(x);
+            let masses = leaves.get(&t.root.leaf_index(x));
+            let mut attributed = 0.0;
+            for (sum, m) in tracked.iter_mut().zip(masses.into_iter().flatten()) {
+                *sum += m;
+                attributed += m;
+            }
+            untracked += (leaf.mass - attributed).max(0.0);
+        }
+        let mut out: Vec<(u16, f64)> = tracker
+            .ids
+            .iter()
+            .zip(tracked)
+            .filter(|&(_, m)| m > 0.0)
+            .map(|(&id, m)| (id, m / n_trees))
+            .collect();
+        if untracked > 0.0 {
+            out.push((Self::UNTRACKED_SOURCE, untracked / n_trees));
+        }
+        out
+    }
+}
+
+impl SourceTracker {
+    pub(crate) fn tree_added(&mut self) {
+        self.leaves.push(HashMap::new());
+    }
+
+    pub(crate) fn tree_removed(&mut self, i: usize) {
+        self.leaves.remove(i);
+    }
+
+    /// Tree `i` has new geometry, so its leaves no longer match the recorded ones.
+    pub(crate) fn tree_redrawn(&mut self, i: usize) {
+        self.leaves[i].clear();
+    }
+
+    /// Decay only the masses recorded for leaf `leaf` of tree `tree`.
+    pub(crate) fn decay_leaf(&mut self, tree: usize, leaf: usize, alpha: f64) {
+        if let Some(masses) = self.leaves[tree].get_mut(&leaf) {
+            masses.iter_mut().for_each(|m| *m *= alpha);
+        }
+    }
+
+    pub(crate) fn decay(&mut self, alpha: f64) {
+        for masses in self.leaves.iter_mut().flat_map(|l| l.values_mut()) {
+            masses.iter_mut().for_each(|m| *m *= alpha);
+        }
+    }
+}
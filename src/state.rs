@@ -0,0 +1,420 @@
+//! Export and reload of a forest's streaming state.
+
+use crate::calibrate::Isotonic;
+use crate::pool::MassPool;
+use crate::sketch::CountMin;
+use crate::source::SourceTracker;
+use crate::{DriftStats, Ewm, HalfSpaceTrees, HstError, Result, WeightSchedule};
+
+/// Everything a forest accumulates while streaming, apart from the trees themselves:
+/// the tree weights, the smoothed score, the recent scores ranked by
+/// [`HalfSpaceTrees::combined_severity`], per-dimension drift moments, the last insert's
+/// per-tree impact, event decay settings, score rounding, the surprise floor, whether
+/// scores pool leaf masses, the frequency sketch, the fitted calibration, the amortized
+/// decay position, the insert weight schedule with its count, any pooled leaf masses and
+/// any per-source masses.
+///
+/// Save it next to the trees (e.g. via [`HalfSpaceTrees::to_flat_f32`]) to resume a
+/// stream after a restart. [`StreamingState::to_bytes`] writes a sequence of
+/// little-endian 8-byte words, starting with a magic word that carries the format
+/// version; [`StreamingState::from_bytes`] reads it back and rejects other versions.
+#[derive(Debug, Clone)]
+pub struct StreamingState {
+    weights: Vec<f64>,
+    smoothed: Option<f64>,
+    severity_window: Vec<f64>,
+    drift: Vec<DriftStats>,
+    last_impact: Vec<f64>,
+    event_decay: (f64, f64),
+    score_quantum: Option<f64>,
+    surprise_floor: f64,
+    pooled_mass: bool,
+    sketch: Option<CountMin>,
+    calibration: Option<Isotonic>,
+    decay_cursor: usize,
+    weight_schedule: WeightSchedule,
+    inserts: u64,
+    pool: Option<MassPool>,
+    sources: Option<SourceTracker>,
+}
+
+// "HSTSTAT" followed by the format version digit in the low byte
+const MAGIC: u64 = 0x4853_5453_5441_5400;
+const VERSION: u8 = b'1';
+
+impl StreamingState {
+    /// Number of input dimensions the drift moments cover.
+    pub fn n_dims(&self) -> usize {
+        self.drift.len()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.u64(MAGIC | VERSION as u64);
+        w.f64s(&self.weights);
+        w.opt(self.smoothed);
+        w.f64s(&self.severity_window);
+        w.u64(self.drift.len() as u64);
+        for d in &self.drift {
+            for v in [d.recent.mean, d.recent.var, d.baseline.mean, d.baseline.var] {
+                w.f64(v);
+            }
+            w.u64(d.seen as u64);
+        }
+        w.f64s(&self.last_impact);
+        w.f64(self.event_decay.0);
+        w.f64(self.event_decay.1);
+        w.opt(self.score_quantum);
+        w.f64(self.surprise_floor);
+        w.u64(self.pooled_mass as u64);
+        match &self.sketch {
+            None => w.u64(0),
+            Some(s) => {
+                w.u64(1);
+                w.f64(s.resolution);
+                w.u64(s.counts.len() as u64);
+                w.u64(s.counts[0].len() as u64);
+                s.counts.iter().flatten().for_each(|&c| w.u64(c));
+            }
+        }
+        match &self.calibration {
+            None => w.u64(0),
+            Some(c) => {
+                w.u64(1);
+                w.f64s(&c.starts);
+                w.f64s(&c.values);
+            }
+        }
+        w.u64(self.decay_cursor as u64);
+        let (kind, scale) = match self.weight_schedule {
+            WeightSchedule::Constant => (0, 0.0),
+            WeightSchedule::Decreasing { scale } => (1, scale),
+            WeightSchedule::Increasing { scale } => (2, scale),
+        };
+        w.u64(kind);
+        w.f64(scale);
+        w.u64(self.inserts);
+        match &self.pool {
+            None => w.u64(0),
+            Some(p) => {
+                w.u64(1);
+                w.f64s(&p.buckets);
+            }
+        }
+        match &self.sources {
+            None => w.u64(0),
+            Some(s) => {
+                w.u64(1);
+                w.u64(s.max_sources as u64);
+                w.u64(s.ids.len() as u64);
+                s.ids.iter().for_each(|&id| w.u64(id as u64));
+                w.u64(s.leaves.len() as u64);
+                for leaves in &s.leaves {
+                    let mut keys: Vec<&usize> = leaves.keys().collect();
+                    keys.sort();
+                    w.u64(keys.len() as u64);
+                    for k in keys {
+                        w.u64(*k as u64);
+                        w.f64s(&leaves[k]);
+                    }
+                }
+            }
+        }
+        w.0
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut r = Reader { bytes, pos: 0 };
+        let magic = r.u64()?;
+        if magic & !0xff != MAGIC {
+            return Err(invalid("not a streaming state"));
+        }
+        let version = (magic & 0xff) as u8;
+        if version != VERSION {
+            return Err(invalid(format!(
+                "unsupported format version {}, expected {}",
+                version as char, VERSION as char
+            )));
+        }
+        let weights = r.f64s()?;
+        if weights.iter().any(|w| !w.is_finite() || *w < 0.0) {
+            return Err(invalid("tree weights must be finite and non-negative"));
+        }
+        let smoothed = r.opt()?;
+        let severity_window = r.f64s()?;
+        if severity_window.len() > HalfSpaceTrees::SEVERITY_WINDOW {
+            return Err(invalid("severity window longer than SEVERITY_WINDOW"));
+        }
+        let n_dims = r.len()?;
+        let drift = (0..n_dims)
+            .map(|_| {
+                Ok(DriftStats {
+                    recent: Ewm {
+                        mean: r.f64()?,
+                        var: r.f64()?,
+                    },
+                    baseline: Ewm {
+                        mean: r.f64()?,
+                        var: r.f64()?,
+                    },
+                    seen: r.flag()?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        let last_impact = r.f64s()?;
+        let event_decay = (r.f64()?, r.f64()?);
+        let score_quantum = r.opt()?;
+        let surprise_floor = r.f64()?;
+        if surprise_floor.is_nan() || surprise_floor <= 0.0 {
+            return Err(invalid("surprise floor must be positive"));
+        }
+        let pooled_mass = r.flag()?;
+        let sketch = if r.flag()? {
+            let resolution = r.f64()?;
+            let (depth, width) = (r.len()?, r.len()?);
+            if depth == 0 || width == 0 || resolution.is_nan() || resolution <= 0.0 {
+                return Err(invalid("degenerate frequency sketch"));
+            }
+            let counts = (0..depth)
+                .map(|_| (0..width).map(|_| r.u64()).collect())
+                .collect::<Result<_>>()?;
+            Some(CountMin { counts, resolution })
+        } else {
+            None
+        };
+        let calibration = if r.flag()? {
+            let (starts, values) = (r.f64s()?, r.f64s()?);
+            if starts.is_empty() || starts.len() != values.len() {
+                return Err(invalid("calibration blocks and values differ in length"));
+            }
+            Some(Isotonic { starts, values })
+        } else {
+            None
+        };
+        let decay_cursor = r.u64()? as usize;
+        let (kind, scale) = (r.u64()?, r.f64()?);
+        let weight_schedule = match kind {
+            0 => WeightSchedule::Constant,
+            1 | 2 if scale.is_nan() || scale <= 0.0 => {
+                return Err(invalid("weight schedule scale must be positive"));
+            }
+            1 => WeightSchedule::Decreasing { scale },
+            2 => WeightSchedule::Increasing { scale },
+            k => return Err(invalid(format!("unknown weight schedule {k}"))),
+        };
+        let inserts = r.u64()?;
+        let pool = if r.flag()? {
+            let buckets = r.f64s()?;
+            if buckets.is_empty() {
+                return Err(invalid("mass pool has no buckets"));
+            }
+            Some(MassPool { buckets })
+        } else {
+            None
+        };
+        let sources = if r.flag()? {
+            let max_sources = r.u64()? as usize;
+            let ids = (0..r.len()?)
+                .map(|_| match r.u64()? {
+                    id if id < HalfSpaceTrees::UNTRACKED_SOURCE as u64 => Ok(id as u16),
+                    id => Err(invalid(format!("invalid source id {id}"))),
+                })
+                .collect::<Result<Vec<u16>>>()?;
+            if max_sources == 0 || ids.len() > max_sources {
+                return Err(invalid("more tracked sources than the cap"));
+            }
+            if (1..ids.len()).any(|i| ids[..i].contains(&ids[i])) {
+                return Err(invalid("duplicate source id"));
+            }
+            let leaves = (0..r.len()?)
+                .map(|_| {
+                    (0..r.len()?)
+                        .map(|_| {
+                            let (key, masses) = (r.u64()? as usize, r.f64s()?);
+                            if masses.len() > ids.len() {
+                                return Err(invalid("leaf has masses for untracked sources"));
+                            }
+                            Ok((key, masses))
+                        })
+                        .collect()
+                })
+                .collect::<Result<_>>()?;
+            Some(SourceTracker {
+                max_sources,
+                ids,
+                leaves,
+            })
+        } else {
+            None
+        };
+        if r.pos != bytes.len() {
+            return Err(invalid(format!("{} trailing bytes", bytes.len() - r.pos)));
+        }
+        Ok(Self {
+            weights,
+            smoothed,
+            severity_window,
+            drift,
+            last_impact,
+            event_decay,
+            score_quantum,
+            surprise_floor,
+            pooled_mass,
+            sketch,
+            calibration,
+            decay_cursor,
+            weight_schedule,
+            inserts,
+            pool,
+            sources,
+        })
+    }
+}
+
+impl HalfSpaceTrees {
+    /// Snapshot the streaming state; see [`StreamingState`].
+    pub fn export_state(&self) -> StreamingState {
+        StreamingState {
+            weights: self.weights.clone(),
+            smoothed: self.smoothed,
+            severity_window: self.severity_window.iter().copied().collect(),
+            drift: self.drift.clone(),
+            last_impact: self.last_impact.clone(),
+            event_decay: self.event_decay,
+            score_quantum: self.score_quantum,
+            surprise_floor: self.surprise_floor,
+            pooled_mass: self.pooled_mass,
+            sketch: self.sketch.clone(),
+            calibration: self.calibration.clone(),
+            decay_cursor: self.decay_cursor,
+            weight_schedule: self.weight_schedule,
+            inserts: self.inserts,
+            pool: self.pool.clone(),
+            sources: self.sources.clone(),
+        }
+    }
+
+    /// Replace the streaming state with `state`, so processing resumes exactly where
+    /// the exporting forest left off (given the same trees).
+    /// Fails if `state` covers a different number of dimensions or trees.
+    pub fn import_state(&mut self, state: StreamingState) -> Result<()> {
+        if state.n_dims() != self.n_dims() {
+            return Err(invalid(format!(
+                "state covers {} dims, forest has {}",
+                state.n_dims(),
+                self.n_dims()
+            )));
+        }
+        if state.weights.len() != self.trees.len() {
+            return Err(invalid(format!(
+                "state covers {} trees, forest has {}",
+                state.weights.len(),
+                self.trees.len()
+            )));
+        }
+        if !state.last_impact.is_empty() && state.last_impact.len() != self.trees.len() {
+            return Err(invalid(
+                "last insert impact covers a different number of trees",
+            ));
+        }
+        if let Some(sources) = &state.sources
+            && sources.leaves.len() != self.trees.len()
+        {
+            return Err(invalid("source masses cover a different number of trees"));
+        }
+        self.weights = state.weights;
+        self.smoothed = state.smoothed;
+        self.severity_window = state.severity_window.into();
+        self.drift = state.drift;
+        self.last_impact = state.last_impact;
+        self.event_decay = state.event_decay;
+        self.score_quantum = state.score_quantum;
+        self.surprise_floor = state.surprise_floor;
+        self.pooled_mass = state.pooled_mass;
+        self.sketch = state.sketch;
+        self.calibration = state.calibration;
+        self.decay_cursor = state.decay_cursor;
+        self.weight_schedule = state.weight_schedule;
+        self.inserts = state.inserts;
+        self.pool = state.pool;
+        self.sources = state.sources;
+        Ok(())
+    }
+}
+
+fn invalid(msg: impl Into<String>) -> HstError {
+    HstError::InvalidState(msg.into())
+}
+
+#[derive(Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u64(&mut self, v: u64) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn f64(&mut self, v: f64) {
+        self.u64(v.to_bits());
+    }
+
+    fn opt(&mut self, v: Option<f64>) {
+        self.u64(v.is_some() as u64);
+        self.f64(v.unwrap_or(0.0));
+    }
+
+    fn f64s(&mut self, vs: &[f64]) {
+        self.u64(vs.len() as u64);
+        vs.iter().for_each(|&v| self.f64(v));
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn u64(&mut self) -> Result<u64> {
+        let word = self
+            .bytes
+            .get(self.pos..self.pos + 8)
+            .ok_or_else(|| invalid("truncated"))?;
+        self.pos += 8;
+        Ok(u64::from_le_bytes(word.try_into().unwrap()))
+    }
+
+    fn f64(&mut self) -> Result<f64> {
+        self.u64().map(f64::from_bits)
+    }
+
+    fn flag(&mut self) -> Result<bool> {
+        match self.u64()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            v => Err(invalid(format!("expected a flag, found {v}"))),
+        }
+    }
+
+    fn opt(&mut self) -> Result<Option<f64>> {
+        let present = self.flag()?;
+        let v = self.f64()?;
+        Ok(present.then_some(v))
+    }
+
+    /// A length prefix, checked against the bytes left so corrupt input cannot
+    /// trigger a huge allocation.
+    fn len(&mut self) -> Result<usize> {
+        let n = self.u64()?;
+        let left = (self.bytes.len() - self.pos) as u64 / 8;
+        if n > left {
+            return Err(invalid(format!("length {n} exceeds remaining data")));
+        }
+        Ok(n as usize)
+    }
+
+    fn f64s(&mut self) -> Result<Vec<f64>> {
+        let n = self.len()?;
+        (0..n).map(|_| self.f64()).collect()
+    }
+}
@@ -0,0 +1,147 @@
+//! Step-by-step record of how a score is computed.
+
+use crate::{DepthWeighting, FeatureVector, HalfSpaceTree, HalfSpaceTrees};
+
+/// Everything [`HalfSpaceTrees::score`] computed for one point, from
+/// [`HalfSpaceTrees::trace_score`]. Replaying the arithmetic gives the score bit for bit:
+/// each tree's `score` is `rarity * depth_factor * spread_factor` (in that order), the
+/// trees combine as described by `aggregation` into `raw`, and `score` is `raw` rounded
+/// to `quantum` if set: `(raw / q).round() * q`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoreTrace {
+    pub trees: Vec<TreeTrace>,
+    pub aggregation: ScoreAggregation,
+    pub raw: f64,
+    pub quantum: Option<f64>,
+    pub score: f64,
+}
+
+/// How per-tree results combine into the forest score.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScoreAggregation {
+    /// `sum(weight * score) / sum(weight)` over trees, accumulated in tree order.
+    WeightedMean,
+    /// [`crate::ScoreMethod::PooledMass`]: the `trimmed` lightest and heaviest leaf
+    /// masses were dropped and `raw = 1 / (1 + max(mean_mass, 0))`. Per-tree scores do
+    /// not enter.
+    TrimmedLeafMass { trimmed: usize, mean_mass: f64 },
+}
+
+/// One tree's part of a [`ScoreTrace`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeTrace {
+    /// Splits from the root down to the leaf.
+    pub path: Vec<SplitDecision>,
+    /// Mass the score read: the leaf's own, or its bucket's in a pooled-mass forest
+    /// (see [`HalfSpaceTrees::new_pooled_mass`]).
+    pub leaf_mass: f64,
+    pub leaf_depth: u32,
+    /// `1 / (1 + mass)` of the leaf under [`DepthWeighting::Linear`]; under
+    /// [`DepthWeighting::Harmonic`], the depth-weighted mean of that along the path.
+    pub rarity: f64,
+    /// Multiplier for shallow leaves; 1 at full depth and for pooled-mass forests.
+    pub depth_factor: f64,
+    /// [`crate::ScoreMethod::VarianceAware`] multiplier, otherwise 1.
+    pub spread_factor: f64,
+    /// Weight of the tree in [`ScoreAggregation::WeightedMean`].
+    pub weight: f64,
+    pub score: f64,
+}
+
+/// One internal node's routing of the point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SplitDecision {
+    /// Split dimension; the first of the two for oblique splits.
+    pub dim: usize,
+    pub threshold: f64,
+    /// The coordinate compared against `threshold` (the projection for oblique splits).
+    pub value: f64,
+    /// Whether the point went left, i.e. `value < threshold`.
+    pub left: bool,
+}
+
+impl HalfSpaceTrees {
+    /// The full computation behind [`HalfSpaceTrees::score`] for `x`, as ground truth
+    /// for reproducing a score by hand or diffing two versions; see [`ScoreTrace`].
+    pub fn trace_score(&self, x: &FeatureVector) -> ScoreTrace {
+        assert_eq!(x.len(), self.n_dims());
+        let mut trees: Vec<TreeTrace> = self
+            .trees
+            .iter()
+            .zip(&self.weights)
+            .map(|(t, &w)| t.trace(x, w))
+            .collect();
+        if let (Some(pool), Some(masses)) = (&self.pool, self.pooled_leaf_masses(x)) {
+            for ((trace, t), (_, mass)) in trees.iter_mut().zip(&self.trees).zip(masses) {
+                trace.leaf_mass = mass;
+                trace.rarity = pool.score(t, x);
+                trace.depth_factor = 1.0;
+                trace.spread_factor = 1.0;
+                trace.score = trace.rarity;
+            }
+        }
+        let (aggregation, raw) = if self.pool.is_none() && self.pools_leaf_masses() {
+            let (trimmed, mean_mass) = self.trimmed_leaf_mass(x);
+            let aggregation = ScoreAggregation::TrimmedLeafMass { trimmed, mean_mass };
+            (aggregation, 1.0 / (1.0 + mean_mass.max(0.0)))
+        } else {
+            let (mut s, mut w) = (0.0, 0.0);
+            for t in &trees {
+                s += t.weight * t.score;
+                w += t.weight;
+            }
+            (ScoreAggregation::WeightedMean, s / w)
+        };
+        let score = match self.score_quantum {
+            Some(q) => (raw / q).round() * q,
+            None => raw,
+        };
+        ScoreTrace {
+            trees,
+            aggregation,
+            raw,
+            quantum: self.score_quantum,
+            score,
+        }
+    }
+}
+
+impl<M> HalfSpaceTree<M> {
+    fn trace(&self, x: &FeatureVector, weight: f64) -> TreeTrace {
+        let mut path = Vec::new();
+        let (mut num, mut den) = (0.0, 0.0);
+        let mut node = &self.root;
+        loop {
+            let w = 1.0 / ((self.max_depth - node.depth) as f64 + 1.0);
+            num += w * node.rarity();
+            den += w;
+            let (Some(l), Some(r)) = (&node.left, &node.right) else {
+                break;
+            };
+            let left = node.goes_left(x);
+            path.push(SplitDecision {
+                dim: node.split_dim,
+                threshold: node.split_val,
+                value: node.coord(x),
+                left,
+            });
+            node = if left { l } else { r };
+        }
+        let rarity = match self.weighting {
+            DepthWeighting::Linear => node.rarity(),
+            DepthWeighting::Harmonic => num / den,
+        };
+        let depth_factor = node.depth_factor(self.max_depth);
+        let spread_factor = self.spread_factor(x);
+        TreeTrace {
+            path,
+            leaf_mass: node.mass,
+            leaf_depth: node.depth,
+            rarity,
+            depth_factor,
+            spread_factor,
+            weight,
+            score: rarity * depth_factor * spread_factor,
+        }
+    }
+}
@@ -0,0 +1,56 @@
+//! Exact sliding-window wrapper around a forest.
+
+use std::collections::VecDeque;
+
+use crate::{FeatureVector, HalfSpaceTrees};
+
+/// A forest whose masses count exactly the last `capacity` pushed points.
+///
+/// Each push inserts the new point and removes the one falling out of the window,
+/// so no decay is needed (or allowed) to forget old data.
+#[derive(Debug)]
+pub struct WindowedForest {
+    forest: HalfSpaceTrees,
+    window: VecDeque<Vec<f64>>,
+    capacity: usize,
+}
+
+impl WindowedForest {
+    /// Wrap an untrained forest with a window of `capacity` points.
+    pub fn new(forest: HalfSpaceTrees, capacity: usize) -> Self {
+        assert!(capacity > 0, "capacity must be positive");
+        Self {
+            forest,
+            window: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Add `x` to the window, evicting the oldest point once the window is full.
+    pub fn push(&mut self, x: &FeatureVector) {
+        if self.window.len() == self.capacity
+            && let Some(old) = self.window.pop_front()
+        {
+            self.forest.remove(&old);
+        }
+        self.forest.insert(x);
+        self.window.push_back(x.to_vec());
+    }
+
+    pub fn score(&self, x: &FeatureVector) -> f64 {
+        self.forest.score(x)
+    }
+
+    /// Points currently in the window, oldest first.
+    pub fn window(&self) -> &VecDeque<Vec<f64>> {
+        &self.window
+    }
+
+    pub fn forest(&self) -> &HalfSpaceTrees {
+        &self.forest
+    }
+
+    pub fn into_inner(self) -> HalfSpaceTrees {
+        self.forest
+    }
+}
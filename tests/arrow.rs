@@ -0,0 +1,37 @@
+#![cfg(feature = "arrow")]
+
+use arrow_array::{Array, Float64Array, UInt32Array, UInt64Array};
+use half_space_trees::HalfSpaceTrees;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+#[test]
+fn record_batch_has_one_row_per_node() {
+    let bounds = vec![(0.0, 1.0); 3];
+    let mut forest = HalfSpaceTrees::new(4, 3, &bounds, &mut StdRng::seed_from_u64(1));
+    for i in 0..100 {
+        forest.insert(&[(i % 10) as f64 / 10.0, 0.5, 0.2]);
+    }
+    let batch = forest.to_record_batch();
+    assert_eq!(batch.num_rows(), 4 * 15);
+    assert_eq!(batch.num_columns(), 7);
+
+    let col = |name: &str| batch.column_by_name(name).unwrap().clone();
+    let tree = col("tree");
+    let tree = tree.as_any().downcast_ref::<UInt32Array>().unwrap();
+    let mass = col("mass");
+    let mass = mass.as_any().downcast_ref::<Float64Array>().unwrap();
+    let left = col("left");
+    let left = left.as_any().downcast_ref::<UInt64Array>().unwrap();
+    let right = col("right");
+    let right = right.as_any().downcast_ref::<UInt64Array>().unwrap();
+
+    for (i, t) in forest.trees().iter().enumerate() {
+        let root = i * 15;
+        assert_eq!(tree.value(root), i as u32);
+        assert_eq!(mass.value(root), t.total_mass());
+        let (l, r) = (left.value(root) as usize, right.value(root) as usize);
+        assert_eq!(mass.value(l) + mass.value(r), mass.value(root));
+    }
+    assert_eq!(left.null_count(), 4 * 8);
+}
@@ -0,0 +1,20 @@
+#![cfg(feature = "tokio")]
+
+use half_space_trees::HalfSpaceTrees;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+#[tokio::test]
+async fn async_batch_matches_sync_scores() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut forest = HalfSpaceTrees::new(10, 6, &bounds, &mut StdRng::seed_from_u64(1));
+    let mut rng = StdRng::seed_from_u64(2);
+    for _ in 0..500 {
+        forest.insert(&[0.5 * rng.random::<f64>(), rng.random()]);
+    }
+    let xs: Vec<Vec<f64>> = (0..32).map(|_| vec![rng.random(), rng.random()]).collect();
+    let expected: Vec<f64> = xs.iter().map(|x| forest.score(x)).collect();
+
+    let scores = forest.score_batch_async(xs).await;
+    assert_eq!(scores, expected);
+}
@@ -0,0 +1,141 @@
+use half_space_trees::eval::{
+    auc, auc_bootstrap_ci, best_mass_threshold, best_threshold_youden, decay_ablation,
+    pairwise_ranking_accuracy, roc_curve,
+};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+#[test]
+fn youden_threshold_separates_classes() {
+    let scores = [0.1, 0.35, 0.2, 0.9, 0.3, 0.8, 0.85];
+    let labels = [false, false, false, true, false, true, true];
+    let (t, j) = best_threshold_youden(&scores, &labels);
+    assert_eq!(j, 1.0);
+    assert_eq!(t, 0.8);
+    for (s, l) in scores.iter().zip(labels) {
+        assert_eq!(*s >= t, l);
+    }
+}
+
+#[test]
+fn youden_threshold_breaks_ties_deterministically() {
+    // Both 0.5 and 0.7 reach J = 0.5; the higher threshold wins.
+    let scores = [0.7, 0.5, 0.6, 0.1];
+    let labels = [true, true, false, false];
+    assert_eq!(best_threshold_youden(&scores, &labels), (0.7, 0.5));
+    assert_eq!(best_threshold_youden(&[0.3], &[true]), (f64::INFINITY, 0.0));
+}
+
+#[test]
+fn mass_threshold_splits_separable_groups() {
+    let inliers = [40.0, 55.0, 32.0, 70.0, 48.0];
+    let outliers = [0.0, 3.0, 1.5, 6.0];
+    let cut = best_mass_threshold(&inliers, &outliers);
+    assert_eq!(cut, 19.0);
+    assert!(outliers.iter().all(|&m| m < cut));
+    assert!(inliers.iter().all(|&m| m > cut));
+
+    // Overlap: flagging up to 12 catches 4 of 5 outliers at the cost of one inlier.
+    let (inliers, outliers) = ([8.0, 30.0, 40.0, 50.0], [2.0, 5.0, 10.0, 12.0, 35.0]);
+    assert_eq!(best_mass_threshold(&inliers, &outliers), 21.0);
+    assert_eq!(best_mass_threshold(&[], &[1.0]), f64::NEG_INFINITY);
+}
+
+#[test]
+fn auc_counts_ties_as_half() {
+    assert_eq!(auc(&[0.9, 0.1, 0.8, 0.2], &[true, false, true, false]), 1.0);
+    assert_eq!(auc(&[0.5, 0.5], &[true, false]), 0.5);
+    assert_eq!(auc(&[0.1, 0.9, 0.5], &[true, false, false]), 0.0);
+}
+
+#[test]
+fn auc_bootstrap_interval_brackets_point_and_narrows() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let sample = |n: usize, rng: &mut StdRng| -> (Vec<f64>, Vec<bool>) {
+        (0..n)
+            .map(|i| {
+                let anomaly = i % 4 == 0;
+                let shift = if anomaly { 0.3 } else { 0.0 };
+                (rng.random::<f64>() + shift, anomaly)
+            })
+            .unzip()
+    };
+    let (small_s, small_l) = sample(80, &mut rng);
+    let (big_s, big_l) = sample(2000, &mut rng);
+    let (lo, point, hi) = auc_bootstrap_ci(&small_s, &small_l, 500, &mut rng, 0.95);
+    assert_eq!(point, auc(&small_s, &small_l));
+    assert!(lo <= point && point <= hi);
+    let (big_lo, big_point, big_hi) = auc_bootstrap_ci(&big_s, &big_l, 500, &mut rng, 0.95);
+    assert!(big_lo <= big_point && big_point <= big_hi);
+    assert!(big_hi - big_lo < hi - lo);
+}
+
+#[test]
+fn pairwise_accuracy_reflects_overlap() {
+    let separated = pairwise_ranking_accuracy(&[0.9, 0.95, 0.8], &[0.1, 0.2, 0.3, 0.25]);
+    assert_eq!(separated, 1.0);
+    let overlapping = pairwise_ranking_accuracy(&[0.5, 0.2, 0.7], &[0.4, 0.6, 0.1]);
+    assert!((overlapping - 6.0 / 9.0).abs() < 1e-12);
+    assert_eq!(pairwise_ranking_accuracy(&[], &[0.3]), 0.5);
+}
+
+#[test]
+fn roc_curve_groups_ties_and_spans_unit_square() {
+    let scores = [0.9, 0.8, 0.8, 0.6, 0.4, 0.4, 0.2];
+    let labels = [true, true, false, false, true, false, false];
+    let curve = roc_curve(&scores, &labels);
+    assert_eq!(
+        curve,
+        vec![
+            (0.0, 0.0),
+            (0.0, 1.0 / 3.0),
+            (0.25, 2.0 / 3.0),
+            (0.5, 2.0 / 3.0),
+            (0.75, 1.0),
+            (1.0, 1.0),
+        ]
+    );
+    assert!(
+        curve
+            .windows(2)
+            .all(|w| w[0].0 <= w[1].0 && w[0].1 <= w[1].1)
+    );
+
+    // Trapezoids under the curve give back the AUC.
+    let area: f64 = curve
+        .windows(2)
+        .map(|w| (w[1].0 - w[0].0) * (w[0].1 + w[1].1) / 2.0)
+        .sum();
+    assert!((area - auc(&scores, &labels)).abs() < 1e-12);
+    assert_eq!(
+        roc_curve(&[0.3, 0.7], &[true, true]),
+        vec![(0.0, 0.0), (1.0, 1.0)]
+    );
+}
+
+#[test]
+fn decay_ablation_rewards_forgetting_under_drift() {
+    let mut rng = StdRng::seed_from_u64(4);
+    let mut stream = Vec::new();
+    let mut labels = Vec::new();
+    // The normal cluster jumps from 0.2 to 0.8; afterwards, points at the old spot are
+    // the anomalies.
+    for i in 0..4000 {
+        let drifted = i >= 1500;
+        let anomaly = drifted && i % 40 == 0;
+        let center = if drifted && !anomaly { 0.8 } else { 0.2 };
+        stream.push(vec![
+            center + rng.random_range(-0.05..0.05),
+            center + rng.random_range(-0.05..0.05),
+        ]);
+        labels.push(anomaly);
+    }
+    let results = decay_ablation(&stream, &labels, &[1.0, 0.999, 0.99], &mut rng);
+    assert_eq!(
+        results.iter().map(|r| r.0).collect::<Vec<_>>(),
+        vec![1.0, 0.999, 0.99]
+    );
+    let no_decay = results[0].1;
+    let best = results[1..].iter().map(|r| r.1).fold(0.0, f64::max);
+    assert!(best > no_decay + 0.1, "{results:?}");
+}
@@ -1,6 +1,10 @@
-use half_space_trees::HalfSpaceTrees;
-use rand::SeedableRng;
+use half_space_trees::{
+    DepthWeighting, DualTimescaleForest, ForestLayout, HalfSpaceTree, HalfSpaceTrees, HstError,
+    ProcessMode, ScoreAggregation, ScoreDistributionMonitor, ScoreMethod, ScoreTrace, SplitMode,
+    Transform, WeightSchedule, WindowedForest,
+};
 use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
 #[test]
 fn basic_separation() {
@@ -26,3 +30,2866 @@ fn basic_separation() {
         "outlier should have higher score: s_o={s_o}, s_n={s_n}"
     );
 }
+
+#[test]
+fn sparse_insert_matches_dense() {
+    let bounds = vec![(0.0, 1.0); 4];
+    let mut dense = HalfSpaceTrees::new(10, 6, &bounds, &mut StdRng::seed_from_u64(3));
+    let mut sparse = HalfSpaceTrees::new(10, 6, &bounds, &mut StdRng::seed_from_u64(3));
+
+    for i in 0..200 {
+        let v = (i % 10) as f64 / 10.0;
+        dense.insert(&[v, 0.0, 0.7, 0.0]);
+        sparse.insert_sparse([(2, 0.7), (0, v)]);
+    }
+
+    for probe in [[0.1, 0.0, 0.7, 0.0], [0.9, 0.0, 0.1, 0.0]] {
+        let sparse_probe = probe.iter().copied().enumerate().filter(|&(_, v)| v != 0.0);
+        assert_eq!(dense.score(&probe), sparse.score_sparse(sparse_probe));
+    }
+}
+
+fn variance(xs: &[f64]) -> f64 {
+    let mean = xs.iter().sum::<f64>() / xs.len() as f64;
+    xs.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / xs.len() as f64
+}
+
+#[test]
+fn smoothed_scores_are_less_noisy_but_track_shifts() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(11);
+    let mut forest = HalfSpaceTrees::new(20, 8, &bounds, &mut rng);
+    let mut noise = StdRng::seed_from_u64(12);
+
+    let (mut raw, mut smooth) = (Vec::new(), Vec::new());
+    for _ in 0..1000 {
+        let x = [
+            0.3 + 0.2 * noise.random::<f64>(),
+            0.3 + 0.2 * noise.random::<f64>(),
+        ];
+        let (r, s) = forest.process_smoothed(&x, 0.1);
+        raw.push(r);
+        smooth.push(s);
+    }
+    let tail = 500..;
+    assert!(variance(&smooth[tail.clone()]) < variance(&raw[tail]));
+
+    let before = *smooth.last().unwrap();
+    let mut after = before;
+    for _ in 0..30 {
+        after = forest.process_smoothed(&[0.95, 0.95], 0.1).1;
+    }
+    assert!(
+        after > before,
+        "smoothed score should follow a sustained shift"
+    );
+}
+
+#[test]
+fn occupancy_entropy_reflects_spread() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut spread = HalfSpaceTrees::new(10, 6, &bounds, &mut StdRng::seed_from_u64(5));
+    let mut point = HalfSpaceTrees::new(10, 6, &bounds, &mut StdRng::seed_from_u64(5));
+    assert_eq!(point.occupancy_entropy(), 0.0);
+
+    let mut rng = StdRng::seed_from_u64(6);
+    for _ in 0..2000 {
+        spread.insert(&[rng.random(), rng.random()]);
+        point.insert(&[0.5, 0.5]);
+    }
+    assert!(spread.occupancy_entropy() > point.occupancy_entropy());
+    assert!(point.occupancy_entropy().abs() < 1e-12);
+}
+
+#[test]
+fn decay_with_prior_keeps_a_floor() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut forest = HalfSpaceTrees::new(10, 6, &bounds, &mut StdRng::seed_from_u64(8));
+    for _ in 0..100 {
+        forest.insert(&[0.1, 0.1]);
+    }
+    let prior = 0.05;
+    for _ in 0..50 {
+        forest.decay_with_prior(0.5, prior);
+    }
+    for t in forest.trees() {
+        assert!(t.leaf_masses().iter().all(|&m| m >= prior));
+    }
+    // Leaves sit at max depth, so an empty one would score exactly 1.0.
+    let unseen = forest.score(&[0.99, 0.99]);
+    assert!(unseen <= 1.0 / (1.0 + prior) + 1e-12);
+}
+
+#[test]
+fn compatibility_requires_identical_geometry() {
+    let bounds = vec![(0.0, 1.0); 3];
+    let a = HalfSpaceTrees::new(5, 5, &bounds, &mut StdRng::seed_from_u64(1));
+    let mut b = HalfSpaceTrees::new(5, 5, &bounds, &mut StdRng::seed_from_u64(1));
+    let c = HalfSpaceTrees::new(5, 5, &bounds, &mut StdRng::seed_from_u64(2));
+    let d = HalfSpaceTrees::new(5, 4, &bounds, &mut StdRng::seed_from_u64(1));
+
+    b.insert(&[0.5, 0.5, 0.5]); // masses do not matter
+    assert!(a.is_compatible_with(&b));
+    assert!(!a.is_compatible_with(&c));
+    assert!(!a.is_compatible_with(&d));
+}
+
+#[test]
+fn worst_case_score_dominates_nominal() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut forest = HalfSpaceTrees::new(20, 6, &bounds, &mut StdRng::seed_from_u64(9));
+    let mut rng = StdRng::seed_from_u64(10);
+    for _ in 0..2000 {
+        forest.insert(&[0.5 * rng.random::<f64>(), 0.5 * rng.random::<f64>()]);
+    }
+    let x = [0.45, 0.45];
+    let nominal = forest.score(&x);
+    assert_eq!(forest.worst_case_score(&x, 0.0), nominal);
+    assert!(forest.worst_case_score(&x, 0.1) > nominal);
+}
+
+#[test]
+fn worst_case_score_bounds_perturbed_oblique_scores() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(146);
+    let mut forest = HalfSpaceTrees::with_split_mode(20, 6, &bounds, SplitMode::Oblique, &mut rng);
+    for _ in 0..2000 {
+        forest.insert(&[0.5 * rng.random::<f64>(), 0.5 * rng.random::<f64>()]);
+    }
+    let eps = 0.05;
+    for _ in 0..200 {
+        let x = [rng.random::<f64>(), rng.random::<f64>()];
+        let bound: Vec<f64> = forest
+            .trees()
+            .iter()
+            .map(|t| t.worst_case_score(&x, eps))
+            .collect();
+        // Corners of the perturbation box move `w·x` furthest.
+        for (dx, dy) in [(-eps, -eps), (-eps, eps), (eps, -eps), (eps, eps)] {
+            let y = [x[0] + dx, x[1] + dy];
+            for (t, &b) in forest.trees().iter().zip(&bound) {
+                assert!(
+                    t.score(&y) <= b,
+                    "{x:?} + ({dx}, {dy}): {} > {b}",
+                    t.score(&y)
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn learned_extent_is_tighter_than_bounds() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut forest = HalfSpaceTrees::new(25, 8, &bounds, &mut StdRng::seed_from_u64(13));
+    assert_eq!(forest.learned_extent(), bounds);
+
+    let mut rng = StdRng::seed_from_u64(14);
+    for _ in 0..500 {
+        forest.insert(&[
+            0.2 + 0.1 * rng.random::<f64>(),
+            0.6 + 0.1 * rng.random::<f64>(),
+        ]);
+    }
+    let extent = forest.learned_extent();
+    let (x, y) = (extent[0], extent[1]);
+    assert!(x.0 <= 0.2 && x.1 >= 0.3 && y.0 <= 0.6 && y.1 >= 0.7);
+    assert!(x.1 - x.0 < 1.0 && y.1 - y.0 < 1.0);
+}
+
+#[test]
+fn guarded_insert_rejects_outliers() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut forest = HalfSpaceTrees::new(20, 8, &bounds, &mut StdRng::seed_from_u64(15));
+    for _ in 0..1000 {
+        forest.insert(&[0.3, 0.3]);
+    }
+    let threshold = 0.5;
+
+    let outlier = [0.9, 0.9];
+    let before = forest.score(&outlier);
+    assert!(!forest.insert_guarded(&outlier, threshold));
+    assert_eq!(forest.score(&outlier), before);
+
+    let inlier = [0.3, 0.3];
+    let before = forest.score(&inlier);
+    assert!(forest.insert_guarded(&inlier, threshold));
+    assert!(forest.score(&inlier) < before);
+}
+
+#[test]
+fn dimension_drift_flags_the_shifted_dimension() {
+    let bounds = vec![(0.0, 1.0); 3];
+    let mut forest = HalfSpaceTrees::new(5, 4, &bounds, &mut StdRng::seed_from_u64(16));
+    let mut rng = StdRng::seed_from_u64(17);
+    let mut noisy = |c: f64| c + 0.05 * (rng.random::<f64>() - 0.5);
+
+    for _ in 0..3000 {
+        forest.insert(&[noisy(0.3), noisy(0.5), noisy(0.7)]);
+    }
+    let calm = forest.dimension_drift();
+    assert!(calm.iter().all(|d| d.abs() < 1.0), "{calm:?}");
+
+    for _ in 0..100 {
+        forest.insert(&[noisy(0.3), noisy(0.8), noisy(0.7)]);
+    }
+    let drift = forest.dimension_drift();
+    assert!(drift[1] > 2.5, "{drift:?}");
+    assert!(drift[0].abs() < 1.0 && drift[2].abs() < 1.0, "{drift:?}");
+}
+
+#[test]
+fn flat_f32_round_trip_preserves_scores() {
+    let bounds = vec![(0.0, 1.0); 3];
+    let mut forest = HalfSpaceTrees::new(8, 5, &bounds, &mut StdRng::seed_from_u64(18));
+    let mut rng = StdRng::seed_from_u64(19);
+    for _ in 0..300 {
+        forest.insert(&[rng.random(), 0.5 * rng.random::<f64>(), 0.3]);
+    }
+
+    let (blob, layout) = forest.to_flat_f32().unwrap();
+    assert_eq!(blob.len(), layout.len());
+    let restored = HalfSpaceTrees::from_flat_f32(&blob, layout).unwrap();
+    for _ in 0..50 {
+        let x = [rng.random(), rng.random(), rng.random()];
+        assert!((forest.score(&x) - restored.score(&x)).abs() < 1e-4);
+    }
+
+    assert!(HalfSpaceTrees::from_flat_f32(&blob[1..], layout).is_err());
+    for max_depth in [41, 63, u32::MAX] {
+        let deep = ForestLayout {
+            max_depth,
+            ..layout
+        };
+        assert!(matches!(
+            HalfSpaceTrees::from_flat_f32(&blob, deep),
+            Err(HstError::InvalidLayout(_))
+        ));
+    }
+    let huge = ForestLayout {
+        n_trees: usize::MAX,
+        ..layout
+    };
+    assert!(HalfSpaceTrees::from_flat_f32(&blob, huge).is_err());
+}
+
+#[test]
+fn flat_export_rejects_what_the_layout_cannot_hold() {
+    let bounds = vec![(0.0, 1.0); 3];
+    let mut rng = StdRng::seed_from_u64(133);
+    let oblique = HalfSpaceTrees::with_split_mode(4, 5, &bounds, SplitMode::Oblique, &mut rng);
+    assert!(matches!(
+        oblique.to_flat_f32(),
+        Err(HstError::InvalidLayout(_))
+    ));
+
+    let mut mixed = HalfSpaceTrees::new(4, 5, &bounds, &mut rng);
+    mixed.add_tree(HalfSpaceTree::new(3, &bounds, &mut rng));
+    assert!(mixed.to_flat_f32().is_err());
+}
+
+#[test]
+fn nearest_normal_suggestion_lowers_score() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut forest = HalfSpaceTrees::new(20, 6, &bounds, &mut StdRng::seed_from_u64(20));
+    let mut rng = StdRng::seed_from_u64(21);
+    for _ in 0..2000 {
+        forest.insert(&[0.3 * rng.random::<f64>(), 0.3 * rng.random::<f64>()]);
+    }
+    let x = [0.4, 0.25];
+    let suggestion = forest.nearest_normal_suggestion(&x);
+    assert_eq!(suggestion.len(), 2);
+    assert!(forest.score(&suggestion) < forest.score(&x));
+    let changed = suggestion.iter().zip(&x).filter(|(a, b)| a != b).count();
+    assert_eq!(changed, 1);
+}
+
+#[test]
+fn auto_forest_separates_planted_outlier() {
+    let mut rng = StdRng::seed_from_u64(22);
+    let data: Vec<Vec<f64>> = (0..1000)
+        .map(|_| vec![5.0 + rng.random::<f64>(), -2.0 + rng.random::<f64>()])
+        .collect();
+    let forest = HalfSpaceTrees::auto(&data, 0.25, &mut rng);
+    assert!(!forest.trees().is_empty());
+    assert!(forest.score(&[6.08, -1.92]) > forest.score(&[5.5, -1.5]));
+}
+
+#[test]
+fn agreement_matrix_is_symmetric_with_unit_diagonal() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let forest = HalfSpaceTrees::new(30, 6, &bounds, &mut StdRng::seed_from_u64(23));
+    let xs = vec![
+        vec![0.2, 0.2],
+        vec![0.2001, 0.2001],
+        vec![0.9, 0.1],
+        vec![0.5, 0.8],
+    ];
+    let m = forest.agreement_matrix(&xs);
+    assert_eq!(m.len(), 4);
+    for (i, row) in m.iter().enumerate() {
+        assert_eq!(row[i], 1.0);
+        for (j, &v) in row.iter().enumerate() {
+            assert_eq!(v, m[j][i]);
+            assert!((0.0..=1.0).contains(&v));
+        }
+    }
+    assert!(m[0][1] > 0.8);
+    assert!(m[0][1] > m[0][2]);
+}
+
+#[test]
+fn tree_cap_evicts_down_to_limit() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut forest = HalfSpaceTrees::new(40, 6, &bounds, &mut StdRng::seed_from_u64(24));
+    let mut rng = StdRng::seed_from_u64(25);
+    for _ in 0..500 {
+        forest.insert(&[0.4 * rng.random::<f64>(), 0.4 * rng.random::<f64>()]);
+    }
+    forest.enforce_tree_cap(15);
+    assert_eq!(forest.trees().len(), 15);
+    assert!(forest.score(&[0.9, 0.9]) > forest.score(&[0.2, 0.2]));
+
+    forest.enforce_tree_cap(20);
+    assert_eq!(forest.trees().len(), 15);
+}
+
+#[test]
+#[should_panic(expected = "tree cap must be positive")]
+fn tree_cap_of_zero_is_rejected() {
+    let mut forest = HalfSpaceTrees::new(4, 4, &[(0.0, 1.0)], &mut StdRng::seed_from_u64(24));
+    forest.enforce_tree_cap(0);
+}
+
+#[test]
+fn harmonic_weighting_favours_deep_evidence() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut linear = HalfSpaceTrees::new(30, 8, &bounds, &mut StdRng::seed_from_u64(26));
+    let mut rng = StdRng::seed_from_u64(27);
+    for _ in 0..3000 {
+        let x = [0.5 * rng.random::<f64>(), 0.5 * rng.random::<f64>()];
+        linear.insert(&x);
+    }
+    let normal = [0.25, 0.25];
+    let far = [0.95, 0.95];
+    let (n_lin, f_lin) = (linear.score(&normal), linear.score(&far));
+
+    let mut harmonic = linear;
+    harmonic.set_depth_weighting(DepthWeighting::Harmonic);
+    let (n_har, f_har) = (harmonic.score(&normal), harmonic.score(&far));
+
+    assert!(f_lin > n_lin);
+    assert!(f_har > n_har);
+    // Averaging in the path's rarities shrinks the normal point's score more than the far
+    // point's, whose path empties early, so the far point stands out more.
+    assert!(
+        f_har / n_har > 1.2 * (f_lin / n_lin),
+        "{} vs {}",
+        f_har / n_har,
+        f_lin / n_lin
+    );
+    // The empty leaf alone carries weight 1 out of H(9) = 1 + 1/2 + ... + 1/9,
+    // so a deep miss can never be washed out by dense shallow ancestors.
+    let harmonic_sum: f64 = (1..=9).map(|k| 1.0 / k as f64).sum();
+    assert!(f_har >= 1.0 / harmonic_sum);
+}
+
+/// Evaluate the nested `if x[d] < v { .. } else { .. }` body emitted by `to_rust_source`.
+fn eval_generated(lines: &[&str], pos: &mut usize, x: &[f64]) -> f64 {
+    let line = lines[*pos];
+    *pos += 1;
+    if let Some(rest) = line.strip_prefix("if x[") {
+        let (dim, rest) = rest.split_once("] < ").unwrap();
+        let split: f64 = rest.trim_end_matches(" {").parse().unwrap();
+        let left = eval_generated(lines, pos, x);
+        assert_eq!(lines[*pos], "} else {");
+        *pos += 1;
+        let right = eval_generated(lines, pos, x);
+        assert_eq!(lines[*pos], "}");
+        *pos += 1;
+        if x[dim.parse::<usize>().unwrap()] < split {
+            left
+        } else {
+            right
+        }
+    } else {
+        line.split(" //").next().unwrap().parse().unwrap()
+    }
+}
+
+#[test]
+fn rust_source_export_matches_tree_scores() {
+    let bounds = vec![(0.0, 1.0); 3];
+    let mut forest = HalfSpaceTrees::new(1, 4, &bounds, &mut StdRng::seed_from_u64(28));
+    let mut rng = StdRng::seed_from_u64(29);
+    for _ in 0..200 {
+        forest.insert(&[rng.random(), 0.5 * rng.random::<f64>(), rng.random()]);
+    }
+    let tree = &forest.trees()[0];
+    let src = tree.to_rust_source("score_tree");
+
+    assert!(src.contains("pub fn score_tree(x: &[f64]) -> f64 {"));
+    assert_eq!(src.matches("if x[").count(), (1 << 4) - 1);
+
+    let body: Vec<&str> = src.lines().map(str::trim).collect();
+    let start = body.iter().position(|l| l.starts_with("pub fn")).unwrap() + 1;
+    for _ in 0..50 {
+        let x = [rng.random(), rng.random(), rng.random()];
+        let mut pos = start;
+        assert_eq!(eval_generated(&body, &mut pos, &x), tree.score(&x));
+        assert_eq!(body[pos], "}");
+    }
+}
+
+#[test]
+fn last_insert_impact_tracks_each_tree() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut forest = HalfSpaceTrees::new(6, 5, &bounds, &mut StdRng::seed_from_u64(30));
+    assert!(forest.last_insert_impact().is_empty());
+
+    forest.insert(&[0.3, 0.3]);
+    forest.decay(0.5);
+    assert_eq!(forest.last_insert_impact(), vec![1.0; 6]);
+    for t in forest.trees() {
+        assert_eq!(t.total_mass(), 0.5);
+    }
+}
+
+#[test]
+fn score_at_alpha_predicts_decay_without_mutation() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut forest = HalfSpaceTrees::new(10, 6, &bounds, &mut StdRng::seed_from_u64(31));
+    for _ in 0..50 {
+        forest.insert(&[0.4, 0.6]);
+    }
+    let x = [0.4, 0.6];
+    let now = forest.score(&x);
+    assert_eq!(forest.score_at_alpha(&x, 0.9, 0), now);
+    let mild = forest.score_at_alpha(&x, 0.9, 5);
+    let strong = forest.score_at_alpha(&x, 0.5, 5);
+    assert!(now < mild && mild < strong);
+    assert_eq!(forest.score(&x), now);
+    // Past i32::MAX ticks the masses have long decayed away.
+    assert_eq!(
+        forest.score_at_alpha(&x, 0.9, u32::MAX),
+        forest.score_at_alpha(&x, 0.0, 1)
+    );
+
+    for _ in 0..5 {
+        forest.decay(0.5);
+    }
+    assert!((forest.score(&x) - strong).abs() < 1e-12);
+}
+
+#[test]
+fn group_insert_counts_as_one_event() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut forest = HalfSpaceTrees::new(5, 5, &bounds, &mut StdRng::seed_from_u64(32));
+    let session: Vec<Vec<f64>> = (0..10).map(|i| vec![0.1 * i as f64, 0.5]).collect();
+    forest.insert_group(&session, 1.0);
+    for t in forest.trees() {
+        assert!((t.total_mass() - 1.0).abs() < 1e-12);
+    }
+    forest.insert(&[0.5, 0.5]);
+    for t in forest.trees() {
+        assert!((t.total_mass() - 2.0).abs() < 1e-12);
+    }
+}
+
+#[test]
+fn benchmark_stats_are_ordered() {
+    let bounds = vec![(0.0, 1.0); 3];
+    let forest = HalfSpaceTrees::new(10, 6, &bounds, &mut StdRng::seed_from_u64(33));
+    let stats = forest.benchmark_score(&[0.1, 0.2, 0.3], 200);
+    assert!(stats.min <= stats.median);
+    assert!(stats.median <= stats.p99);
+    assert!(stats.p99 <= stats.max);
+}
+
+#[test]
+#[should_panic(expected = "iters must be positive")]
+fn benchmark_rejects_zero_iters() {
+    let bounds = vec![(0.0, 1.0); 3];
+    let forest = HalfSpaceTrees::new(1, 2, &bounds, &mut StdRng::seed_from_u64(33));
+    forest.benchmark_score(&[0.1, 0.2, 0.3], 0);
+}
+
+#[test]
+fn variance_aware_scoring_penalizes_spread_leaves() {
+    let bounds = [(0.0, 1.0)];
+    let mut tight = HalfSpaceTree::new(0, &bounds, &mut StdRng::seed_from_u64(34));
+    let mut spread = HalfSpaceTree::new(0, &bounds, &mut StdRng::seed_from_u64(34));
+    for i in 0..10 {
+        tight.insert(&[0.5]);
+        spread.insert(&[if i % 2 == 0 { 0.0 } else { 1.0 }]);
+    }
+    assert_eq!(tight.leaf_variance(&[0.5]), 0.0);
+    assert!((spread.leaf_variance(&[0.5]) - 0.25).abs() < 1e-12);
+    assert_eq!(tight.score(&[0.5]), spread.score(&[0.5]));
+
+    tight.set_score_method(ScoreMethod::VarianceAware);
+    spread.set_score_method(ScoreMethod::VarianceAware);
+    assert!(spread.score(&[0.5]) > tight.score(&[0.5]));
+}
+
+#[test]
+fn decay_half_life_matches_closed_form() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let forest = HalfSpaceTrees::new(1, 2, &bounds, &mut StdRng::seed_from_u64(35));
+    let alpha: f64 = 0.999;
+    let inserts = forest.decay_half_life(alpha, 1.0);
+    assert!((inserts - 0.5f64.ln() / alpha.ln()).abs() < 1e-9);
+    assert!((forest.decay_half_life(alpha, 100.0) - inserts / 100.0).abs() < 1e-9);
+    assert!((alpha.powf(inserts) - 0.5).abs() < 1e-12);
+    assert_eq!(forest.decay_half_life(1.0, 1.0), f64::INFINITY);
+}
+
+#[test]
+fn windowed_forest_evicts_exactly() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let forest = HalfSpaceTrees::new(10, 6, &bounds, &mut StdRng::seed_from_u64(36));
+    let mut windowed = WindowedForest::new(forest, 50);
+
+    let old = [0.9, 0.9];
+    for _ in 0..50 {
+        windowed.push(&old);
+    }
+    let old_score = windowed.score(&old);
+    for _ in 0..50 {
+        windowed.push(&[0.1, 0.1]);
+    }
+    assert_eq!(windowed.window().len(), 50);
+    for t in windowed.forest().trees() {
+        assert!((t.total_mass() - 50.0).abs() < 1e-9);
+    }
+    // The evicted region is back to looking unseen.
+    assert!(windowed.score(&old) > old_score);
+    assert!((windowed.score(&old) - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn self_score_summary_is_ordered() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut forest = HalfSpaceTrees::new(20, 6, &bounds, &mut StdRng::seed_from_u64(37));
+    let mut rng = StdRng::seed_from_u64(38);
+    let data: Vec<Vec<f64>> = (0..500)
+        .map(|_| vec![rng.random::<f64>().powi(3), rng.random()])
+        .collect();
+    for x in &data {
+        forest.insert(x);
+    }
+    let s = forest.self_score_summary(&data);
+    assert!(s.min <= s.median && s.median <= s.p95 && s.p95 <= s.p99 && s.p99 <= s.max);
+    assert!(s.min <= s.mean && s.mean <= s.max);
+    assert!(s.p99 >= s.median);
+}
+
+#[test]
+fn holdout_reweighting_downweights_a_contrarian_tree() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(39);
+    let mut forest = HalfSpaceTrees::new(15, 6, &bounds, &mut rng);
+    let mut rogue = HalfSpaceTree::new(6, &bounds, &mut rng);
+    for _ in 0..2000 {
+        let (a, b) = (rng.random::<f64>(), rng.random::<f64>());
+        forest.insert(&[0.4 * a, 0.4 * b]);
+        rogue.insert(&[1.0 - 0.4 * a, 1.0 - 0.4 * b]);
+    }
+    forest.add_tree(rogue);
+    assert_eq!(forest.tree_weights(), vec![1.0; 16]);
+
+    let holdout: Vec<Vec<f64>> = (0..200).map(|_| vec![rng.random(), rng.random()]).collect();
+    forest.reweight_by_holdout(&holdout);
+    let w = forest.tree_weights();
+    let typical = w[..15].iter().sum::<f64>() / 15.0;
+    assert!(w[15] < 0.5 * typical, "{w:?}");
+    assert!(forest.score(&[0.9, 0.9]) > forest.score(&[0.2, 0.2]));
+}
+
+#[test]
+fn loo_stability_exposes_a_dominant_tree() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(40);
+    let mut balanced = HalfSpaceTrees::new(11, 6, &bounds, &mut rng);
+    let mut skewed = HalfSpaceTrees::new(10, 6, &bounds, &mut rng);
+    let mut odd_one = HalfSpaceTree::new(6, &bounds, &mut rng);
+    for _ in 0..1000 {
+        let x = [0.3 * rng.random::<f64>(), 0.3 * rng.random::<f64>()];
+        balanced.insert(&x);
+        skewed.insert(&x);
+    }
+    odd_one.insert(&[0.9, 0.9]);
+    skewed.add_tree(odd_one);
+
+    let x = [0.15, 0.15];
+    assert!(skewed.loo_stability(&x) > balanced.loo_stability(&x));
+}
+
+#[test]
+fn loo_stability_skips_sub_forests_without_weight() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(140);
+    let mut forest = HalfSpaceTrees::new(1, 6, &bounds, &mut rng);
+    for _ in 0..500 {
+        forest.insert(&[rng.random(), rng.random()]);
+    }
+    // The untrained tree scores every point alike, so the holdout gives it no weight.
+    forest.add_tree(HalfSpaceTree::new(6, &bounds, &mut rng));
+    let holdout: Vec<Vec<f64>> = (0..50).map(|_| vec![rng.random(), rng.random()]).collect();
+    forest.reweight_by_holdout(&holdout);
+    assert_eq!(forest.loo_stability(&[0.5, 0.5]), 0.0);
+}
+
+#[test]
+fn dampened_insert_discounts_anomalies() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut forest = HalfSpaceTrees::new(20, 6, &bounds, &mut StdRng::seed_from_u64(41));
+    for _ in 0..500 {
+        forest.insert(&[0.2, 0.2]);
+    }
+    let mass = |f: &HalfSpaceTrees| f.trees().iter().map(|t| t.total_mass()).sum::<f64>();
+
+    let before = mass(&forest);
+    let w_in = forest.insert_dampened(&[0.2, 0.2], 0.1);
+    let inlier_gain = mass(&forest) - before;
+
+    let before = mass(&forest);
+    let w_out = forest.insert_dampened(&[0.9, 0.9], 0.1);
+    let outlier_gain = mass(&forest) - before;
+
+    assert!(w_out < w_in && w_in <= 1.0);
+    assert!(outlier_gain < inlier_gain);
+    assert!(outlier_gain > 0.0);
+}
+
+#[test]
+fn tightest_margin_finds_the_nearby_split() {
+    let bounds = vec![(0.0, 1.0); 3];
+    let forest = HalfSpaceTrees::new(10, 6, &bounds, &mut StdRng::seed_from_u64(42));
+    let mut x = vec![0.5, 0.5, 0.5];
+    let (dim, margin) = forest.tightest_margin(&x);
+    assert!(margin.is_finite());
+
+    // Slide that feature to just right of its split.
+    x[dim] -= margin - 1e-9;
+    let (near_dim, near_margin) = forest.tightest_margin(&x);
+    assert_eq!(near_dim, dim);
+    assert!(near_margin.abs() < 1e-6, "{near_margin}");
+}
+
+#[test]
+fn event_decay_scales_with_gap_length() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut bursty = HalfSpaceTrees::new(5, 4, &bounds, &mut StdRng::seed_from_u64(43));
+    let mut sparse = HalfSpaceTrees::new(5, 4, &bounds, &mut StdRng::seed_from_u64(43));
+    bursty.set_event_decay(0.9, 1.0);
+    sparse.set_event_decay(0.9, 1.0);
+    for _ in 0..20 {
+        bursty.insert_event(&[0.5, 0.5], 0.1);
+        sparse.insert_event(&[0.5, 0.5], 10.0);
+    }
+    let mass = |f: &HalfSpaceTrees| f.trees()[0].total_mass();
+    assert!(mass(&sparse) < mass(&bursty));
+    // A gap of 10 baseline intervals decays by 0.9^10 before each insert.
+    let expected = 1.0 / (1.0 - 0.9f64.powi(10));
+    assert!((mass(&sparse) - expected).abs() < 1e-3);
+}
+
+#[test]
+fn coverage_drops_for_shifted_validation() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut forest = HalfSpaceTrees::new(20, 6, &bounds, &mut StdRng::seed_from_u64(44));
+    let mut rng = StdRng::seed_from_u64(45);
+    let mut sample = |offset: f64| {
+        vec![
+            offset + 0.3 * rng.random::<f64>(),
+            0.3 * rng.random::<f64>(),
+        ]
+    };
+    for _ in 0..3000 {
+        let x = sample(0.0);
+        forest.insert(&x);
+    }
+    let same: Vec<Vec<f64>> = (0..200).map(|_| sample(0.0)).collect();
+    let shifted: Vec<Vec<f64>> = (0..200).map(|_| sample(0.65)).collect();
+    // Shallow trees lump regions together, so the mean leaf mass is large everywhere
+    // a few coarse trees overlap; a high floor isolates genuinely learned regions.
+    assert!(forest.coverage(&same, 400.0) > 0.9);
+    assert!(forest.coverage(&shifted, 400.0) < 0.1);
+}
+
+#[test]
+fn memory_by_level_doubles_per_level() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let forest = HalfSpaceTrees::new(7, 5, &bounds, &mut StdRng::seed_from_u64(46));
+    let levels = forest.memory_by_level();
+    assert_eq!(levels.len(), 6);
+    let node_bytes = levels[0] / 7;
+    assert!(node_bytes > 0);
+    for (d, &bytes) in levels.iter().enumerate() {
+        assert_eq!(bytes, 7 * (1 << d) * node_bytes);
+    }
+    assert_eq!(levels.iter().sum::<usize>(), forest.memory_bytes());
+}
+
+#[test]
+fn relative_score_is_negative_where_only_current_learned() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut baseline = HalfSpaceTrees::new(20, 6, &bounds, &mut StdRng::seed_from_u64(47));
+    let mut current = HalfSpaceTrees::new(20, 6, &bounds, &mut StdRng::seed_from_u64(47));
+    for _ in 0..500 {
+        baseline.insert(&[0.2, 0.2]);
+        current.insert(&[0.2, 0.2]);
+        current.insert(&[0.8, 0.8]);
+    }
+    assert!(current.relative_score(&baseline, &[0.8, 0.8]) < 0.0);
+    assert!(current.relative_score(&baseline, &[0.2, 0.2]).abs() < 0.05);
+}
+
+#[test]
+fn oblique_splits_catch_broken_correlation() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut axis = HalfSpaceTrees::new(40, 6, &bounds, &mut StdRng::seed_from_u64(48));
+    let mut oblique = HalfSpaceTrees::with_split_mode(
+        40,
+        6,
+        &bounds,
+        SplitMode::Oblique,
+        &mut StdRng::seed_from_u64(48),
+    );
+    let mut rng = StdRng::seed_from_u64(49);
+    for _ in 0..3000 {
+        let t = rng.random::<f64>();
+        let x = [t, (t + 0.02 * (rng.random::<f64>() - 0.5)).clamp(0.0, 1.0)];
+        axis.insert(&x);
+        oblique.insert(&x);
+    }
+    // Each coordinate is ordinary on its own; only the pair is off the diagonal.
+    let (normal, anomaly) = ([0.5, 0.5], [0.4, 0.6]);
+    let axis_gap = axis.score(&anomaly) - axis.score(&normal);
+    let oblique_gap = oblique.score(&anomaly) - oblique.score(&normal);
+    assert!(
+        oblique_gap > axis_gap,
+        "oblique {oblique_gap} vs axis {axis_gap}"
+    );
+    assert!(oblique_gap > 0.0);
+}
+
+#[test]
+fn score_convergence_settles_on_full_forest_score() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut forest = HalfSpaceTrees::new(200, 6, &bounds, &mut StdRng::seed_from_u64(50));
+    let mut rng = StdRng::seed_from_u64(51);
+    for _ in 0..1000 {
+        forest.insert(&[rng.random(), rng.random()]);
+    }
+    let x = [0.3, 0.7];
+    let curve = forest.score_convergence(&x, 10);
+    assert_eq!(curve.len(), 20);
+    assert_eq!(*curve.last().unwrap(), forest.score(&x));
+
+    let deltas: Vec<f64> = curve.windows(2).map(|w| (w[1] - w[0]).abs()).collect();
+    let early = deltas[..5].iter().sum::<f64>();
+    let late = deltas[deltas.len() - 5..].iter().sum::<f64>();
+    assert!(late < early, "early {early} late {late}");
+}
+
+#[test]
+fn quantized_scores_snap_to_the_quantum() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut forest = HalfSpaceTrees::new(20, 6, &bounds, &mut StdRng::seed_from_u64(52));
+    for i in 0..300 {
+        forest.insert(&[(i % 17) as f64 / 17.0, 0.4]);
+    }
+    let (a, b) = ([0.3, 0.4], [0.3 + 1e-12, 0.4]);
+    let raw = forest.score(&a);
+
+    let q = 1e-3;
+    forest.set_score_quantum(Some(q));
+    let qa = forest.score(&a);
+    assert!(((qa / q) - (qa / q).round()).abs() < 1e-9);
+    assert!((qa - raw).abs() <= q / 2.0 + 1e-12);
+    assert_eq!(qa, forest.score(&b));
+
+    forest.set_score_quantum(None);
+    assert_eq!(forest.score(&a), raw);
+}
+
+#[test]
+fn pseudocode_has_one_line_per_decision_and_leaf() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut tree = HalfSpaceTree::new(3, &bounds, &mut StdRng::seed_from_u64(53));
+    tree.insert(&[0.5, 0.5]);
+    let text = tree.to_pseudocode();
+    assert!(text.starts_with("if x["));
+    assert_eq!(text.matches("if ").count(), 7);
+    assert_eq!(text.matches("} else {").count(), 7);
+    assert_eq!(text.matches("leaf (depth 3, mass").count(), 8);
+    assert_eq!(text.matches("mass 1.0000").count(), 1);
+}
+
+#[test]
+fn rebuild_preview_matches_actual_rebuild() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(54);
+    let mut forest = HalfSpaceTrees::new(10, 5, &bounds, &mut rng);
+    for _ in 0..500 {
+        let x = [rng.random_range(0.2..0.4), rng.random_range(0.6..0.9)];
+        forest.insert(&x);
+    }
+    let before = forest.trees()[0].total_mass();
+    let preview = forest.preview_rebuild_balance(&mut StdRng::seed_from_u64(7));
+    assert_eq!(forest.trees()[0].total_mass(), before);
+
+    forest.rebuild(&mut StdRng::seed_from_u64(7));
+    assert_eq!(preview, forest.split_balance());
+    assert!(preview > 0.0 && preview <= 1.0);
+    assert!((forest.trees()[0].total_mass() - before).abs() < 1e-9);
+}
+
+#[test]
+fn frequency_sketch_counts_repeated_payloads() {
+    let bounds = vec![(0.0, 1.0); 3];
+    let mut rng = StdRng::seed_from_u64(55);
+    let mut forest = HalfSpaceTrees::new(10, 6, &bounds, &mut rng);
+    let repeated = [0.25, 0.5, 0.75];
+    assert_eq!(forest.estimated_frequency(&repeated), 0);
+
+    forest.enable_frequency_sketch(256, 4, 1e-6);
+    let mut last = 0;
+    for i in 1..=50u64 {
+        forest.insert(&repeated);
+        for _ in 0..10 {
+            let x: Vec<f64> = (0..3).map(|_| rng.random()).collect();
+            forest.insert(&x);
+        }
+        let est = forest.estimated_frequency(&repeated);
+        assert!(est >= i && est >= last);
+        last = est;
+    }
+    // 550 inserts over 256 columns: collisions can add only a handful on the min row
+    assert!(last <= 50 + 550 * 3 / 256);
+    assert!(forest.estimated_frequency(&[0.9, 0.1, 0.1]) < 10);
+}
+
+#[test]
+fn dimension_focused_score_tracks_that_dimension() {
+    let bounds = vec![(0.0, 1.0); 3];
+    let mut rng = StdRng::seed_from_u64(56);
+    let mut forest = HalfSpaceTrees::new(30, 3, &bounds, &mut rng);
+    for _ in 0..1000 {
+        let x: Vec<f64> = vec![rng.random_range(0.4..0.6), rng.random(), rng.random()];
+        forest.insert(&x);
+    }
+    let normal = [0.5, 0.5, 0.5];
+    let odd = [0.95, 0.5, 0.5];
+    assert!(forest.score_for_dim(&odd, 0) > forest.score_for_dim(&normal, 0));
+    assert!(forest.score_for_dim(&odd, 0) > forest.score(&odd));
+
+    let stump = HalfSpaceTrees::new(1, 1, &[(0.0, 1.0); 2], &mut rng);
+    let used = (0..2).find(|&d| stump.trees()[0].uses_dim(d)).unwrap();
+    assert!(!stump.score_for_dim(&normal[..2], used).is_nan());
+    assert!(stump.score_for_dim(&normal[..2], 1 - used).is_nan());
+}
+
+#[test]
+fn marginal_peaks_at_the_trained_cluster() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(57);
+    let mut forest = HalfSpaceTrees::new(30, 8, &bounds, &mut rng);
+    for _ in 0..1000 {
+        forest.insert(&[rng.random_range(0.74..0.76), rng.random()]);
+    }
+    let hist = forest.marginal(0, 10);
+    assert_eq!(hist.len(), 10);
+    assert!((hist.iter().sum::<f64>() - 1000.0).abs() < 1e-6);
+    let peak = (0..10)
+        .max_by(|&a, &b| hist[a].total_cmp(&hist[b]))
+        .unwrap();
+    assert!((6..=8).contains(&peak), "peak bin {peak}");
+    assert!(hist[peak] > 3.0 * hist[2]);
+}
+
+#[test]
+fn density_seeding_puts_mass_where_the_density_is_high() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut forest = HalfSpaceTrees::new(20, 6, &bounds, &mut StdRng::seed_from_u64(58));
+    forest.insert(&[0.9, 0.9]);
+    let bump = |x: &[f64]| (-50.0 * ((x[0] - 0.3).powi(2) + (x[1] - 0.3).powi(2))).exp();
+    forest.seed_from_density(bump, 500.0);
+
+    for t in forest.trees() {
+        assert!((t.total_mass() - 500.0).abs() < 1e-6);
+    }
+    let (dense, sparse) = ([0.3, 0.3], [0.9, 0.9]);
+    let mass = |x: &[f64]| -> f64 { forest.trees().iter().map(|t| t.leaf_mass(x)).sum() };
+    assert!(mass(&dense) > 10.0 * mass(&sparse));
+    assert!(forest.score(&dense) < forest.score(&sparse));
+}
+
+#[test]
+fn tree_correlation_flags_duplicate_trees() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(59);
+    let mut forest = HalfSpaceTrees::new(4, 5, &bounds, &mut rng);
+    forest.add_tree(HalfSpaceTree::new(
+        5,
+        &bounds,
+        &mut StdRng::seed_from_u64(1),
+    ));
+    forest.add_tree(HalfSpaceTree::new(
+        5,
+        &bounds,
+        &mut StdRng::seed_from_u64(1),
+    ));
+    for _ in 0..300 {
+        forest.insert(&[rng.random(), rng.random::<f64>().powi(2)]);
+    }
+    let probe: Vec<Vec<f64>> = (0..50).map(|_| vec![rng.random(), rng.random()]).collect();
+    let m = forest.tree_correlation(&probe);
+    assert_eq!(m.len(), 6);
+    for (i, row) in m.iter().enumerate() {
+        assert_eq!(row[i], 1.0);
+        for (j, &v) in row.iter().enumerate() {
+            assert_eq!(v, m[j][i]);
+        }
+    }
+    assert!((m[4][5] - 1.0).abs() < 1e-9);
+    assert!(m[0][1] < 0.99);
+}
+
+#[test]
+fn reconstruction_stays_in_bounds_and_near_the_input() {
+    let bounds = vec![(0.0, 1.0), (-5.0, 5.0)];
+    let forest = HalfSpaceTrees::new(50, 10, &bounds, &mut StdRng::seed_from_u64(60));
+    let x = [0.42, 1.3];
+    let r = forest.reconstruct(&x);
+    for ((v, (lo, hi)), xi) in r.iter().zip(&bounds).zip(x) {
+        assert!(lo <= v && v <= hi);
+        assert!((v - xi).abs() < 0.1 * (hi - lo), "{v} vs {xi}");
+    }
+    for t in forest.trees() {
+        let region = t.leaf_region(&x);
+        assert!(
+            region
+                .iter()
+                .zip(x)
+                .all(|((lo, hi), xi)| *lo <= xi && xi < *hi)
+        );
+    }
+}
+
+#[test]
+fn smoothing_sweep_finds_a_separating_constant() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(61);
+    let mut forest = HalfSpaceTrees::new(30, 8, &bounds, &mut rng);
+    for _ in 0..2000 {
+        forest.insert(&[rng.random_range(0.3..0.5), rng.random_range(0.3..0.5)]);
+    }
+    let mut probe = Vec::new();
+    let mut labels = Vec::new();
+    for i in 0..100 {
+        let outlier = i % 5 == 0;
+        probe.push(if outlier {
+            vec![rng.random_range(0.55..1.0), rng.random()]
+        } else {
+            vec![rng.random_range(0.3..0.5), rng.random_range(0.3..0.5)]
+        });
+        labels.push(outlier);
+    }
+    let values = [0.01, 0.1, 1.0, 10.0, 100.0, 1000.0];
+    let sweep = forest.smoothing_sweep(&probe, &labels, &values);
+    assert_eq!(sweep.len(), values.len());
+    assert!(sweep.iter().all(|&s| s > 0.0));
+    let best = (0..values.len())
+        .max_by(|&a, &b| sweep[a].total_cmp(&sweep[b]))
+        .unwrap();
+    // Tiny constants blow up empty-leaf scores, huge ones flatten every score.
+    assert!(best > 0 && best < values.len() - 1, "{sweep:?}");
+}
+
+#[test]
+fn heavy_training_saturates_leaves() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut forest = HalfSpaceTrees::new(10, 4, &bounds, &mut StdRng::seed_from_u64(62));
+    assert_eq!(forest.saturated_leaves(1e-3), 0);
+    for _ in 0..500 {
+        forest.insert(&[0.2, 0.7]);
+    }
+    assert_eq!(forest.saturated_leaves(1e-3), 0);
+    for _ in 0..1000 {
+        forest.insert(&[0.2, 0.7]);
+    }
+    assert_eq!(forest.saturated_leaves(1e-3), 10);
+    assert_eq!(forest.saturated_leaves(1.1), 160);
+}
+
+#[test]
+fn anomaly_stream_yields_only_outliers() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(63);
+    let mut forest = HalfSpaceTrees::new(25, 8, &bounds, &mut rng);
+    for _ in 0..2000 {
+        forest.insert(&[rng.random_range(0.4..0.6), rng.random_range(0.4..0.6)]);
+    }
+    let threshold = forest
+        .self_score_summary(&[vec![0.5, 0.5], vec![0.95, 0.05]])
+        .mean;
+    let stream: Vec<Vec<f64>> = (0..200)
+        .map(|i| {
+            if i % 50 == 7 {
+                vec![0.95, 0.05]
+            } else {
+                vec![rng.random_range(0.45..0.55), rng.random_range(0.45..0.55)]
+            }
+        })
+        .collect();
+
+    let flagged: Vec<(Vec<f64>, f64)> = forest
+        .anomalies(
+            stream.clone().into_iter(),
+            threshold,
+            ProcessMode::ScoreOnly,
+        )
+        .collect();
+    assert_eq!(flagged.len(), 4);
+    assert!(
+        flagged
+            .iter()
+            .all(|(x, s)| x == &[0.95, 0.05] && *s > threshold)
+    );
+
+    let before = forest.trees()[0].total_mass();
+    let n = forest
+        .anomalies(stream.into_iter(), threshold, ProcessMode::ScoreThenInsert)
+        .count();
+    assert!((1..=4).contains(&n));
+    assert_eq!(forest.trees()[0].total_mass(), before + 200.0);
+}
+
+#[test]
+fn isotonic_calibration_is_monotonic_and_calibrated() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(64);
+    let mut forest = HalfSpaceTrees::new(25, 7, &bounds, &mut rng);
+    for _ in 0..2000 {
+        forest.insert(&[rng.random_range(0.3..0.6), rng.random_range(0.3..0.6)]);
+    }
+    let mut points = Vec::new();
+    let mut labels = Vec::new();
+    for i in 0..400 {
+        let anomaly = i % 4 == 0;
+        points.push(if anomaly {
+            vec![rng.random(), rng.random()]
+        } else {
+            vec![rng.random_range(0.3..0.6), rng.random_range(0.3..0.6)]
+        });
+        labels.push(anomaly);
+    }
+    let scores: Vec<f64> = points.iter().map(|x| forest.score(x)).collect();
+    forest.fit_isotonic(&scores, &labels);
+
+    let mut by_score: Vec<(f64, f64)> = points
+        .iter()
+        .zip(&scores)
+        .map(|(x, &s)| (s, forest.probability(x)))
+        .collect();
+    by_score.sort_by(|a, b| a.0.total_cmp(&b.0));
+    assert!(by_score.windows(2).all(|w| w[0].1 <= w[1].1));
+    assert!(by_score.iter().all(|&(_, p)| (0.0..=1.0).contains(&p)));
+    assert_eq!(forest.probability(&[0.45, 0.45]), by_score[0].1);
+
+    let brier = |ps: &[f64]| -> f64 {
+        ps.iter()
+            .zip(&labels)
+            .map(|(p, &l)| (p - if l { 1.0 } else { 0.0 }).powi(2))
+            .sum::<f64>()
+            / ps.len() as f64
+    };
+    let calibrated: Vec<f64> = points.iter().map(|x| forest.probability(x)).collect();
+    assert!(brier(&calibrated) < brier(&scores));
+}
+
+#[test]
+fn noise_moves_the_expected_score_only_near_splits() {
+    let bounds = vec![(0.0, 1.0)];
+    let mut rng = StdRng::seed_from_u64(65);
+    let mut forest = HalfSpaceTrees::new(1, 1, &bounds, &mut rng);
+    let split = -forest.tightest_margin(&[0.0]).1;
+    for _ in 0..100 {
+        forest.insert(&[split / 2.0]);
+    }
+    let sigma = 0.01 * split.min(1.0 - split);
+    let near = [split + 1e-6];
+    let far = [split / 2.0];
+    let noisy_near = forest.expected_score_under_noise(&near, &[sigma], 2000, &mut rng);
+    let noisy_far = forest.expected_score_under_noise(&far, &[sigma], 2000, &mut rng);
+    assert!((noisy_near - forest.score(&near)).abs() > 0.2);
+    assert!((noisy_far - forest.score(&far)).abs() < 1e-12);
+}
+
+#[test]
+fn leaf_metadata_follows_routing() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut tree =
+        HalfSpaceTree::new(4, &bounds, &mut StdRng::seed_from_u64(66)).with_leaf_meta::<&str>();
+    let x = [0.3, 0.8];
+    assert_eq!(tree.leaf_meta(&x), None);
+    tree.set_leaf_meta(&x, "example #17");
+    tree.insert(&x);
+    assert_eq!(tree.leaf_meta(&x), Some(&"example #17"));
+    assert_eq!(tree.leaf_mass(&x), 1.0);
+
+    let elsewhere = (0..100)
+        .map(|i| [i as f64 / 100.0, 1.0 - i as f64 / 100.0])
+        .find(|p| tree.leaf_mass(p) == 0.0)
+        .unwrap();
+    assert_eq!(tree.leaf_meta(&elsewhere), None);
+}
+
+#[test]
+fn more_splits_mean_finer_resolution() {
+    let bounds = vec![(0.0, 1.0); 3];
+    let forest = HalfSpaceTrees::new(1, 5, &bounds, &mut StdRng::seed_from_u64(67));
+    let text = forest.trees()[0].to_pseudocode();
+    let splits: Vec<usize> = (0..3)
+        .map(|d| text.matches(&format!("if x[{d}] <")).count())
+        .collect();
+    let res = forest.effective_resolution();
+    assert_eq!(splits.iter().sum::<usize>(), 31);
+    for d in 0..3 {
+        assert!((res[d] - 1.0 / (splits[d] + 1) as f64).abs() < 1e-12);
+        for e in 0..3 {
+            if splits[d] > splits[e] {
+                assert!(res[d] < res[e]);
+            }
+        }
+    }
+}
+
+#[test]
+fn ties_rank_in_input_order() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut forest = HalfSpaceTrees::new(10, 6, &bounds, &mut StdRng::seed_from_u64(68));
+    for _ in 0..200 {
+        forest.insert(&[0.5, 0.5]);
+    }
+    // Unseen corners all tie at the maximum score; the trained point scores lowest.
+    let xs: Vec<Vec<f64>> = (0..30)
+        .map(|i| {
+            if i % 3 == 0 {
+                vec![0.5, 0.5]
+            } else {
+                vec![0.99, 0.01]
+            }
+        })
+        .collect();
+    let top = forest.top_k_anomalies(&xs, 12);
+    let idx: Vec<usize> = top.iter().map(|&(i, _)| i).collect();
+    let expected: Vec<usize> = (0..30).filter(|i| i % 3 != 0).take(12).collect();
+    assert_eq!(idx, expected);
+    assert!(top.windows(2).all(|w| w[0].1 == w[1].1));
+    assert_eq!(forest.top_k_anomalies(&xs, 100).len(), 30);
+
+    let streamed: Vec<f64> = forest
+        .anomalies(
+            xs.clone().into_iter(),
+            top[0].1 - 1e-9,
+            ProcessMode::ScoreOnly,
+        )
+        .map(|(x, _)| x[0])
+        .collect();
+    assert_eq!(streamed, vec![0.99; 20]);
+}
+
+#[test]
+fn region_masses_land_in_overlapping_leaves() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut forest = HalfSpaceTrees::new(1, 4, &bounds, &mut StdRng::seed_from_u64(69));
+    let x = [0.3, 0.6];
+    let cell = forest.trees()[0].leaf_region(&x);
+    let before = forest.score(&x);
+
+    forest.apply_region_masses(&[(cell.clone(), 50.0)]);
+    let tree = &forest.trees()[0];
+    assert!((tree.leaf_mass(&x) - 50.0).abs() < 1e-9);
+    assert!((tree.total_mass() - 50.0).abs() < 1e-9);
+    assert!(forest.score(&x) < before);
+
+    // A box over the whole space spreads its mass by leaf volume.
+    forest.apply_region_masses(&[(bounds.clone(), 100.0)]);
+    let tree = &forest.trees()[0];
+    let area: f64 = cell.iter().map(|(lo, hi)| hi - lo).product();
+    assert!((tree.leaf_mass(&x) - 50.0 - 100.0 * area).abs() < 1e-9);
+    assert!((tree.total_mass() - 150.0).abs() < 1e-9);
+}
+
+#[test]
+fn log_transform_separates_heavy_tailed_outliers() {
+    let bounds = vec![(0.0, 1e6), (0.0, 1.0)];
+    let mut rng = StdRng::seed_from_u64(70);
+    let data: Vec<Vec<f64>> = (0..2000)
+        .map(|_| vec![(rng.random_range(0.0..5.0f64)).exp(), rng.random()])
+        .collect();
+    let outlier = [5e4, 0.5];
+    let separation = |transform: Transform, rng: &mut StdRng| -> f64 {
+        let mut forest =
+            HalfSpaceTrees::with_transforms(25, 8, &bounds, &[transform, Transform::Identity], rng);
+        for x in &data {
+            forest.insert(x);
+        }
+        forest.score(&outlier) - forest.self_score_summary(&data).median
+    };
+    let identity = separation(Transform::Identity, &mut rng);
+    let log = separation(Transform::Log1p, &mut rng);
+    assert!(log > identity + 0.2, "log {log} identity {identity}");
+
+    assert_eq!(Transform::Symlog.apply(-(1f64.exp() - 1.0)), -1.0);
+}
+
+#[test]
+#[should_panic(expected = "non-negative")]
+fn log_transform_rejects_negative_bounds() {
+    let mut rng = StdRng::seed_from_u64(71);
+    HalfSpaceTree::with_transforms(3, &[(-1.0, 1.0)], &[Transform::Log1p], &mut rng);
+}
+
+#[test]
+fn leaf_volumes_tile_the_bounding_box() {
+    let bounds = vec![(0.0, 2.0), (-1.0, 1.0), (10.0, 13.0)];
+    let tree = HalfSpaceTree::new(7, &bounds, &mut StdRng::seed_from_u64(72));
+    let volumes = tree.leaf_volumes();
+    assert_eq!(volumes.len(), 128);
+    assert!((volumes.iter().sum::<f64>() - 12.0).abs() < 1e-9);
+    assert!(std::ptr::eq(volumes, tree.leaf_volumes()));
+
+    let logged = HalfSpaceTree::with_transforms(
+        5,
+        &bounds[..1],
+        &[Transform::Log1p],
+        &mut StdRng::seed_from_u64(72),
+    );
+    assert!((logged.leaf_volumes().iter().sum::<f64>() - 2.0).abs() < 1e-9);
+}
+
+#[test]
+fn dimension_weights_amplify_the_anomalous_dimension() {
+    let bounds = vec![(0.0, 1.0); 3];
+    let mut rng = StdRng::seed_from_u64(73);
+    let mut forest = HalfSpaceTrees::new(30, 6, &bounds, &mut rng);
+    for _ in 0..1000 {
+        forest.insert(&[rng.random_range(0.4..0.6), rng.random(), rng.random()]);
+    }
+    let x = [0.95, 0.5, 0.5];
+    let uniform = forest.score_weighted_dims(&x, &[1.0; 3]);
+    assert!((uniform - forest.score(&x)).abs() < 1e-12);
+    let boost_anomalous = forest.score_weighted_dims(&x, &[3.0, 1.0, 1.0]);
+    let boost_other = forest.score_weighted_dims(&x, &[1.0, 3.0, 1.0]);
+    assert!(boost_anomalous > uniform);
+    assert!(
+        boost_anomalous > boost_other,
+        "{boost_anomalous} vs {boost_other}"
+    );
+}
+
+#[test]
+fn out_of_bounds_points_are_flagged_per_tree() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(74);
+    let mut forest = HalfSpaceTrees::new(6, 4, &bounds, &mut rng);
+    assert_eq!(forest.oob_tree_fraction(&[0.5, 1.0]), 0.0);
+    assert_eq!(forest.oob_tree_fraction(&[50.0, 0.5]), 1.0);
+
+    for _ in 0..2 {
+        forest.add_tree(HalfSpaceTree::new(4, &[(0.0, 2.0), (0.0, 1.0)], &mut rng));
+    }
+    assert_eq!(forest.oob_tree_fraction(&[1.5, 0.5]), 0.75);
+}
+
+#[test]
+fn fused_processing_matches_separate_passes_on_path() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(75);
+    let mut fused = HalfSpaceTrees::new(10, 6, &bounds, &mut rng);
+    for _ in 0..300 {
+        fused.insert(&[rng.random(), rng.random()]);
+    }
+    fused.set_depth_weighting(DepthWeighting::Harmonic);
+    let mut separate = HalfSpaceTrees::new(0, 6, &bounds, &mut rng);
+    for t in fused.trees() {
+        separate.add_tree(t.clone());
+    }
+
+    let (x, other) = ([0.3, 0.7], [0.9, 0.1]);
+    let off_path_before = fused.trees()[0].leaf_mass(&other);
+    let s_fused = fused.process_fused(&x, 0.9);
+    separate.decay(0.9);
+    let s_separate = separate.score(&x);
+    separate.insert(&x);
+
+    assert!((s_fused - s_separate).abs() < 1e-12);
+    for (a, b) in fused.trees().iter().zip(separate.trees()) {
+        assert!((a.total_mass() - b.total_mass()).abs() < 1e-9);
+        assert!((a.leaf_mass(&x) - b.leaf_mass(&x)).abs() < 1e-9);
+    }
+    // Off the path nothing was decayed.
+    assert_eq!(fused.trees()[0].leaf_mass(&other), off_path_before);
+}
+
+#[test]
+fn fused_processing_keeps_score_method_schedule_and_sources() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(145);
+    let built = HalfSpaceTrees::new(12, 6, &bounds, &mut rng);
+    let mut fused = HalfSpaceTrees::new(0, 6, &bounds, &mut rng);
+    let mut separate = HalfSpaceTrees::new(0, 6, &bounds, &mut rng);
+    for f in [&mut fused, &mut separate] {
+        for t in built.trees() {
+            f.add_tree(t.clone());
+        }
+        f.set_score_method(ScoreMethod::PooledMass);
+        f.set_weight_schedule(WeightSchedule::Decreasing { scale: 20.0 });
+        f.enable_source_tracking(2);
+        for i in 0..200 {
+            f.insert_tagged(&[0.3, 0.7], i % 2);
+        }
+    }
+    for _ in 0..50 {
+        // Repeating one point keeps every update on the same path.
+        let x = [0.3, 0.7];
+        let s_fused = fused.process_fused(&x, 0.95);
+        separate.decay(0.95);
+        let s_separate = separate.score(&x);
+        separate.insert(&x);
+        assert!(
+            (s_fused - s_separate).abs() < 1e-12,
+            "{s_fused} vs {s_separate}"
+        );
+    }
+    let (a, b) = (
+        fused.source_mass(&[0.3, 0.7]),
+        separate.source_mass(&[0.3, 0.7]),
+    );
+    assert_eq!(a.len(), b.len());
+    for ((ia, ma), (ib, mb)) in a.into_iter().zip(b) {
+        assert_eq!(ia, ib);
+        assert!((ma - mb).abs() < 1e-9, "{ma} vs {mb}");
+    }
+}
+
+#[test]
+fn ablating_the_anomalous_feature_drops_the_score_most() {
+    let bounds = vec![(0.0, 1.0); 3];
+    let mut rng = StdRng::seed_from_u64(76);
+    let mut forest = HalfSpaceTrees::new(30, 8, &bounds, &mut rng);
+    for _ in 0..2000 {
+        let x: Vec<f64> = (0..3).map(|_| rng.random_range(0.4..0.6)).collect();
+        forest.insert(&x);
+    }
+    let x = [0.5, 0.05, 0.5];
+    let base = forest.score(&x);
+    let ablated = forest.ablation_scores(&x);
+    assert_eq!(ablated.len(), 3);
+    let most = (0..3)
+        .min_by(|&a, &b| ablated[a].total_cmp(&ablated[b]))
+        .unwrap();
+    assert_eq!(most, 1);
+    assert!(ablated[1] < 0.5 * base);
+}
+
+#[test]
+fn budgeted_score_is_coarse_but_outlier_sensitive() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(77);
+    let mut forest = HalfSpaceTrees::new(20, 8, &bounds, &mut rng);
+    for _ in 0..2000 {
+        forest.insert(&[rng.random_range(0.4..0.6), rng.random_range(0.4..0.6)]);
+    }
+    let (inlier, outlier) = ([0.5, 0.5], [0.95, 0.05]);
+    let full = 20 * 9;
+    assert_eq!(
+        forest.score_with_budget(&inlier, full),
+        forest.score(&inlier)
+    );
+    assert_eq!(
+        forest.score_with_budget(&outlier, full),
+        forest.score(&outlier)
+    );
+
+    let tight = 20 * 3;
+    let (i, o) = (
+        forest.score_with_budget(&inlier, tight),
+        forest.score_with_budget(&outlier, tight),
+    );
+    assert_ne!(i, forest.score(&inlier));
+    assert!(o > i, "outlier {o} inlier {i}");
+}
+
+#[test]
+fn js_divergence_is_higher_for_outliers() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(78);
+    let mut forest = HalfSpaceTrees::new(20, 6, &bounds, &mut rng);
+    assert_eq!(forest.js_anomaly(&[0.5, 0.5]), std::f64::consts::LN_2);
+    for _ in 0..1000 {
+        forest.insert(&[rng.random_range(0.4..0.6), rng.random_range(0.4..0.6)]);
+    }
+    let inlier = forest.js_anomaly(&[0.5, 0.5]);
+    let outlier = forest.js_anomaly(&[0.95, 0.05]);
+    assert!(inlier < outlier);
+    assert!(inlier > 0.0 && outlier <= std::f64::consts::LN_2 + 1e-12);
+
+    let mut single = HalfSpaceTrees::new(3, 4, &bounds, &mut rng);
+    single.insert(&[0.2, 0.2]);
+    assert!(single.js_anomaly(&[0.2, 0.2]).abs() < 1e-12);
+}
+
+#[test]
+fn validation_driven_decay_forgets_contamination() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(79);
+    let normal = |rng: &mut StdRng| vec![rng.random_range(0.2..0.5), rng.random_range(0.2..0.5)];
+    let odd = |rng: &mut StdRng| vec![rng.random_range(0.8..0.9), rng.random_range(0.8..0.9)];
+    let mut stream: Vec<Vec<f64>> = (0..1000).map(|_| normal(&mut rng)).collect();
+    stream.extend((0..800).map(|_| odd(&mut rng)));
+    stream.extend((0..1500).map(|_| normal(&mut rng)));
+    let mut val: Vec<Vec<f64>> = (0..50).map(|_| normal(&mut rng)).collect();
+    val.extend((0..50).map(|_| odd(&mut rng)));
+    let labels: Vec<bool> = (0..100).map(|i| i >= 50).collect();
+
+    let built = HalfSpaceTrees::new(20, 7, &bounds, &mut StdRng::seed_from_u64(1));
+    let mut fixed = HalfSpaceTrees::new(0, 7, &bounds, &mut rng);
+    let mut adaptive = HalfSpaceTrees::new(0, 7, &bounds, &mut rng);
+    for t in built.trees() {
+        fixed.add_tree(t.clone());
+        adaptive.add_tree(t.clone());
+    }
+    for (i, x) in stream.iter().enumerate() {
+        fixed.insert(x);
+        if (i + 1) % 100 == 0 {
+            fixed.decay(0.9999f64.powi(100));
+        }
+    }
+    let final_alpha = adaptive.train_with_validation(stream, 0.9999, &val, &labels);
+    assert!(final_alpha < 0.9999);
+
+    let auc = |f: &HalfSpaceTrees| {
+        let scores: Vec<f64> = val.iter().map(|v| f.score(v)).collect();
+        half_space_trees::eval::auc(&scores, &labels)
+    };
+    assert!(
+        auc(&adaptive) > auc(&fixed) + 0.1,
+        "{} vs {}",
+        auc(&adaptive),
+        auc(&fixed)
+    );
+}
+
+#[test]
+fn validation_training_decays_a_trailing_partial_interval() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(141);
+    let stream: Vec<Vec<f64>> = (0..150).map(|_| vec![rng.random(), rng.random()]).collect();
+    let val = vec![vec![0.5, 0.5], vec![0.95, 0.95]];
+    let labels = [false, true];
+
+    let built = HalfSpaceTrees::new(5, 6, &bounds, &mut rng);
+    let mut manual = HalfSpaceTrees::new(0, 6, &bounds, &mut rng);
+    let mut trained = HalfSpaceTrees::new(0, 6, &bounds, &mut rng);
+    for t in built.trees() {
+        manual.add_tree(t.clone());
+        trained.add_tree(t.clone());
+    }
+    for chunk in stream.chunks(100) {
+        chunk.iter().for_each(|x| manual.insert(x));
+        manual.decay(0.99f64.powi(chunk.len() as i32));
+    }
+    trained.train_with_validation(stream, 0.99, &val, &labels);
+    for t in 0..5 {
+        assert_eq!(
+            trained.trees()[t].total_mass(),
+            manual.trees()[t].total_mass()
+        );
+    }
+}
+
+#[test]
+fn contribution_entropy_separates_broad_from_lone_detections() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(80);
+    let x = [0.9, 0.1];
+    let cluster: Vec<Vec<f64>> = (0..500)
+        .map(|_| vec![rng.random_range(0.4..0.6), rng.random_range(0.4..0.6)])
+        .collect();
+
+    let mut broad = HalfSpaceTrees::new(10, 6, &bounds, &mut rng);
+    let mut lone = HalfSpaceTrees::new(9, 6, &bounds, &mut rng);
+    for p in &cluster {
+        broad.insert(p);
+        lone.insert(p);
+    }
+    for _ in 0..500 {
+        lone.insert(&x);
+    }
+    let mut fresh = HalfSpaceTree::new(6, &bounds, &mut rng);
+    for p in &cluster {
+        fresh.insert(p);
+    }
+    lone.add_tree(fresh);
+
+    let (b, l) = (
+        broad.contribution_entropy(&x),
+        lone.contribution_entropy(&x),
+    );
+    assert!(b > l, "broad {b} lone {l}");
+    assert!(b <= 10f64.ln() + 1e-12);
+}
+
+#[test]
+fn minimal_perturbation_reaches_the_target_with_few_edits() {
+    let bounds = vec![(0.0, 1.0); 4];
+    let mut rng = StdRng::seed_from_u64(81);
+    let mut forest = HalfSpaceTrees::new(20, 6, &bounds, &mut rng);
+    let data: Vec<Vec<f64>> = (0..1000)
+        .map(|_| (0..4).map(|_| rng.random_range(0.3..0.7)).collect())
+        .collect();
+    for x in &data {
+        forest.insert(x);
+    }
+    let target = forest.self_score_summary(&data).p95;
+    let x = [0.5, 0.97, 0.5, 0.5];
+    assert!(forest.score(&x) >= target);
+
+    let fixed = forest.minimal_perturbation_to_normal(&x, target).unwrap();
+    assert!(forest.score(&fixed) < target);
+    let changed: Vec<usize> = (0..4).filter(|&d| fixed[d] != x[d]).collect();
+    assert!(!changed.is_empty() && changed.len() <= 2, "{changed:?}");
+    assert!(changed.contains(&1));
+    assert!(fixed.iter().all(|v| (0.0..=1.0).contains(v)));
+
+    assert_eq!(forest.minimal_perturbation_to_normal(&x, 0.0), None);
+}
+
+#[test]
+fn leaf_table_mirrors_tree_state() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(82);
+    let mut forest = HalfSpaceTrees::new(5, 4, &bounds, &mut rng);
+    for _ in 0..100 {
+        forest.insert(&[rng.random(), rng.random()]);
+    }
+    let table = forest.leaf_table();
+    assert_eq!(table.len(), 5 * 16);
+    for (i, t) in forest.trees().iter().enumerate() {
+        let rows: Vec<_> = table.iter().filter(|r| r.tree == i).collect();
+        let masses: Vec<f64> = rows.iter().map(|r| r.mass).collect();
+        assert_eq!(masses, t.leaf_masses());
+        let volumes: Vec<f64> = rows.iter().map(|r| r.volume).collect();
+        assert_eq!(volumes, t.leaf_volumes());
+        assert!(
+            rows.iter()
+                .enumerate()
+                .all(|(j, r)| r.leaf == j && r.depth == 4)
+        );
+        assert!(rows.iter().all(|r| r.rarity == 1.0 / (1.0 + r.mass)));
+    }
+}
+
+#[test]
+fn rankings_survive_mild_decay_on_stationary_data() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(83);
+    let mut forest = HalfSpaceTrees::new(20, 6, &bounds, &mut rng);
+    for _ in 0..2000 {
+        forest.insert(&[rng.random::<f64>().powi(2), rng.random()]);
+    }
+    let probe: Vec<Vec<f64>> = (0..100).map(|_| vec![rng.random(), rng.random()]).collect();
+    let mild = forest.ranking_stability(&probe, 0.95);
+    assert!(mild > 0.95, "{mild}");
+    assert!(mild <= 1.0 + 1e-12);
+}
+
+#[test]
+fn separating_candidates_gain_and_blind_ones_do_not() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(84);
+    let normal = |rng: &mut StdRng| vec![rng.random_range(0.4..0.6), rng.random_range(0.4..0.6)];
+    let mut forest = HalfSpaceTrees::new(5, 6, &bounds, &mut rng);
+    for _ in 0..500 {
+        forest.insert(&[rng.random(), rng.random()]);
+    }
+    let mut trained = HalfSpaceTree::new(6, &bounds, &mut rng);
+    for _ in 0..500 {
+        trained.insert(&normal(&mut rng));
+    }
+    let blind = HalfSpaceTree::new(6, &bounds, &mut rng);
+
+    let mut probe = Vec::new();
+    let mut labels = Vec::new();
+    for i in 0..100 {
+        let anomaly = i % 5 == 0;
+        probe.push(if anomaly {
+            vec![rng.random_range(0.8..1.0), rng.random_range(0.0..0.2)]
+        } else {
+            normal(&mut rng)
+        });
+        labels.push(anomaly);
+    }
+    let gains = forest.candidate_gain(&[trained, blind], &probe, &labels);
+    assert!(gains[0] > 0.1, "{gains:?}");
+    assert!(gains[1].abs() < 1e-9, "{gains:?}");
+}
+
+#[test]
+fn recent_activity_tracks_inserts_since_snapshot() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(85);
+    let mut forest = HalfSpaceTrees::new(10, 6, &bounds, &mut rng);
+    for _ in 0..500 {
+        forest.insert(&[rng.random(), rng.random()]);
+    }
+    let reference = forest.leaf_masses();
+    let (hot, quiet) = ([0.2, 0.8], [0.8, 0.2]);
+    assert_eq!(forest.recent_activity(&hot, &reference), 0.0);
+
+    for _ in 0..200 {
+        forest.insert(&hot);
+    }
+    assert_eq!(forest.recent_activity(&hot, &reference), 200.0);
+    assert!(forest.recent_activity(&quiet, &reference) < 1.0);
+}
+
+#[test]
+fn ensure_diversity_breaks_up_duplicate_trees() {
+    let bounds = vec![(0.0, 1.0); 3];
+    let mut rng = StdRng::seed_from_u64(86);
+    let mut forest = HalfSpaceTrees::new(1, 5, &bounds, &mut rng);
+    let base = HalfSpaceTree::new(5, &bounds, &mut rng);
+    for _ in 0..7 {
+        forest.add_tree(base.clone());
+    }
+    for _ in 0..1000 {
+        forest.insert(&[rng.random(), rng.random(), rng.random::<f64>().powi(3)]);
+    }
+    let probe: Vec<Vec<f64>> = (0..100)
+        .map(|_| (0..3).map(|_| rng.random()).collect())
+        .collect();
+    let mean_pairwise = |forest: &HalfSpaceTrees| {
+        let m = forest.tree_correlation(&probe);
+        let n = m.len();
+        let off: f64 = (0..n)
+            .flat_map(|i| (0..n).filter(move |&j| j != i).map(move |j| (i, j)))
+            .map(|(i, j)| m[i][j])
+            .sum();
+        off / (n * (n - 1)) as f64
+    };
+    let target = 0.5;
+    let before = mean_pairwise(&forest);
+    assert!(before > target, "{before}");
+
+    let redrawn = forest.ensure_diversity(&probe, target, &mut rng);
+    let after = mean_pairwise(&forest);
+    assert!(redrawn > 0);
+    assert!(after < target, "{before} -> {after}");
+    assert_eq!(forest.ensure_diversity(&probe, 1.0, &mut rng), 0);
+}
+
+#[test]
+fn quadrant_mass_partitions_total() {
+    let bounds = vec![(0.0, 1.0), (-1.0, 1.0), (0.0, 10.0)];
+    let mut rng = StdRng::seed_from_u64(87);
+    let mut forest = HalfSpaceTrees::new(10, 9, &bounds, &mut rng);
+    for i in 0..1000 {
+        // Three quarters of the stream sits in the (high, low) quadrant of dims 0 and 1.
+        let x = if i % 4 == 0 {
+            vec![rng.random_range(0.0..0.5), rng.random_range(0.0..1.0), 5.0]
+        } else {
+            vec![rng.random_range(0.5..1.0), rng.random_range(-1.0..0.0), 5.0]
+        };
+        forest.insert(&x);
+    }
+    let quadrants = forest.quadrant_mass(&[0, 1]);
+    assert_eq!(quadrants.len(), 4);
+    let patterns: Vec<Vec<bool>> = quadrants.iter().map(|(p, _)| p.clone()).collect();
+    assert_eq!(
+        patterns,
+        vec![
+            vec![false, false],
+            vec![false, true],
+            vec![true, false],
+            vec![true, true]
+        ]
+    );
+    let total: f64 = quadrants.iter().map(|(_, m)| m).sum();
+    assert!((total - 1000.0).abs() < 1e-6, "{total}");
+    let busiest = quadrants.iter().max_by(|a, b| a.1.total_cmp(&b.1)).unwrap();
+    assert_eq!(busiest.0, vec![true, false]);
+    assert!(busiest.1 > 500.0, "{quadrants:?}");
+}
+
+#[test]
+fn from_data_never_splits_on_constant_dims() {
+    let mut rng = StdRng::seed_from_u64(88);
+    let data: Vec<Vec<f64>> = (0..500)
+        .map(|i| {
+            vec![
+                rng.random(),
+                3.0,
+                1.0 + 1e-12 * (i % 2) as f64,
+                rng.random(),
+            ]
+        })
+        .collect();
+    let (forest, constant) = HalfSpaceTrees::from_data(&data, 15, 6, 1e-9, &mut rng);
+    assert_eq!(constant, vec![false, true, true, false]);
+    assert!(forest.score_for_dim(&data[0], 1).is_nan());
+    assert!(forest.score_for_dim(&data[0], 2).is_nan());
+    assert!(!forest.score_for_dim(&data[0], 0).is_nan());
+    assert!(forest.score(&[2.0, 3.0, 1.0, 2.0]) > forest.score(&[0.5, 3.0, 1.0, 0.5]));
+
+    let mut tree = HalfSpaceTree::with_split_mode(
+        6,
+        &[(0.0, 1.0), (3.0, 3.0), (0.0, 1.0)],
+        SplitMode::Oblique,
+        &mut rng,
+    );
+    tree.insert(&[0.5, 3.0, 0.5]);
+    assert!(!tree.uses_dim(1));
+}
+
+#[test]
+fn expected_uniform_mass_follows_leaf_volume() {
+    let bounds = vec![(0.0, 2.0), (-1.0, 1.0)];
+    let mut rng = StdRng::seed_from_u64(89);
+    let tree = HalfSpaceTree::new(5, &bounds, &mut rng);
+    let expected = tree.expected_uniform_mass(640.0);
+    assert_eq!(expected.len(), 32);
+    let total: f64 = expected.iter().sum();
+    assert!((total - 640.0).abs() < 1e-9, "{total}");
+    for (e, v) in expected.iter().zip(tree.leaf_volumes()) {
+        assert!((e - 640.0 * v / 4.0).abs() < 1e-9);
+    }
+
+    // Halve the box on each axis in turn: four equal quarters.
+    let forest = HalfSpaceTrees::new(1, 2, &bounds, &mut rng);
+    let (mut blob, layout) = forest.to_flat_f32().unwrap();
+    for (node, (dim, value)) in [(0, (0.0, 1.0)), (1, (1.0, 0.0)), (2, (1.0, 0.0))] {
+        let at = layout.header_len + node * layout.node_stride;
+        blob[at + layout.dim_offset] = dim;
+        blob[at + layout.value_offset] = value;
+    }
+    let quarters = HalfSpaceTrees::from_flat_f32(&blob, layout).unwrap();
+    let tree = &quarters.trees()[0];
+    assert_eq!(tree.expected_uniform_mass(100.0), vec![25.0; 4]);
+}
+
+#[test]
+fn insert_path_matches_insert() {
+    let bounds = vec![(0.0, 1.0); 3];
+    let mut rng = StdRng::seed_from_u64(90);
+    let mut direct = HalfSpaceTree::new(7, &bounds, &mut rng);
+    let mut replayed = direct.clone();
+    let points: Vec<Vec<f64>> = (0..200)
+        .map(|_| (0..3).map(|_| rng.random()).collect())
+        .collect();
+    let paths: Vec<Vec<bool>> = points.iter().map(|x| direct.path(x)).collect();
+    for (x, path) in points.iter().zip(&paths) {
+        assert_eq!(path.len(), 7);
+        direct.insert_weighted(x, 1.5);
+        replayed.insert_path(path, 1.5);
+    }
+    assert_eq!(replayed.leaf_masses(), direct.leaf_masses());
+    assert_eq!(replayed.total_mass(), direct.total_mass());
+    for x in &points {
+        assert_eq!(replayed.score(x), direct.score(x));
+    }
+}
+
+#[test]
+#[should_panic(expected = "path ends above a leaf")]
+fn insert_path_rejects_wrong_length() {
+    let mut rng = StdRng::seed_from_u64(91);
+    let mut tree = HalfSpaceTree::new(4, &[(0.0, 1.0)], &mut rng);
+    tree.insert_path(&[true, false], 1.0);
+}
+
+#[test]
+fn compare_to_baseline_is_spearman() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(92);
+    let mut forest = HalfSpaceTrees::new(10, 6, &bounds, &mut rng);
+    for _ in 0..500 {
+        forest.insert(&[rng.random_range(0.3..0.7), rng.random_range(0.3..0.7)]);
+    }
+    let probe = vec![
+        vec![0.5, 0.5],
+        vec![0.6, 0.4],
+        vec![0.75, 0.25],
+        vec![0.95, 0.05],
+    ];
+    let scores: Vec<f64> = probe.iter().map(|x| forest.score(x)).collect();
+    let mut order: Vec<usize> = (0..4).collect();
+    order.sort_by(|&a, &b| scores[a].total_cmp(&scores[b]));
+    assert!(order.windows(2).all(|w| scores[w[0]] < scores[w[1]]));
+
+    // Monotone transforms agree fully, reversals fully disagree.
+    assert!((forest.compare_to_baseline(|x| forest.score(x).exp(), &probe) - 1.0).abs() < 1e-12);
+    assert!((forest.compare_to_baseline(|x| -forest.score(x), &probe) + 1.0).abs() < 1e-12);
+    assert_eq!(forest.compare_to_baseline(|_| 3.0, &probe), 0.0);
+
+    // Swapping the two highest ranks: rho = 1 - 6 * 2 / (4 * 15) = 0.8.
+    let mut swapped = [0.0; 4];
+    for (rank, &i) in order.iter().enumerate() {
+        swapped[i] = rank as f64;
+    }
+    swapped.swap(order[2], order[3]);
+    let rank_of = |x: &[f64]| swapped[probe.iter().position(|p| p == x).unwrap()];
+    assert!((forest.compare_to_baseline(rank_of, &probe) - 0.8).abs() < 1e-12);
+}
+
+#[test]
+fn amortized_decay_matches_global_decay_per_cycle() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(93);
+    let mut global = HalfSpaceTrees::new(6, 5, &bounds, &mut rng);
+    for _ in 0..300 {
+        global.insert(&[rng.random(), rng.random()]);
+    }
+    let masses = |f: &HalfSpaceTrees| f.leaf_masses();
+    let (blob, layout) = global.to_flat_f32().unwrap();
+    let mut amortized = HalfSpaceTrees::from_flat_f32(&blob, layout).unwrap();
+    assert_eq!(masses(&amortized), masses(&global));
+
+    for cycle in 0..3 {
+        for _ in 0..6 {
+            amortized.amortized_decay_tick(0.99);
+            global.decay(0.99);
+        }
+        for (a, g) in masses(&amortized).iter().zip(masses(&global)) {
+            assert!(
+                (a - g).abs() < 1e-9 * g.max(1.0),
+                "cycle {cycle}: {a} vs {g}"
+            );
+        }
+    }
+
+    // Mid-cycle only the visited trees have moved.
+    let before = amortized.trees()[3].total_mass();
+    amortized.amortized_decay_tick(0.99);
+    assert!(amortized.trees()[0].total_mass() < global.trees()[0].total_mass());
+    assert_eq!(amortized.trees()[3].total_mass(), before);
+}
+
+#[test]
+fn unstable_trees_flags_near_splits() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(94);
+    // Five stumps splitting dim 0 at fixed points; the leaves' dummy splits sit right on
+    // the probe but never route, so they must not count.
+    let forest = HalfSpaceTrees::new(5, 1, &bounds, &mut rng);
+    let (mut blob, layout) = forest.to_flat_f32().unwrap();
+    for (tree, split) in [0.3f32, 0.5, 0.5004, 0.7, 0.4995].into_iter().enumerate() {
+        let root = layout.header_len + tree * layout.tree_stride;
+        blob[root + layout.dim_offset] = 0.0;
+        blob[root + layout.value_offset] = split;
+        for leaf in 1..3 {
+            let at = root + leaf * layout.node_stride;
+            blob[at + layout.dim_offset] = 1.0;
+            blob[at + layout.value_offset] = 0.25;
+        }
+    }
+    let forest = HalfSpaceTrees::from_flat_f32(&blob, layout).unwrap();
+    let x = [0.5, 0.25];
+    assert_eq!(forest.unstable_trees(&x, 1e-3), vec![1, 2, 4]);
+    assert_eq!(forest.unstable_trees(&x, 1e-5), vec![1]);
+    assert_eq!(forest.unstable_trees(&x, 0.25), vec![0, 1, 2, 3, 4]);
+    assert!(forest.unstable_trees(&[0.05, 0.25], 0.1).is_empty());
+}
+
+#[test]
+fn effective_trees_counts_contributing_trees() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(95);
+    let mut forest = HalfSpaceTrees::new(9, 6, &bounds, &mut rng);
+    let x = [0.4, 0.6];
+    // Untrained, every tree scores `x` alike.
+    assert!((forest.effective_trees(&x) - 9.0).abs() < 1e-9);
+
+    for _ in 0..1000 {
+        forest.insert(&x);
+    }
+    assert!((forest.effective_trees(&x) - 9.0).abs() < 1e-9);
+    // A fresh tree still sees `x` as maximally rare and carries almost all of the score.
+    forest.add_tree(HalfSpaceTree::new(6, &bounds, &mut rng));
+    let dominated = forest.effective_trees(&x);
+    assert!(dominated > 1.0 && dominated < 1.1, "{dominated}");
+}
+
+#[test]
+fn dual_timescale_treats_recent_shift_as_relatively_normal() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(96);
+    let recent = HalfSpaceTrees::new(15, 6, &bounds, &mut rng);
+    let baseline = HalfSpaceTrees::new(15, 6, &bounds, &mut rng);
+    let mut dual = DualTimescaleForest::new(recent, baseline, 0.99, 0.9999);
+    let near = |c: f64, rng: &mut StdRng| [c + rng.random_range(-0.05..0.05), c];
+    for _ in 0..3000 {
+        dual.insert(&near(0.25, &mut rng));
+    }
+    // The stream moves to a new pattern; the baseline has seen a little of it by now.
+    for _ in 0..500 {
+        dual.insert(&near(0.75, &mut rng));
+    }
+    let (old, new) = ([0.25, 0.25], [0.75, 0.75]);
+    assert!(dual.baseline().score(&new) < dual.baseline().score(&[0.9, 0.1]));
+    assert!(dual.relative_recent_score(&new) < 0.0);
+    assert!(dual.relative_recent_score(&old) > 0.0);
+}
+
+#[test]
+fn sanitize_masses_repairs_corrupt_imports() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(97);
+    let mut forest = HalfSpaceTrees::new(3, 3, &bounds, &mut rng);
+    for _ in 0..100 {
+        forest.insert(&[rng.random(), rng.random()]);
+    }
+    let (mut blob, layout) = forest.to_flat_f32().unwrap();
+    let mass_at = |tree: usize, node: usize| {
+        layout.header_len
+            + tree * layout.tree_stride
+            + node * layout.node_stride
+            + layout.mass_offset
+    };
+    blob[mass_at(0, 0)] = f32::NAN;
+    blob[mass_at(1, 7)] = -4.0;
+    blob[mass_at(2, 14)] = f32::INFINITY;
+    blob[mass_at(2, 3)] = f32::NEG_INFINITY;
+    let mut imported = HalfSpaceTrees::from_flat_f32(&blob, layout).unwrap();
+
+    assert_eq!(imported.sanitize_masses(), 4);
+    assert_eq!(imported.trees()[0].total_mass(), 0.0);
+    assert_eq!(imported.trees()[1].leaf_masses()[0], 0.0);
+    assert_eq!(imported.trees()[2].leaf_masses()[7], 0.0);
+    assert!(
+        imported
+            .leaf_masses()
+            .iter()
+            .all(|m| m.is_finite() && *m >= 0.0)
+    );
+    assert_eq!(imported.sanitize_masses(), 0);
+    assert!(imported.score(&[0.5, 0.5]).is_finite());
+}
+
+#[test]
+fn dropout_pulls_scores_toward_the_marginal() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(98);
+    let mut forest = HalfSpaceTrees::new(15, 6, &bounds, &mut rng);
+    for _ in 0..1000 {
+        forest.insert(&[rng.random_range(0.4..0.6), rng.random_range(0.4..0.6)]);
+    }
+    let x = [0.95, 0.9];
+    let full = forest.score_with_dropout(&x, 0.0, 5, &mut rng);
+    assert!((full - forest.score(&x)).abs() < 1e-12);
+    let some = forest.score_with_dropout(&x, 0.5, 400, &mut rng);
+    let all = forest.score_with_dropout(&x, 1.0, 5, &mut rng);
+    assert!(full > some && some > all, "{full} {some} {all}");
+    // With every dimension missing only the mass-weighted average remains, which is
+    // the same for any point.
+    let elsewhere = forest.score_with_dropout(&[0.1, 0.5], 1.0, 1, &mut rng);
+    assert!((all - elsewhere).abs() < 1e-12);
+}
+
+#[test]
+fn occupancy_controller_holds_mean_leaf_mass() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(99);
+    let mut forest = HalfSpaceTrees::new(10, 6, &bounds, &mut rng);
+    let mean_leaf_mass = |f: &HalfSpaceTrees| {
+        let masses = f.leaf_masses();
+        masses.iter().sum::<f64>() / masses.len() as f64
+    };
+    let target = 2.0;
+    let mut scores = Vec::new();
+    for i in 0..20_000 {
+        scores.push(forest.process_occupancy_controlled(&[rng.random(), rng.random()], target));
+        if i == 50 {
+            // Still filling: nothing decayed yet.
+            assert!((mean_leaf_mass(&forest) - 51.0 / 64.0).abs() < 1e-9);
+        }
+    }
+    let settled = mean_leaf_mass(&forest);
+    assert!((settled - target).abs() < 1e-6, "{settled}");
+    // The score range holds steady once the target is reached.
+    let (a, b) = (&scores[5000..6000], &scores[19_000..]);
+    let mean = |s: &[f64]| s.iter().sum::<f64>() / s.len() as f64;
+    assert!(
+        (mean(a) - mean(b)).abs() < 0.05 * mean(a),
+        "{} {}",
+        mean(a),
+        mean(b)
+    );
+}
+
+#[test]
+fn occupancy_controller_follows_schedule_and_merged_leaves() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(147);
+    let mut tree = HalfSpaceTree::new(6, &bounds, &mut rng);
+    for _ in 0..2000 {
+        tree.insert(&[rng.random_range(0.0..0.5), rng.random()]);
+    }
+    tree.merge_similar_leaves(0.3);
+    let mut forest = HalfSpaceTrees::new(0, 6, &bounds, &mut rng);
+    forest.add_tree(tree);
+    forest.set_weight_schedule(WeightSchedule::Increasing { scale: 100.0 });
+    for _ in 0..500 {
+        forest.process_occupancy_controlled(&[rng.random(), rng.random()], 2.0);
+    }
+    let masses = forest.leaf_masses();
+    assert!(masses.len() < 64, "{}", masses.len());
+    let mean = masses.iter().sum::<f64>() / masses.len() as f64;
+    assert!((mean - 2.0).abs() < 1e-9, "{mean}");
+}
+
+#[test]
+#[should_panic(expected = "target_leaf_mass too small")]
+fn occupancy_controller_rejects_unreachable_targets() {
+    let mut forest = HalfSpaceTrees::new(2, 6, &[(0.0, 1.0); 2], &mut StdRng::seed_from_u64(148));
+    forest.process_occupancy_controlled(&[0.5, 0.5], 0.01);
+}
+
+#[test]
+fn most_informative_split_hugs_the_cluster() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let (cx, cy) = ((0.6, 0.65), (0.3, 0.35));
+    for seed in 100..110 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut tree = HalfSpaceTree::new(8, &bounds, &mut rng);
+        for _ in 0..1000 {
+            tree.insert(&[rng.random_range(cx.0..cx.1), rng.random_range(cy.0..cy.1)]);
+        }
+        let (dim, val, depth) = tree.most_informative_split();
+        let (lo, hi) = if dim == 0 { cx } else { cy };
+        // Random splits rarely land right at the edge, but the chosen one never cuts the
+        // cluster and sits close by.
+        assert!(
+            !(lo..=hi).contains(&val),
+            "dim {dim} split {val} at depth {depth}"
+        );
+        assert!(val > lo - 0.25 && val < hi + 0.25, "dim {dim} split {val}");
+        assert!(depth < 8);
+    }
+}
+
+#[test]
+fn max_repeats_caps_dimension_use_per_path() {
+    let bounds = vec![(0.0, 1.0); 3];
+    let mut rng = StdRng::seed_from_u64(110);
+    let mut forest = HalfSpaceTrees::with_max_repeats(5, 7, &bounds, 3, &mut rng);
+    let max_per_path = |forest: &HalfSpaceTrees, tree: usize| {
+        let (blob, layout) = forest.to_flat_f32().unwrap();
+        let dim = |node: usize| {
+            blob[layout.header_len
+                + tree * layout.tree_stride
+                + node * layout.node_stride
+                + layout.dim_offset] as usize
+        };
+        // Walk every root-to-leaf path over the heap-ordered internal nodes.
+        let first_leaf = (1 << layout.max_depth) - 1;
+        (first_leaf..layout.nodes_per_tree)
+            .map(|leaf| {
+                let mut counts = [0; 3];
+                let mut node = leaf;
+                while node > 0 {
+                    node = (node - 1) / 2;
+                    counts[dim(node)] += 1;
+                }
+                *counts.iter().max().unwrap()
+            })
+            .max()
+            .unwrap()
+    };
+    for tree in 0..5 {
+        assert!(max_per_path(&forest, tree) <= 3);
+    }
+    // Redrawn trees keep the cap.
+    forest.rebuild(&mut rng);
+    for tree in 0..5 {
+        assert!(max_per_path(&forest, tree) <= 3);
+    }
+
+    // A cap that never binds draws exactly the unconstrained tree.
+    let a = HalfSpaceTree::with_max_repeats(7, &bounds, 7, &mut StdRng::seed_from_u64(5));
+    let b = HalfSpaceTree::new(7, &bounds, &mut StdRng::seed_from_u64(5));
+    assert!(a.is_compatible_with(&b));
+}
+
+#[test]
+#[should_panic(expected = "max_repeats_per_dim too small")]
+fn max_repeats_must_fill_the_depth() {
+    let mut rng = StdRng::seed_from_u64(111);
+    HalfSpaceTree::with_max_repeats(7, &[(0.0, 1.0); 3], 2, &mut rng);
+}
+
+#[test]
+fn coverage_gaps_flag_unseen_ranges() {
+    let bounds = vec![(0.0, 1.0), (0.0, 10.0)];
+    let mut rng = StdRng::seed_from_u64(112);
+    let mut forest = HalfSpaceTrees::new(20, 10, &bounds, &mut rng);
+    assert_eq!(forest.coverage_gaps(1, 4), vec![true; 4]);
+    for _ in 0..3000 {
+        forest.insert(&[rng.random(), rng.random_range(0.0..5.0)]);
+    }
+    let gaps = forest.coverage_gaps(1, 10);
+    assert_eq!(&gaps[..5], &[false; 5]);
+    // Leaves straddling 5.0 can reach a little past it; the far end is never covered.
+    assert!(gaps[7..].iter().all(|&g| g), "{gaps:?}");
+    assert_eq!(forest.coverage_gaps(0, 5), vec![false; 5]);
+}
+
+#[test]
+fn estimate_modes_counts_clusters() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(113);
+    let mut forest = HalfSpaceTrees::new(25, 8, &bounds, &mut rng);
+    assert_eq!(forest.estimate_modes(0, 20), 0);
+    for i in 0..4000 {
+        let center = if i % 2 == 0 { 0.2 } else { 0.75 };
+        forest.insert(&[center + rng.random_range(-0.05..0.05), rng.random()]);
+    }
+    assert_eq!(forest.estimate_modes(0, 20), 2);
+    // The uniform dimension wiggles a little but has a single plateau.
+    assert_eq!(forest.estimate_modes(1, 20), 1);
+}
+
+#[test]
+fn expected_fpr_falls_with_threshold() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(114);
+    let mut forest = HalfSpaceTrees::new(25, 8, &bounds, &mut rng);
+    assert_eq!(forest.expected_fpr(0.0, 10, &mut rng), 0.0);
+    for _ in 0..2000 {
+        forest.insert(&[rng.random_range(0.3..0.7), rng.random()]);
+    }
+    let x = forest.sample_normal(&mut rng).unwrap();
+    assert!(x[0] >= 0.0 && x[0] <= 1.0 && x[1] >= 0.0 && x[1] <= 1.0);
+
+    // Sampled points land where mass is, so they look normal to the forest.
+    let typical = forest.score(&[0.5, 0.5]);
+    let fprs: Vec<f64> = [0.0, typical, 0.5, 1.0]
+        .iter()
+        .map(|&t| forest.expected_fpr(t, 2000, &mut StdRng::seed_from_u64(0)))
+        .collect();
+    assert_eq!(fprs[0], 1.0);
+    assert!(fprs.windows(2).all(|w| w[1] <= w[0]), "{fprs:?}");
+    assert!(fprs[2] < 0.5 * fprs[1], "{fprs:?}");
+    assert_eq!(fprs[3], 0.0);
+}
+
+#[test]
+fn reweight_to_marginal_moves_toward_target() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(115);
+    let mut forest = HalfSpaceTrees::new(20, 8, &bounds, &mut rng);
+    for _ in 0..3000 {
+        forest.insert(&[rng.random(), rng.random()]);
+    }
+    let target = [4.0, 3.0, 2.0, 1.0];
+    let l1 = |hist: Vec<f64>| -> f64 {
+        let total: f64 = hist.iter().sum();
+        hist.iter()
+            .zip(target)
+            .map(|(h, t)| (h / total - t / 10.0).abs())
+            .sum()
+    };
+    let before = l1(forest.marginal(0, 4));
+    let mass = forest.trees()[0].total_mass();
+    forest.reweight_to_marginal(0, &target);
+    let after = l1(forest.marginal(0, 4));
+    assert!(after < 0.5 * before, "{before} -> {after}");
+    assert!((forest.trees()[0].total_mass() - mass).abs() < 1e-6 * mass);
+    // The other dimension stays roughly uniform.
+    let other = forest.marginal(1, 4);
+    let mean = other.iter().sum::<f64>() / 4.0;
+    assert!(
+        other.iter().all(|m| (m - mean).abs() < 0.2 * mean),
+        "{other:?}"
+    );
+}
+
+#[test]
+fn score_decomposition_separates_rarity_from_forgetting() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(116);
+    let mut forest = HalfSpaceTrees::new(20, 8, &bounds, &mut rng);
+    let (visited, unseen) = ([0.1, 0.1], [0.9, 0.9]);
+    for _ in 0..50 {
+        forest.insert(&visited);
+    }
+    let (rarity, recency) = forest.score_decomposition(&visited);
+    assert_eq!(recency, 0.0);
+    assert!(rarity < 0.05);
+
+    // Forget nearly everything: both leaves now hold (almost) no mass.
+    forest.decay(1e-12);
+    let (seen_rarity, seen_recency) = forest.score_decomposition(&visited);
+    let (unseen_rarity, unseen_recency) = forest.score_decomposition(&unseen);
+    assert!((forest.score(&visited) - forest.score(&unseen)).abs() < 1e-9);
+    assert!((seen_rarity + seen_recency - forest.score(&visited)).abs() < 1e-12);
+    assert!(seen_recency > 0.9 && seen_rarity < 0.05);
+    assert_eq!(
+        (unseen_rarity, unseen_recency),
+        (forest.score(&unseen), 0.0)
+    );
+}
+
+#[test]
+fn consensus_sparse_region_finds_untrained_space() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(117);
+    let mut forest = HalfSpaceTrees::new(30, 8, &bounds, &mut rng);
+    assert_eq!(forest.consensus_sparse_region(4), bounds);
+    // Train on the left half only.
+    for _ in 0..3000 {
+        forest.insert(&[rng.random_range(0.0..0.5), rng.random()]);
+    }
+    let region = forest.consensus_sparse_region(10);
+    assert!(region[0].0 >= 0.5, "{region:?}");
+    assert_eq!(region[0].1, 1.0);
+    for x in [[0.95, 0.05], [0.95, 0.95]] {
+        assert!(
+            (0..2).all(|d| region[d].0 <= x[d] && x[d] <= region[d].1),
+            "{region:?}"
+        );
+    }
+}
+
+#[test]
+fn decreasing_weight_schedule_favours_early_inserts() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(118);
+    let mut forest = HalfSpaceTrees::new(10, 6, &bounds, &mut rng);
+    forest.set_weight_schedule(WeightSchedule::Decreasing { scale: 100.0 });
+    let mass = |f: &HalfSpaceTrees| f.trees()[0].total_mass();
+    let mut gained = Vec::new();
+    for _ in 0..4 {
+        let before = mass(&forest);
+        for _ in 0..100 {
+            forest.insert(&[rng.random(), rng.random()]);
+        }
+        gained.push(mass(&forest) - before);
+    }
+    assert!((gained[0] - 100.0 * 0.69).abs() < 1.0, "{gained:?}");
+    assert!(gained.windows(2).all(|w| w[1] < w[0]), "{gained:?}");
+
+    // Explicit weights bypass the schedule and its count.
+    let before = mass(&forest);
+    forest.insert_weighted(&[0.5, 0.5], 1.0);
+    assert_eq!(mass(&forest) - before, 1.0);
+    forest.set_weight_schedule(WeightSchedule::Increasing { scale: 100.0 });
+    let before = mass(&forest);
+    forest.insert(&[0.5, 0.5]);
+    assert!((mass(&forest) - before - 5.0).abs() < 1e-12);
+    assert_eq!(WeightSchedule::Constant.weight(1_000), 1.0);
+}
+
+#[test]
+fn score_monitor_divergence_rises_after_shift() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(119);
+    let mut forest = HalfSpaceTrees::new(20, 8, &bounds, &mut rng);
+    // Well-learned points score near 0, so resolve the low end finely.
+    let mut monitor = ScoreDistributionMonitor::new(20, 0.0, 0.2, 0.995);
+    let normal = |rng: &mut StdRng| [rng.random_range(0.4..0.6), rng.random_range(0.4..0.6)];
+    for _ in 0..2000 {
+        forest.process_monitored(&normal(&mut rng), &mut monitor);
+    }
+    let baseline = monitor.snapshot();
+    assert!((baseline.probabilities().iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    for _ in 0..300 {
+        forest.process_monitored(&normal(&mut rng), &mut monitor);
+    }
+    let steady = monitor.divergence_from(&baseline);
+
+    // Scores jump once the stream moves to a region the forest has not learned.
+    for _ in 0..300 {
+        let x = [rng.random_range(0.0..0.2), rng.random_range(0.8..1.0)];
+        forest.process_monitored(&x, &mut monitor);
+    }
+    let shifted = monitor.divergence_from(&baseline);
+    assert!(steady < 0.01, "{steady}");
+    assert!(shifted > 0.2, "{steady} -> {shifted}");
+    assert!(shifted <= 1.0);
+}
+
+#[test]
+fn pooled_mass_is_shared_by_bucket() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(120);
+    let mut forest = HalfSpaceTrees::new_pooled_mass(12, 6, &bounds, 5, &mut rng);
+    assert!(
+        HalfSpaceTrees::new(2, 3, &bounds, &mut rng)
+            .pooled_leaf_masses(&[0.5, 0.5])
+            .is_none()
+    );
+    let (probe, y) = ([0.3, 0.7], [0.8, 0.1]);
+    let before = forest.pooled_leaf_masses(&probe).unwrap();
+    assert!(before.iter().all(|&(_, m)| m == 0.0));
+    assert_eq!(forest.score(&probe), 1.0);
+
+    forest.insert(&y);
+    let y_buckets: Vec<usize> = forest
+        .pooled_leaf_masses(&y)
+        .unwrap()
+        .iter()
+        .map(|&(b, _)| b)
+        .collect();
+    // Each tree's insert lands in one bucket, and every tree whose leaf for the probe
+    // maps to that bucket sees it, whatever its own geometry.
+    let after = forest.pooled_leaf_masses(&probe).unwrap();
+    for ((b, m0), (b1, m1)) in before.into_iter().zip(after) {
+        assert_eq!(b, b1);
+        let hits = y_buckets.iter().filter(|&&yb| yb == b).count();
+        assert_eq!(m1 - m0, hits as f64);
+    }
+    assert!(forest.score(&probe) < 1.0);
+
+    forest.decay(0.5);
+    let total: f64 = forest
+        .pooled_leaf_masses(&y)
+        .unwrap()
+        .iter()
+        .map(|&(_, m)| m)
+        .sum();
+    assert!(total > 0.0);
+    forest.remove(&y);
+    assert!(
+        forest
+            .pooled_leaf_masses(&y)
+            .unwrap()
+            .iter()
+            .all(|&(_, m)| m <= 0.0)
+    );
+}
+
+#[test]
+fn pooled_mass_follows_every_mass_update() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(142);
+    let probe: Vec<Vec<f64>> = (0..40).map(|_| vec![rng.random(), rng.random()]).collect();
+    // With one bucket it holds every tree's total mass; with many, each tree's leaf
+    // shares its bucket at most with other leaves, so the bucket never holds less.
+    for n_buckets in [1, 4096] {
+        let check = |forest: &HalfSpaceTrees, step: &str| {
+            let total: f64 = forest.trees().iter().map(|t| t.total_mass()).sum();
+            for x in &probe {
+                let pooled = forest.pooled_leaf_masses(x).unwrap();
+                for (t, &(_, m)) in forest.trees().iter().zip(&pooled) {
+                    assert!(
+                        m >= t.leaf_mass(x) - 1e-9,
+                        "{step}: {m} < {}",
+                        t.leaf_mass(x)
+                    );
+                    if n_buckets == 1 {
+                        assert!((m - total).abs() < 1e-9 * total.max(1.0), "{step}");
+                    }
+                }
+            }
+        };
+        let mut rng = StdRng::seed_from_u64(143);
+        let mut forest = HalfSpaceTrees::new_pooled_mass(8, 5, &bounds, n_buckets, &mut rng);
+        for _ in 0..300 {
+            forest.insert(&[rng.random_range(0.2..0.6), rng.random()]);
+        }
+        check(&forest, "insert");
+        forest.decay_with_prior(0.9, 0.05);
+        check(&forest, "decay_with_prior");
+        for _ in 0..8 {
+            forest.amortized_decay_tick(0.99);
+        }
+        check(&forest, "amortized_decay_tick");
+        for _ in 0..50 {
+            forest.process_occupancy_controlled(&[rng.random(), rng.random()], 2.0);
+        }
+        check(&forest, "process_occupancy_controlled");
+        forest.rebuild(&mut rng);
+        check(&forest, "rebuild");
+        forest.ensure_diversity(&probe, 0.0, &mut rng);
+        check(&forest, "ensure_diversity");
+        let mut extra = HalfSpaceTree::new(5, &bounds, &mut rng);
+        for _ in 0..100 {
+            extra.insert(&[rng.random(), rng.random()]);
+        }
+        forest.add_tree(extra);
+        check(&forest, "add_tree");
+        forest.enforce_tree_cap(6);
+        check(&forest, "enforce_tree_cap");
+        forest.seed_from_density(|x| x[0], 500.0);
+        check(&forest, "seed_from_density");
+    }
+}
+
+#[test]
+#[should_panic(expected = "process_fused cannot decay a shared mass pool")]
+fn pooled_mass_rejects_fused_processing() {
+    let mut rng = StdRng::seed_from_u64(144);
+    let mut forest = HalfSpaceTrees::new_pooled_mass(4, 4, &[(0.0, 1.0); 2], 16, &mut rng);
+    forest.process_fused(&[0.5, 0.5], 0.99);
+}
+
+#[test]
+fn score_derivative_in_alpha_matches_finite_difference() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(121);
+    let mut forest = HalfSpaceTrees::new(15, 7, &bounds, &mut rng);
+    for _ in 0..500 {
+        forest.insert(&[rng.random_range(0.2..0.8), rng.random()]);
+    }
+    for weighting in [DepthWeighting::Linear, DepthWeighting::Harmonic] {
+        forest.set_depth_weighting(weighting);
+        for x in [[0.5, 0.5], [0.25, 0.9], [0.95, 0.05]] {
+            for (alpha, ticks) in [(0.99, 10), (0.9, 3), (0.5, 1)] {
+                let (score, grad) = forest.score_dscore_dalpha(&x, alpha, ticks);
+                assert_eq!(score, forest.score_at_alpha(&x, alpha, ticks));
+                let h = 1e-6;
+                let numeric = (forest.score_at_alpha(&x, alpha + h, ticks)
+                    - forest.score_at_alpha(&x, alpha - h, ticks))
+                    / (2.0 * h);
+                assert!(
+                    (grad - numeric).abs() <= 1e-5 * numeric.abs().max(1e-3),
+                    "{weighting:?} {x:?} {alpha}^{ticks}: {grad} vs {numeric}"
+                );
+                assert!(grad <= 0.0);
+            }
+        }
+    }
+    assert_eq!(forest.score_dscore_dalpha(&[0.5, 0.5], 0.9, 0).1, 0.0);
+    let (score, grad) = forest.score_dscore_dalpha(&[0.5, 0.5], 0.9, u32::MAX);
+    assert_eq!(score, forest.score_at_alpha(&[0.5, 0.5], 0.9, u32::MAX));
+    assert_eq!(grad, 0.0);
+}
+
+#[test]
+fn depth_mass_histogram_puts_mass_at_leaves() {
+    let bounds = vec![(0.0, 1.0); 3];
+    let mut rng = StdRng::seed_from_u64(122);
+    let mut forest = HalfSpaceTrees::new(8, 5, &bounds, &mut rng);
+    assert_eq!(forest.depth_mass_histogram(), vec![0.0; 6]);
+    for _ in 0..300 {
+        forest.insert(&[rng.random(), rng.random(), rng.random()]);
+    }
+    forest.decay(0.7);
+    let hist = forest.depth_mass_histogram();
+    assert_eq!(hist.len(), 6);
+    assert!(hist[..5].iter().all(|&m| m == 0.0));
+    let total: f64 = forest.trees().iter().map(|t| t.total_mass()).sum();
+    assert!((hist[5] - total).abs() < 1e-9 * total);
+    assert!((total - 8.0 * 300.0 * 0.7).abs() < 1e-6);
+}
+
+#[test]
+fn deepening_helps_with_fine_subclusters() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(123);
+    let mut forest = HalfSpaceTrees::new(25, 4, &bounds, &mut rng);
+    let centers = [0.2, 0.4, 0.6, 0.8];
+    for i in 0..4000 {
+        let (cx, cy) = (centers[i % 4], centers[(i / 4) % 4]);
+        forest.insert(&[
+            cx + rng.random_range(-0.02..0.02),
+            cy + rng.random_range(-0.02..0.02),
+        ]);
+    }
+    // Gaps between the sub-clusters, inside the overall envelope.
+    let probe: Vec<Vec<f64>> = [0.3, 0.5, 0.7]
+        .iter()
+        .flat_map(|&x| [0.3, 0.5, 0.7].map(|y| vec![x, y]))
+        .collect();
+    let before = forest.score(&probe[0]);
+    let benefit = forest.deepen_benefit(&probe, 4, &mut rng);
+    assert!(benefit > 0.0, "{benefit}");
+    assert_eq!(forest.score(&probe[0]), before);
+    assert_eq!(forest.depth_mass_histogram().len(), 5);
+
+    let deeper = forest.trees()[0].deepened(2, &mut rng);
+    assert_eq!(deeper.leaf_masses().len(), 64);
+    assert_eq!(deeper.total_mass(), forest.trees()[0].total_mass());
+}
+
+#[test]
+fn deepen_benefit_skips_trees_without_mass() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(138);
+    let mut forest = HalfSpaceTrees::new(5, 4, &bounds, &mut rng);
+    for _ in 0..500 {
+        forest.insert(&[rng.random_range(0.3..0.7), rng.random_range(0.3..0.7)]);
+    }
+    forest.add_tree(HalfSpaceTree::new(4, &bounds, &mut rng));
+    let benefit = forest.deepen_benefit(&[vec![0.9, 0.9]], 2, &mut rng);
+    assert!((-1.0..=1.0).contains(&benefit));
+}
+
+#[test]
+fn source_masses_sum_to_leaf_mass() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(124);
+    let mut forest = HalfSpaceTrees::new(10, 4, &bounds, &mut rng);
+    let x = [0.3, 0.6];
+    assert!(forest.source_mass(&x).is_empty());
+    forest.insert(&x);
+    forest.enable_source_tracking(2);
+    for i in 0..300 {
+        let p = [rng.random(), rng.random()];
+        forest.insert_tagged(&p, [7, 9, 11][i % 3]);
+        if i % 10 == 0 {
+            forest.insert(&x);
+        }
+    }
+    forest.insert_tagged(&x, 9);
+    forest.decay(0.8);
+
+    let breakdown = forest.source_mass(&x);
+    let leaf_mass: f64 = forest.trees().iter().map(|t| t.leaf_mass(&x)).sum::<f64>() / 10.0;
+    let total: f64 = breakdown.iter().map(|&(_, m)| m).sum();
+    assert!(
+        (total - leaf_mass).abs() < 1e-9,
+        "{breakdown:?} vs {leaf_mass}"
+    );
+    // Source 11 arrived after the cap of 2 and is folded into the untracked share.
+    let ids: Vec<u16> = breakdown.iter().map(|&(id, _)| id).collect();
+    assert!(!ids.contains(&11));
+    assert!(ids.contains(&9) && ids.contains(&HalfSpaceTrees::UNTRACKED_SOURCE));
+    assert!(breakdown.iter().all(|&(_, m)| m > 0.0));
+}
+
+#[test]
+fn source_masses_follow_added_and_evicted_trees() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(139);
+    let mut forest = HalfSpaceTrees::new(6, 4, &bounds, &mut rng);
+    forest.enable_source_tracking(4);
+    let stream = |forest: &mut HalfSpaceTrees, rng: &mut StdRng| {
+        for i in 0..200 {
+            forest.insert_tagged(&[rng.random(), rng.random()], i % 3);
+        }
+    };
+    stream(&mut forest, &mut rng);
+    forest.add_tree(HalfSpaceTree::new(4, &bounds, &mut rng));
+    stream(&mut forest, &mut rng);
+    forest.enforce_tree_cap(4);
+    stream(&mut forest, &mut rng);
+
+    // Every insert was tagged, so nothing may show up as untracked.
+    for _ in 0..100 {
+        let x = [rng.random(), rng.random()];
+        let breakdown = forest.source_mass(&x);
+        let n = forest.trees().len() as f64;
+        let leaf_mass: f64 = forest.trees().iter().map(|t| t.leaf_mass(&x)).sum::<f64>() / n;
+        let total: f64 = breakdown.iter().map(|&(_, m)| m).sum();
+        assert!((total - leaf_mass).abs() < 1e-9);
+        assert!(
+            breakdown
+                .iter()
+                .all(|&(id, _)| id != HalfSpaceTrees::UNTRACKED_SOURCE),
+            "{breakdown:?}"
+        );
+    }
+}
+
+#[test]
+fn lodo_scores_single_out_the_anomalous_dimension() {
+    let bounds = vec![(0.0, 1.0); 3];
+    let mut rng = StdRng::seed_from_u64(125);
+    let mut forest = HalfSpaceTrees::new(30, 8, &bounds, &mut rng);
+    for _ in 0..3000 {
+        let x: Vec<f64> = (0..3).map(|_| rng.random_range(0.35..0.65)).collect();
+        forest.insert(&x);
+    }
+    let x = [0.5, 0.95, 0.45];
+    let full = forest.score_with_dropout(&x, 0.0, 1, &mut rng);
+    let lodo = forest.lodo_scores(&x);
+    assert_eq!(lodo.len(), 3);
+    let reduction: Vec<f64> = lodo.iter().map(|s| full - s).collect();
+    let key = (0..3)
+        .max_by(|&a, &b| reduction[a].total_cmp(&reduction[b]))
+        .unwrap();
+    assert_eq!(key, 1, "{reduction:?}");
+    assert!(reduction[1] > 0.0);
+}
+
+#[test]
+fn combined_severity_requires_tree_consensus() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(126);
+    let mut forest = HalfSpaceTrees::new(20, 6, &bounds, &mut rng);
+    for _ in 0..2000 {
+        let x: Vec<f64> = (0..2).map(|_| rng.random_range(0.3..0.7)).collect();
+        forest.insert(&x);
+    }
+    let mut routine_max: f64 = 0.0;
+    for _ in 0..200 {
+        let x: Vec<f64> = (0..2).map(|_| rng.random_range(0.35..0.65)).collect();
+        routine_max = routine_max.max(forest.score(&x));
+        assert!(forest.combined_severity(&x, 0.5) < 0.5);
+    }
+
+    // Just outside the cluster only a few trees isolate the point; far away almost all do.
+    let (edge, far) = ([0.75, 0.5], [0.95, 0.95]);
+    assert!(forest.score(&edge) > routine_max && forest.score(&far) > routine_max);
+    let edge_severity = forest.combined_severity(&edge, 0.5);
+    let far_severity = forest.combined_severity(&far, 0.5);
+    assert!(edge_severity < 0.3, "{edge_severity}");
+    assert!(
+        far_severity > 2.0 * edge_severity,
+        "{far_severity} vs {edge_severity}"
+    );
+}
+
+#[test]
+fn surprise_is_high_in_sparse_regions_and_tracks_score() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(127);
+    let mut forest = HalfSpaceTrees::new(25, 6, &bounds, &mut rng);
+    for _ in 0..3000 {
+        let x: Vec<f64> = (0..2).map(|_| rng.random_range(0.3..0.7)).collect();
+        forest.insert(&x);
+    }
+    let (dense, sparse) = ([0.5, 0.5], [0.9, 0.1]);
+    assert!(forest.surprise(&sparse) > forest.surprise(&dense) + 2.0);
+    assert!(forest.surprise(&sparse).is_finite());
+
+    // Lowering the floor only raises surprise where leaves are empty.
+    let before = forest.surprise(&sparse);
+    forest.set_surprise_floor(1e-6);
+    assert!(forest.surprise(&sparse) > before);
+
+    let probe: Vec<Vec<f64>> = (0..200)
+        .map(|_| (0..2).map(|_| rng.random::<f64>()).collect())
+        .collect();
+    let rho = forest.compare_to_baseline(|x| forest.surprise(x), &probe);
+    assert!(rho > 0.8, "{rho}");
+}
+
+#[test]
+fn rarity_consensus_ranks_outliers_low_and_inliers_high() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(128);
+    let mut forest = HalfSpaceTrees::new(25, 6, &bounds, &mut rng);
+    for _ in 0..3000 {
+        let x: Vec<f64> = (0..2).map(|_| rng.random_range(0.3..0.7)).collect();
+        forest.insert(&x);
+    }
+    let inlier = forest.rarity_consensus(&[0.5, 0.5]);
+    let outlier = forest.rarity_consensus(&[0.95, 0.05]);
+    assert!(inlier > 0.8, "{inlier}");
+    assert!(outlier < 0.2, "{outlier}");
+}
+
+#[test]
+fn similar_sibling_leaves_merge_into_their_parent() {
+    let bounds = [(0.0, 1.0); 2];
+    let mut empty = HalfSpaceTree::new(6, &bounds, &mut StdRng::seed_from_u64(129));
+    empty.insert(&[0.3, 0.3]);
+    // Siblings off the inserted point's path are all empty and collapse; on it they differ.
+    empty.merge_similar_leaves(0.0);
+    assert_eq!(empty.leaf_masses().len(), 7);
+    assert_eq!(empty.total_mass(), 1.0);
+    assert_eq!(empty.score(&[0.3, 0.3]), 0.5);
+
+    let mut rng = StdRng::seed_from_u64(130);
+    let mut tree = HalfSpaceTree::new(6, &bounds, &mut rng);
+    for _ in 0..20_000 {
+        tree.insert(&[rng.random::<f64>(), rng.random::<f64>()]);
+    }
+    let before = tree.clone();
+    tree.merge_similar_leaves(0.2);
+    assert!(tree.leaf_masses().len() < before.leaf_masses().len());
+    assert!((tree.leaf_masses().iter().sum::<f64>() - 20_000.0).abs() < 1e-9);
+
+    let (mut merged, mut kept) = (0, 0);
+    for _ in 0..500 {
+        let x = [rng.random::<f64>(), rng.random::<f64>()];
+        let (old, new) = (before.score(&x), tree.score(&x));
+        if tree.leaf_region(&x) == before.leaf_region(&x) {
+            assert_eq!(old, new);
+            kept += 1;
+        } else {
+            assert!((new - old).abs() <= 0.5 * old, "{old} -> {new}");
+            merged += 1;
+        }
+    }
+    assert!(merged > 0 && kept > 0, "{merged} merged, {kept} kept");
+}
+
+#[test]
+fn pooled_mass_scoring_shrugs_off_a_degenerate_tree() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(131);
+    let mut forest = HalfSpaceTrees::new(19, 6, &bounds, &mut rng);
+    for _ in 0..3000 {
+        let x: Vec<f64> = (0..2).map(|_| rng.random_range(0.3..0.7)).collect();
+        forest.insert(&x);
+    }
+    let inliers: Vec<Vec<f64>> = (0..50)
+        .map(|_| (0..2).map(|_| rng.random_range(0.4..0.6)).collect())
+        .collect();
+    let scores =
+        |forest: &HalfSpaceTrees| -> Vec<f64> { inliers.iter().map(|x| forest.score(x)).collect() };
+    let standard = scores(&forest);
+    forest.set_score_method(ScoreMethod::PooledMass);
+    let pooled = scores(&forest);
+
+    // An untrained tree sees every inlier in an empty leaf.
+    forest.add_tree(HalfSpaceTree::new(6, &bounds, &mut rng));
+    let pooled_after = scores(&forest);
+    forest.set_score_method(ScoreMethod::Standard);
+    let standard_after = scores(&forest);
+
+    let max_rel_change = |a: &[f64], b: &[f64]| -> f64 {
+        a.iter()
+            .zip(b)
+            .map(|(a, b)| (b - a).abs() / a)
+            .fold(0.0, f64::max)
+    };
+    let pooled_change = max_rel_change(&pooled, &pooled_after);
+    let standard_change = max_rel_change(&standard, &standard_after);
+    assert!(pooled_change < 0.2, "{pooled_change}");
+    assert!(standard_change > 10.0 * pooled_change, "{standard_change}");
+}
+
+#[test]
+fn score_trace_reproduces_the_score_exactly() {
+    let bounds = vec![(0.0, 1.0); 3];
+    let mut rng = StdRng::seed_from_u64(132);
+    let mut forest = HalfSpaceTrees::new(12, 5, &bounds, &mut rng);
+    for _ in 0..2000 {
+        let x: Vec<f64> = (0..3).map(|_| rng.random_range(0.2..0.8)).collect();
+        forest.insert(&x);
+    }
+    let holdout: Vec<Vec<f64>> = (0..100)
+        .map(|_| (0..3).map(|_| rng.random::<f64>()).collect())
+        .collect();
+    forest.reweight_by_holdout(&holdout);
+    let replay = |trace: &ScoreTrace| -> f64 {
+        let raw = match trace.aggregation {
+            ScoreAggregation::WeightedMean => {
+                let (mut s, mut w) = (0.0, 0.0);
+                for t in &trace.trees {
+                    assert_eq!(t.rarity * t.depth_factor * t.spread_factor, t.score);
+                    s += t.weight * t.score;
+                    w += t.weight;
+                }
+                s / w
+            }
+            ScoreAggregation::TrimmedLeafMass { mean_mass, .. } => 1.0 / (1.0 + mean_mass.max(0.0)),
+        };
+        assert_eq!(raw, trace.raw);
+        match trace.quantum {
+            Some(q) => (raw / q).round() * q,
+            None => raw,
+        }
+    };
+
+    let configs: [&dyn Fn(&mut HalfSpaceTrees); 4] = [
+        &|_| {},
+        &|f| f.set_depth_weighting(DepthWeighting::Harmonic),
+        &|f| {
+            f.set_score_method(ScoreMethod::VarianceAware);
+            f.set_score_quantum(Some(1e-4));
+        },
+        &|f| f.set_score_method(ScoreMethod::PooledMass),
+    ];
+    for configure in configs {
+        configure(&mut forest);
+        for x in &holdout {
+            let trace = forest.trace_score(x);
+            assert_eq!(replay(&trace), forest.score(x));
+            assert_eq!(trace.score, forest.score(x));
+            for (t, tree) in trace.trees.iter().zip(forest.trees()) {
+                assert_eq!(t.path.len() as u32, t.leaf_depth);
+                assert!(t.path.iter().all(|d| d.left == (d.value < d.threshold)));
+                assert_eq!(t.leaf_mass, tree.leaf_mass(x));
+                assert_eq!(t.score, tree.score(x));
+            }
+        }
+    }
+    assert!(matches!(
+        forest.trace_score(&holdout[0]).aggregation,
+        ScoreAggregation::TrimmedLeafMass { trimmed: 1, .. }
+    ));
+
+    let mut pooled = HalfSpaceTrees::new_pooled_mass(8, 5, &bounds, 64, &mut rng);
+    for x in &holdout {
+        pooled.insert(x);
+    }
+    for x in &holdout {
+        assert_eq!(replay(&pooled.trace_score(x)), pooled.score(x));
+    }
+}
+
+#[test]
+fn merged_trees_keep_leaf_bookkeeping_consistent() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut rng = StdRng::seed_from_u64(137);
+    let mut tree = HalfSpaceTree::new(6, &bounds, &mut rng);
+    for _ in 0..5000 {
+        tree.insert(&[rng.random::<f64>(), rng.random::<f64>()]);
+    }
+    tree.merge_similar_leaves(0.3);
+    let probe: Vec<[f64; 2]> = (0..2000)
+        .map(|_| [rng.random::<f64>(), rng.random::<f64>()])
+        .collect();
+    assert!(
+        probe
+            .iter()
+            .any(|x| tree.path(x).len() < 6 && tree.leaf_mass(x) > 0.0)
+    );
+
+    // Cached paths end at the merged leaves and replay like inserts.
+    let mut replayed = tree.clone();
+    for x in &probe[..200] {
+        tree.insert(x);
+        replayed.insert_path(&replayed.path(x), 1.0);
+    }
+    assert_eq!(tree.leaf_masses(), replayed.leaf_masses());
+
+    // Payloads stay on the leaf they were attached to.
+    let mut tagged = tree.clone().with_leaf_meta::<usize>();
+    tagged.set_leaf_meta(&probe[0], 7);
+    for x in &probe {
+        let same_leaf = tagged.leaf_region(x) == tagged.leaf_region(&probe[0]);
+        assert_eq!(tagged.leaf_meta(x).is_some(), same_leaf);
+    }
+
+    let mut forest = HalfSpaceTrees::new(4, 6, &bounds, &mut rng);
+    forest.add_tree(tree);
+    forest.enable_source_tracking(2);
+    for (i, x) in probe.iter().enumerate() {
+        forest.insert_tagged(x, (i % 2) as u16);
+    }
+    let snapshot = forest.leaf_masses();
+    forest.insert(&probe[0]);
+    assert!((forest.recent_activity(&probe[0], &snapshot) - 1.0).abs() < 1e-12);
+    for x in &probe {
+        let by_source: f64 = forest.source_mass(x).iter().map(|(_, m)| m).sum();
+        let mean_leaf: f64 = forest.trees().iter().map(|t| t.leaf_mass(x)).sum::<f64>() / 5.0;
+        assert!((by_source - mean_leaf).abs() < 1e-9);
+    }
+
+    assert!(matches!(
+        forest.to_flat_f32(),
+        Err(HstError::InvalidLayout(_))
+    ));
+}
+
+#[test]
+#[should_panic(expected = "path runs past a leaf")]
+fn insert_path_rejects_paths_through_merged_leaves() {
+    let bounds = [(0.0, 1.0); 2];
+    let mut tree = HalfSpaceTree::new(4, &bounds, &mut StdRng::seed_from_u64(135));
+    tree.merge_similar_leaves(0.0);
+    tree.insert_path(&[true, true, true, true], 1.0);
+}
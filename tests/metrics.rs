@@ -0,0 +1,22 @@
+#![cfg(feature = "metrics")]
+
+use half_space_trees::HalfSpaceTrees;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+#[test]
+fn timing_counters_increment() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut forest = HalfSpaceTrees::new(5, 6, &bounds, &mut StdRng::seed_from_u64(1));
+    assert_eq!(forest.timing_stats().inserts, 0);
+
+    for _ in 0..10 {
+        forest.insert(&[0.2, 0.3]);
+    }
+    forest.score(&[0.2, 0.3]);
+
+    let stats = forest.timing_stats();
+    assert_eq!(stats.inserts, 10);
+    assert_eq!(stats.scores, 1);
+    assert!(stats.insert_nanos > 0);
+}
@@ -0,0 +1,50 @@
+#![cfg(feature = "mmap")]
+
+use half_space_trees::{HalfSpaceTrees, HstError, MmapForest, SplitMode};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+#[test]
+fn mapped_scores_match_in_memory_forest() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let mut forest = HalfSpaceTrees::new(12, 7, &[(0.0, 1.0); 3], &mut rng);
+    for _ in 0..2000 {
+        forest.insert(&[rng.random(), rng.random::<f64>().powi(2), 0.5]);
+    }
+    let path = std::env::temp_dir().join(format!("hst-mmap-{}.bin", std::process::id()));
+    forest.save_flat(&path).unwrap();
+
+    let mapped = MmapForest::open(&path).unwrap();
+    let (blob, layout) = forest.to_flat_f32().unwrap();
+    assert_eq!(mapped.layout(), layout);
+    // The file holds `f32` splits, so compare with the forest rebuilt from the same blob.
+    let reloaded = HalfSpaceTrees::from_flat_f32(&blob, layout).unwrap();
+    for _ in 0..500 {
+        let x = [rng.random(), rng.random(), rng.random()];
+        assert!((mapped.score(&x) - reloaded.score(&x)).abs() < 1e-12);
+    }
+    drop(mapped);
+
+    // Truncated or foreign files are rejected.
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::write(&path, &bytes[..bytes.len() - 4]).unwrap();
+    assert!(matches!(
+        MmapForest::open(&path),
+        Err(HstError::InvalidLayout(_))
+    ));
+    std::fs::write(&path, [0u8; 64]).unwrap();
+    assert!(MmapForest::open(&path).is_err());
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(MmapForest::open(&path), Err(HstError::Io(_))));
+}
+
+#[test]
+fn oblique_forests_are_not_saved() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let forest =
+        HalfSpaceTrees::with_split_mode(3, 4, &[(0.0, 1.0); 3], SplitMode::Oblique, &mut rng);
+    let path = std::env::temp_dir().join(format!("hst-oblique-{}.bin", std::process::id()));
+    let err = forest.save_flat(&path).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    assert!(!path.exists());
+}
@@ -0,0 +1,238 @@
+#![cfg(feature = "onnx")]
+
+use half_space_trees::{DepthWeighting, HalfSpaceTrees, SplitMode};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+
+/// One decoded protobuf field value.
+#[derive(Debug, Clone)]
+enum Value {
+    Varint(u64),
+    Fixed32(u32),
+    Bytes(Vec<u8>),
+}
+
+/// Decode a protobuf message into `(field, value)` pairs, failing on malformed input.
+fn decode(mut buf: &[u8]) -> Vec<(u64, Value)> {
+    fn varint(buf: &mut &[u8]) -> u64 {
+        let (mut v, mut shift) = (0u64, 0);
+        loop {
+            let (&b, rest) = buf.split_first().expect("truncated varint");
+            *buf = rest;
+            v |= ((b & 0x7f) as u64) << shift;
+            if b < 0x80 {
+                return v;
+            }
+            shift += 7;
+            assert!(shift < 64, "varint too long");
+        }
+    }
+    let mut fields = Vec::new();
+    while !buf.is_empty() {
+        let tag = varint(&mut buf);
+        let value = match tag & 7 {
+            0 => Value::Varint(varint(&mut buf)),
+            2 => {
+                let len = varint(&mut buf) as usize;
+                let (bytes, rest) = buf.split_at(len);
+                buf = rest;
+                Value::Bytes(bytes.to_vec())
+            }
+            5 => {
+                let (bytes, rest) = buf.split_at(4);
+                buf = rest;
+                Value::Fixed32(u32::from_le_bytes(bytes.try_into().unwrap()))
+            }
+            t => panic!("unexpected wire type {t}"),
+        };
+        fields.push((tag >> 3, value));
+    }
+    fields
+}
+
+fn messages(fields: &[(u64, Value)], field: u64) -> Vec<Vec<(u64, Value)>> {
+    fields
+        .iter()
+        .filter(|(f, _)| *f == field)
+        .map(|(_, v)| match v {
+            Value::Bytes(b) => decode(b),
+            v => panic!("field {field} is not a message: {v:?}"),
+        })
+        .collect()
+}
+
+fn string(fields: &[(u64, Value)], field: u64) -> String {
+    strings(fields, field).pop().unwrap_or_default()
+}
+
+fn strings(fields: &[(u64, Value)], field: u64) -> Vec<String> {
+    fields
+        .iter()
+        .filter(|(f, _)| *f == field)
+        .map(|(_, v)| match v {
+            Value::Bytes(b) => String::from_utf8(b.clone()).unwrap(),
+            v => panic!("field {field} is not a string: {v:?}"),
+        })
+        .collect()
+}
+
+fn ints(fields: &[(u64, Value)], field: u64) -> Vec<u64> {
+    fields
+        .iter()
+        .filter_map(|(f, v)| match v {
+            Value::Varint(i) if *f == field => Some(*i),
+            _ => None,
+        })
+        .collect()
+}
+
+fn floats(fields: &[(u64, Value)], field: u64) -> Vec<f32> {
+    fields
+        .iter()
+        .filter_map(|(f, v)| match v {
+            Value::Fixed32(bits) if *f == field => Some(f32::from_bits(*bits)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Attributes of a `TreeEnsembleRegressor` node, by name.
+struct Attributes(HashMap<String, Vec<(u64, Value)>>);
+
+impl Attributes {
+    fn ints(&self, name: &str) -> Vec<u64> {
+        ints(&self.0[name], 8)
+    }
+
+    fn floats(&self, name: &str) -> Vec<f32> {
+        floats(&self.0[name], 7)
+    }
+
+    fn strings(&self, name: &str) -> Vec<String> {
+        strings(&self.0[name], 9)
+    }
+
+    fn string(&self, name: &str) -> String {
+        string(&self.0[name], 4)
+    }
+}
+
+/// Reference evaluation of `TreeEnsembleRegressor` with `SUM` aggregation, following the
+/// ONNX-ML operator spec: inputs are `float`, and `BRANCH_LT` takes the true branch when
+/// `x[feature] < value`. No ONNX runtime is available to the test suite, so this stands
+/// in for one.
+fn run(attrs: &Attributes, x: &[f64]) -> f32 {
+    let (tree_ids, node_ids) = (attrs.ints("nodes_treeids"), attrs.ints("nodes_nodeids"));
+    let (features, values) = (attrs.ints("nodes_featureids"), attrs.floats("nodes_values"));
+    let modes = attrs.strings("nodes_modes");
+    let (true_ids, false_ids) = (
+        attrs.ints("nodes_truenodeids"),
+        attrs.ints("nodes_falsenodeids"),
+    );
+    let index: HashMap<(u64, u64), usize> = tree_ids
+        .iter()
+        .zip(&node_ids)
+        .enumerate()
+        .map(|(i, (&t, &n))| ((t, n), i))
+        .collect();
+    let weights: HashMap<(u64, u64), f32> = attrs
+        .ints("target_treeids")
+        .into_iter()
+        .zip(attrs.ints("target_nodeids"))
+        .zip(attrs.floats("target_weights"))
+        .collect();
+    let mut trees: Vec<u64> = tree_ids.clone();
+    trees.dedup();
+    let mut total = 0.0f32;
+    for t in trees {
+        let mut i = index[&(t, 0)];
+        while modes[i] == "BRANCH_LT" {
+            let next = if (x[features[i] as usize] as f32) < values[i] {
+                true_ids[i]
+            } else {
+                false_ids[i]
+            };
+            i = index[&(t, next)];
+        }
+        assert_eq!(modes[i], "LEAF");
+        total += weights[&(t, node_ids[i])];
+    }
+    total
+}
+
+fn load(bytes: &[u8], n_dims: u64) -> Attributes {
+    let model = decode(bytes);
+    assert_eq!(ints(&model, 1), vec![8], "ir_version");
+    let opsets: Vec<(String, u64)> = messages(&model, 8)
+        .iter()
+        .map(|o| (string(o, 1), ints(o, 2)[0]))
+        .collect();
+    assert!(opsets.iter().any(|(d, _)| d == "ai.onnx.ml"), "{opsets:?}");
+
+    let graphs = messages(&model, 7);
+    assert_eq!(graphs.len(), 1);
+    let graph = &graphs[0];
+    let nodes = messages(graph, 1);
+    assert_eq!(nodes.len(), 1);
+    let node = &nodes[0];
+    assert_eq!(string(node, 4), "TreeEnsembleRegressor");
+    assert_eq!(string(node, 7), "ai.onnx.ml");
+    assert_eq!(strings(node, 1), vec!["X"]);
+    assert_eq!(strings(node, 2), vec!["Y"]);
+
+    // Graph input X: float[N, n_dims]
+    let input = &messages(graph, 11)[0];
+    assert_eq!(string(input, 1), "X");
+    let tensor = &messages(&messages(input, 2)[0], 1)[0];
+    assert_eq!(ints(tensor, 1), vec![1]);
+    let dims = messages(&messages(tensor, 2)[0], 1);
+    assert_eq!(string(&dims[0], 2), "N");
+    assert_eq!(ints(&dims[1], 1), vec![n_dims]);
+    assert_eq!(string(&messages(graph, 12)[0], 1), "Y");
+
+    Attributes(
+        messages(node, 5)
+            .into_iter()
+            .map(|a| (string(&a, 1), a))
+            .collect(),
+    )
+}
+
+#[test]
+fn onnx_model_matches_scores() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let mut forest = HalfSpaceTrees::new(15, 6, &[(0.0, 1.0); 3], &mut rng);
+    for _ in 0..1000 {
+        let x: Vec<f64> = (0..3).map(|_| rng.random_range(0.2..0.6)).collect();
+        forest.insert(&x);
+    }
+    forest.set_depth_weighting(DepthWeighting::Harmonic);
+
+    let attrs = load(&forest.to_onnx(), 3);
+    assert_eq!(attrs.string("aggregate_function"), "SUM");
+    assert_eq!(attrs.string("post_transform"), "NONE");
+    assert_eq!(ints(&attrs.0["n_targets"], 3), vec![1]);
+    assert_eq!(attrs.ints("nodes_nodeids").len(), 15 * 127);
+    assert_eq!(attrs.floats("target_weights").len(), 15 * 64);
+
+    let mut mismatches = 0;
+    for _ in 0..500 {
+        let x: Vec<f64> = (0..3).map(|_| rng.random()).collect();
+        let (expected, got) = (forest.score(&x), run(&attrs, &x) as f64);
+        if (expected - got).abs() > 1e-5 * expected.max(1.0) {
+            mismatches += 1;
+        }
+    }
+    // Only points within `f32` rounding of a split may route differently.
+    assert!(mismatches <= 1, "{mismatches} mismatches");
+}
+
+#[test]
+#[should_panic(expected = "oblique splits cannot be exported")]
+fn oblique_forests_cannot_be_exported() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let forest =
+        HalfSpaceTrees::with_split_mode(3, 4, &[(0.0, 1.0); 3], SplitMode::Oblique, &mut rng);
+    forest.to_onnx();
+}
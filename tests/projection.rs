@@ -0,0 +1,56 @@
+use half_space_trees::{HalfSpaceTrees, ProjectedForest, RandomProjection};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+#[test]
+fn projection_is_deterministic_per_seed() {
+    let x: Vec<f64> = (0..40).map(|i| i as f64 / 40.0).collect();
+    let a = RandomProjection::gaussian(40, 6, 9);
+    assert_eq!(a, RandomProjection::gaussian(40, 6, 9));
+    assert_ne!(a, RandomProjection::gaussian(40, 6, 10));
+    assert_eq!(
+        a.project(&x),
+        RandomProjection::gaussian(40, 6, 9).project(&x)
+    );
+
+    let s = RandomProjection::sparse_sign(40, 6, 9);
+    assert_eq!(
+        s.project(&x),
+        RandomProjection::sparse_sign(40, 6, 9).project(&x)
+    );
+    assert_eq!(s.project(&x).len(), 6);
+}
+
+#[test]
+fn outliers_survive_projection() {
+    let (in_dims, out_dims) = (60, 6);
+    let mut rng = StdRng::seed_from_u64(4);
+    for projection in [
+        RandomProjection::gaussian(in_dims, out_dims, 1),
+        RandomProjection::sparse_sign(in_dims, out_dims, 1),
+    ] {
+        let train: Vec<Vec<f64>> = (0..1000)
+            .map(|_| (0..in_dims).map(|_| rng.random_range(0.45..0.55)).collect())
+            .collect();
+        let projected: Vec<Vec<f64>> = train.iter().map(|x| projection.project(x)).collect();
+        // Train on the projected sample directly, then wrap for raw-space queries.
+        let forest = HalfSpaceTrees::auto(&projected, 1.0, &mut rng);
+        let mut model = ProjectedForest::new(projection, forest);
+        model.insert(&train[0]);
+        let inlier = vec![0.5; in_dims];
+        let mut outlier = inlier.clone();
+        outlier[..10].iter_mut().for_each(|v| *v = 1.0);
+        assert!(model.score(&outlier) > model.score(&inlier));
+
+        let full = model
+            .projection()
+            .projected_bounds(&vec![(0.0, 1.0); in_dims]);
+        let corner = model.projection().project(&vec![1.0; in_dims]);
+        assert!(
+            corner
+                .iter()
+                .zip(&full)
+                .all(|(v, (lo, hi))| lo - 1e-9 <= *v && *v <= hi + 1e-9)
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use half_space_trees::RiverCompat;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+// River's scoring rule, worked through by hand for `n_trees=1, height=1, window_size=4`:
+// the scores depend only on which side of the single split each point falls, and padding
+// keeps every split inside [0.15, 0.85]. These are not recorded river outputs.
+#[test]
+fn follows_river_scoring_on_a_single_split() {
+    let mut model = RiverCompat::new(1, 1, 4, &[(0.0, 1.0)], &mut StdRng::seed_from_u64(1));
+    for _ in 0..3 {
+        model.insert(&[0.05]);
+    }
+    assert_eq!(model.score(&[0.05]), 0.0);
+    model.insert(&[0.05]);
+
+    // Seen side: 4 * 2^0 + 4 * 2^1 = 12 of a maximum 1 * 4 * (2^2 - 1) = 12.
+    assert!((model.score(&[0.05]) - 0.0).abs() < 1e-12);
+    // Unseen side: the walk stops at the empty leaf after 4 * 2^0.
+    assert!((model.score(&[0.95]) - 2.0 / 3.0).abs() < 1e-12);
+}
+
+// River's `HalfSpaceTrees(n_trees=3, height=3, window_size=10)` on a stream of the two
+// corners of the unit square. Padded splits never reach a region's edge, so a corner
+// point takes the outermost branch of every split in every tree: its path masses, and so
+// its score, are the same for any seed. The expected values are worked through by hand
+// from river's `score_one`; they are not recorded river outputs.
+#[test]
+fn follows_river_scoring_on_a_deeper_forest() {
+    let (low, high) = ([0.0, 0.0], [1.0, 1.0]);
+    for seed in 0..5 {
+        let mut model =
+            RiverCompat::new(3, 3, 10, &[(0.0, 1.0); 2], &mut StdRng::seed_from_u64(seed));
+        for i in 0..10 {
+            model.insert(if i < 7 { &low } else { &high });
+        }
+        // Per tree, out of a maximum 10 * (2^4 - 1) = 150:
+        // low walks 10 + 7 * (2 + 4 + 8) = 108, high walks 10 + 3 * (2 + 4 + 8) = 52.
+        assert!((model.score(&low) - (1.0 - 108.0 / 150.0)).abs() < 1e-12);
+        assert!((model.score(&high) - (1.0 - 52.0 / 150.0)).abs() < 1e-12);
+
+        // A window without `high`: its walk stops below the root, where the reference
+        // mass 0 is under the 0.1 * 10 cut-off.
+        for _ in 0..10 {
+            model.insert(&low);
+        }
+        assert!((model.score(&low) - (1.0 - 150.0 / 150.0)).abs() < 1e-12);
+        assert!((model.score(&high) - (1.0 - 10.0 / 150.0)).abs() < 1e-12);
+    }
+}
+
+#[test]
+fn window_swap_replaces_the_reference() {
+    let bounds = vec![(0.0, 1.0); 3];
+    let mut model = RiverCompat::new(10, 8, 50, &bounds, &mut StdRng::seed_from_u64(2));
+    for _ in 0..50 {
+        model.insert(&[0.1, 0.1, 0.1]);
+    }
+    let (a, b) = ([0.1, 0.1, 0.1], [0.9, 0.9, 0.9]);
+    assert!(model.score(&a) < model.score(&b));
+    // The next window's data only counts once that window completes.
+    for _ in 0..49 {
+        model.insert(&b);
+    }
+    assert!(model.score(&a) < model.score(&b));
+    model.insert(&b);
+    assert!(model.score(&a) > model.score(&b));
+}
+
+#[test]
+fn blended_window_score_has_no_jump_at_swap() {
+    let bounds = vec![(0.0, 1.0); 2];
+    let mut model = RiverCompat::new(10, 6, 100, &bounds, &mut StdRng::seed_from_u64(3));
+    let (a, b) = ([0.2, 0.2], [0.8, 0.8]);
+    for _ in 0..100 {
+        model.insert(&a);
+    }
+    // Track the score of `b` while a window of `b` fills up and becomes the reference.
+    let (mut plain, mut blended) = (Vec::new(), Vec::new());
+    for _ in 0..150 {
+        model.insert(&b);
+        plain.push(model.score(&b));
+        blended.push(model.score_blended_window(&b, 1.0));
+    }
+    let max_step = |s: &[f64]| {
+        s.windows(2)
+            .map(|w| (w[1] - w[0]).abs())
+            .fold(0.0, f64::max)
+    };
+    assert!(max_step(&plain) > 0.5, "{}", max_step(&plain));
+    // Left: one small step where the blended mass first reaches the 0.1 * window cut-off.
+    assert!(max_step(&blended) < 0.15, "{}", max_step(&blended));
+    // Both agree once the reference holds the `b` window and nothing has moved since.
+    assert!((plain[99] - blended[99]).abs() < 1e-12);
+    assert_eq!(model.score_blended_window(&b, 0.0), model.score(&b));
+}
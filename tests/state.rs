@@ -0,0 +1,198 @@
+use half_space_trees::{HalfSpaceTrees, HstError, ScoreMethod, StreamingState, WeightSchedule};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// A forest whose trees survive the `f32` round trip exactly: splits are already `f32`
+/// and masses stay small integers as long as nothing decays.
+fn flat_forest(seed: u64) -> HalfSpaceTrees {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let forest = HalfSpaceTrees::new(10, 6, &[(0.0, 1.0); 3], &mut rng);
+    let (blob, layout) = forest.to_flat_f32().unwrap();
+    HalfSpaceTrees::from_flat_f32(&blob, layout).unwrap()
+}
+
+fn configure(forest: &mut HalfSpaceTrees, probe: &[Vec<f64>]) {
+    forest.enable_frequency_sketch(64, 4, 0.1);
+    forest.set_score_quantum(Some(1e-6));
+    forest.set_weight_schedule(WeightSchedule::Decreasing { scale: 50.0 });
+    forest.set_surprise_floor(0.5);
+    let scores: Vec<f64> = probe.iter().map(|x| forest.score(x)).collect();
+    let labels: Vec<bool> = (0..probe.len()).map(|i| i % 4 == 0).collect();
+    forest.fit_isotonic(&scores, &labels);
+}
+
+#[test]
+fn restored_state_resumes_identically() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let stream: Vec<Vec<f64>> = (0..600)
+        .map(|_| (0..3).map(|_| rng.random::<f64>()).collect())
+        .collect();
+    let (head, tail) = stream.split_at(300);
+
+    let mut uninterrupted = flat_forest(7);
+    configure(&mut uninterrupted, &stream[..40]);
+    let mut first = flat_forest(7);
+    configure(&mut first, &stream[..40]);
+    for x in head {
+        uninterrupted.process_smoothed(x, 0.2);
+        first.process_smoothed(x, 0.2);
+        uninterrupted.combined_severity(x, 0.01);
+        first.combined_severity(x, 0.01);
+    }
+    uninterrupted.reweight_by_holdout(&stream[..40]);
+    first.reweight_by_holdout(&stream[..40]);
+    let weights = uninterrupted.tree_weights().to_vec();
+    assert!(weights.iter().any(|&w| w != weights[0]));
+
+    // Restart: reload the trees and the streaming state into a fresh process.
+    let (blob, layout) = first.to_flat_f32().unwrap();
+    let bytes = first.export_state().to_bytes();
+    drop(first);
+    let mut resumed = HalfSpaceTrees::from_flat_f32(&blob, layout).unwrap();
+    resumed
+        .import_state(StreamingState::from_bytes(&bytes).unwrap())
+        .unwrap();
+
+    assert_eq!(resumed.tree_weights(), &weights[..]);
+    assert_eq!(
+        resumed.last_insert_impact(),
+        uninterrupted.last_insert_impact()
+    );
+    for x in tail {
+        assert_eq!(
+            resumed.process_smoothed(x, 0.2),
+            uninterrupted.process_smoothed(x, 0.2)
+        );
+        assert_eq!(resumed.probability(x), uninterrupted.probability(x));
+        assert_eq!(
+            resumed.combined_severity(x, 0.01),
+            uninterrupted.combined_severity(x, 0.01)
+        );
+        // Fractional masses pass through `f32`, so only the floor is restored exactly.
+        assert!((resumed.surprise(x) - uninterrupted.surprise(x)).abs() < 1e-6);
+        assert_eq!(
+            resumed.estimated_frequency(x),
+            uninterrupted.estimated_frequency(x)
+        );
+    }
+    assert_eq!(resumed.dimension_drift(), uninterrupted.dimension_drift());
+}
+
+#[test]
+fn malformed_state_is_rejected() {
+    let mut forest = flat_forest(2);
+    let bytes = forest.export_state().to_bytes();
+    assert!(matches!(
+        StreamingState::from_bytes(&bytes[..bytes.len() - 8]),
+        Err(HstError::InvalidState(_))
+    ));
+    assert!(StreamingState::from_bytes(&[bytes.as_slice(), &[0; 8]].concat()).is_err());
+    assert!(StreamingState::from_bytes(&[0; 16]).is_err());
+    // Blobs from other format versions name the version instead of failing midway.
+    let mut old = bytes.clone();
+    old[0] = b'2';
+    match StreamingState::from_bytes(&old) {
+        Err(HstError::InvalidState(msg)) => assert!(msg.contains("version 2"), "{msg}"),
+        other => panic!("{other:?}"),
+    }
+
+    let mut rng = StdRng::seed_from_u64(3);
+    let other = HalfSpaceTrees::new(2, 3, &[(0.0, 1.0); 5], &mut rng);
+    assert!(forest.import_state(other.export_state()).is_err());
+    let state = StreamingState::from_bytes(&bytes).unwrap();
+    assert_eq!(state.n_dims(), 3);
+    assert!(forest.import_state(state).is_ok());
+
+    // Drop one tree's entry from the last insert impact (ten unit impacts after one
+    // insert, written after the identical-looking uniform weights), leaving the tree
+    // weights intact.
+    forest.insert(&[0.5, 0.5, 0.5]);
+    let bytes = forest.export_state().to_bytes();
+    let impact: Vec<u8> = std::iter::once(10u64.to_le_bytes())
+        .chain(std::iter::repeat_n(1f64.to_le_bytes(), 10))
+        .flatten()
+        .collect();
+    let at = bytes
+        .windows(impact.len())
+        .rposition(|w| w == impact.as_slice())
+        .unwrap();
+    let mut short = bytes[..at].to_vec();
+    short.extend_from_slice(&9u64.to_le_bytes());
+    short.extend_from_slice(&bytes[at + 16..]);
+    let state = StreamingState::from_bytes(&short).unwrap();
+    assert!(matches!(
+        forest.import_state(state),
+        Err(HstError::InvalidState(msg)) if msg.contains("impact")
+    ));
+}
+
+#[test]
+fn pooled_masses_survive_a_flat_round_trip() {
+    let mut rng = StdRng::seed_from_u64(4);
+    let mut forest = HalfSpaceTrees::new_pooled_mass(8, 5, &[(0.0, 1.0); 2], 64, &mut rng);
+    for _ in 0..500 {
+        forest.insert(&[rng.random_range(0.3..0.7), rng.random_range(0.3..0.7)]);
+    }
+    let (blob, layout) = forest.to_flat_f32().unwrap();
+    let bytes = forest.export_state().to_bytes();
+
+    let mut restored = HalfSpaceTrees::from_flat_f32(&blob, layout).unwrap();
+    assert!(restored.pooled_leaf_masses(&[0.5, 0.5]).is_none());
+    restored
+        .import_state(StreamingState::from_bytes(&bytes).unwrap())
+        .unwrap();
+    for _ in 0..200 {
+        let x = [rng.random::<f64>(), rng.random::<f64>()];
+        assert_eq!(
+            restored.pooled_leaf_masses(&x),
+            forest.pooled_leaf_masses(&x)
+        );
+        assert_eq!(restored.score(&x), forest.score(&x));
+    }
+}
+
+#[test]
+fn source_masses_survive_a_state_round_trip() {
+    let mut rng = StdRng::seed_from_u64(5);
+    let mut forest = flat_forest(6);
+    forest.enable_source_tracking(2);
+    for i in 0..300 {
+        let x: Vec<f64> = (0..3).map(|_| rng.random::<f64>()).collect();
+        forest.insert_tagged(&x, [3, 5, 8][i % 3]);
+    }
+    let bytes = forest.export_state().to_bytes();
+    let (blob, layout) = forest.to_flat_f32().unwrap();
+    let mut restored = HalfSpaceTrees::from_flat_f32(&blob, layout).unwrap();
+    restored
+        .import_state(StreamingState::from_bytes(&bytes).unwrap())
+        .unwrap();
+    for _ in 0..100 {
+        let x: Vec<f64> = (0..3).map(|_| rng.random::<f64>()).collect();
+        assert_eq!(restored.source_mass(&x), forest.source_mass(&x));
+    }
+
+    let mut other = flat_forest(6);
+    other.enforce_tree_cap(5);
+    assert!(
+        other
+            .import_state(StreamingState::from_bytes(&bytes).unwrap())
+            .is_err()
+    );
+}
+
+#[test]
+fn pooled_scoring_survives_a_flat_round_trip() {
+    let mut rng = StdRng::seed_from_u64(6);
+    let mut forest = flat_forest(7);
+    for _ in 0..300 {
+        forest.insert(&[rng.random(), rng.random(), rng.random()]);
+    }
+    forest.set_score_method(ScoreMethod::PooledMass);
+    let (blob, layout) = forest.to_flat_f32().unwrap();
+    let mut restored = HalfSpaceTrees::from_flat_f32(&blob, layout).unwrap();
+    restored.import_state(forest.export_state()).unwrap();
+    for _ in 0..50 {
+        let x: Vec<f64> = (0..3).map(|_| rng.random::<f64>()).collect();
+        assert_eq!(restored.score(&x), forest.score(&x));
+    }
+}